@@ -1,3 +1,5 @@
+import { HashAlgo } from '~/plugins/tauri/bindings';
+
 export function useHash() {
   const { $tauriCommands } = useNuxtApp();
 
@@ -5,7 +7,12 @@ export function useHash() {
     return $tauriCommands.hashStrInMd5(content)
   }
 
+  function hashStr(content: string, hashAlgo?: HashAlgo) {
+    return $tauriCommands.hashStr(content, hashAlgo ?? null)
+  }
+
   return {
     hashStrInMd5,
+    hashStr,
   };
 }
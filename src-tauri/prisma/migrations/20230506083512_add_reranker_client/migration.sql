@@ -0,0 +1,34 @@
+-- CreateTable
+CREATE TABLE "RerankerClient" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "name" TEXT NOT NULL,
+    "type" TEXT NOT NULL,
+    "info" TEXT NOT NULL
+);
+
+-- RedefineTables
+PRAGMA foreign_keys=OFF;
+CREATE TABLE "new_IndexProfile" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "name" TEXT NOT NULL,
+    "splittingId" INTEGER NOT NULL,
+    "embeddingsClientId" INTEGER NOT NULL,
+    "embeddingsConfigId" INTEGER NOT NULL,
+    "vectorDbClientId" INTEGER NOT NULL,
+    "vectorDbConfigId" INTEGER NOT NULL,
+    "rerankerClientId" INTEGER,
+    CONSTRAINT "IndexProfile_splittingId_fkey" FOREIGN KEY ("splittingId") REFERENCES "Splitting" ("id") ON DELETE RESTRICT ON UPDATE CASCADE,
+    CONSTRAINT "IndexProfile_embeddingsClientId_fkey" FOREIGN KEY ("embeddingsClientId") REFERENCES "EmbeddingsClient" ("id") ON DELETE RESTRICT ON UPDATE CASCADE,
+    CONSTRAINT "IndexProfile_embeddingsConfigId_fkey" FOREIGN KEY ("embeddingsConfigId") REFERENCES "EmbeddingsConfig" ("id") ON DELETE RESTRICT ON UPDATE CASCADE,
+    CONSTRAINT "IndexProfile_vectorDbClientId_fkey" FOREIGN KEY ("vectorDbClientId") REFERENCES "VectorDbClient" ("id") ON DELETE RESTRICT ON UPDATE CASCADE,
+    CONSTRAINT "IndexProfile_vectorDbConfigId_fkey" FOREIGN KEY ("vectorDbConfigId") REFERENCES "VectorDbConfig" ("id") ON DELETE RESTRICT ON UPDATE CASCADE,
+    CONSTRAINT "IndexProfile_rerankerClientId_fkey" FOREIGN KEY ("rerankerClientId") REFERENCES "RerankerClient" ("id") ON DELETE SET NULL ON UPDATE CASCADE
+);
+INSERT INTO "new_IndexProfile" ("embeddingsClientId", "embeddingsConfigId", "id", "name", "splittingId", "vectorDbClientId", "vectorDbConfigId") SELECT "embeddingsClientId", "embeddingsConfigId", "id", "name", "splittingId", "vectorDbClientId", "vectorDbConfigId" FROM "IndexProfile";
+DROP TABLE "IndexProfile";
+ALTER TABLE "new_IndexProfile" RENAME TO "IndexProfile";
+PRAGMA foreign_key_check;
+PRAGMA foreign_keys=ON;
+
+-- CreateIndex
+CREATE UNIQUE INDEX "RerankerClient_type_info_key" ON "RerankerClient"("type", "info");
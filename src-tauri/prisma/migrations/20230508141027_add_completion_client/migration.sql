@@ -0,0 +1,10 @@
+-- CreateTable
+CREATE TABLE "CompletionClient" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "name" TEXT NOT NULL,
+    "type" TEXT NOT NULL,
+    "info" TEXT NOT NULL
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "CompletionClient_type_info_key" ON "CompletionClient"("type", "info");
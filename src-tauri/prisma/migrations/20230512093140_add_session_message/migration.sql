@@ -0,0 +1,47 @@
+-- CreateTable
+CREATE TABLE "SessionMessage" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "role" TEXT NOT NULL,
+    "content" TEXT NOT NULL,
+    "createTime" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "promptTokens" INTEGER,
+    "completionTokens" INTEGER,
+    "citedChunks" TEXT NOT NULL DEFAULT '[]',
+    "model" TEXT,
+    "sessionId" INTEGER NOT NULL,
+    CONSTRAINT "SessionMessage_sessionId_fkey" FOREIGN KEY ("sessionId") REFERENCES "Session" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE INDEX "SessionMessage_sessionId_id_idx" ON "SessionMessage"("sessionId", "id");
+
+-- MigrateData
+-- Split the history of each session into messages. The history is a json array of dialogues, each
+-- of which is a json string holding the question and the answers as json strings again. Only the
+-- chosen answer of each dialogue is kept.
+INSERT INTO "SessionMessage" ("sessionId", "role", "content")
+SELECT "sessionId", "role", "content" FROM (
+    SELECT
+        s."id" AS "sessionId",
+        CAST(d."key" AS INTEGER) AS "dialogueNo",
+        0 AS "turn",
+        'user' AS "role",
+        json_extract(json_extract(d."value", '$.question'), '$.message.text') AS "content"
+    FROM "Session" s, json_each(CASE WHEN json_valid(s."history") THEN s."history" ELSE '[]' END) d
+    WHERE json_valid(d."value") AND json_valid(json_extract(d."value", '$.question'))
+    UNION ALL
+    SELECT
+        s."id" AS "sessionId",
+        CAST(d."key" AS INTEGER) AS "dialogueNo",
+        1 AS "turn",
+        'assistant' AS "role",
+        json_extract(
+            json_extract(d."value", '$.answers[' || json_extract(d."value", '$.chosen') || ']'),
+            '$.message.text'
+        ) AS "content"
+    FROM "Session" s, json_each(CASE WHEN json_valid(s."history") THEN s."history" ELSE '[]' END) d
+    WHERE json_valid(d."value")
+        AND json_valid(json_extract(d."value", '$.answers[' || json_extract(d."value", '$.chosen') || ']'))
+)
+WHERE "content" IS NOT NULL AND "content" <> ''
+ORDER BY "sessionId", "dialogueNo", "turn";
@@ -0,0 +1,47 @@
+-- RedefineTables
+PRAGMA foreign_keys=OFF;
+CREATE TABLE "new_SessionMessage" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "role" TEXT NOT NULL,
+    "content" TEXT NOT NULL,
+    "createTime" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "promptTokens" INTEGER,
+    "completionTokens" INTEGER,
+    "citedChunks" TEXT NOT NULL DEFAULT '[]',
+    "model" TEXT,
+    "sessionId" INTEGER NOT NULL,
+    "parentId" INTEGER,
+    CONSTRAINT "SessionMessage_sessionId_fkey" FOREIGN KEY ("sessionId") REFERENCES "Session" ("id") ON DELETE CASCADE ON UPDATE CASCADE,
+    CONSTRAINT "SessionMessage_parentId_fkey" FOREIGN KEY ("parentId") REFERENCES "SessionMessage" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+INSERT INTO "new_SessionMessage" ("citedChunks", "completionTokens", "content", "createTime", "id", "model", "promptTokens", "role", "sessionId") SELECT "citedChunks", "completionTokens", "content", "createTime", "id", "model", "promptTokens", "role", "sessionId" FROM "SessionMessage";
+DROP TABLE "SessionMessage";
+ALTER TABLE "new_SessionMessage" RENAME TO "SessionMessage";
+CREATE INDEX "SessionMessage_sessionId_id_idx" ON "SessionMessage"("sessionId", "id");
+CREATE INDEX "SessionMessage_parentId_idx" ON "SessionMessage"("parentId");
+CREATE TABLE "new_Session" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "name" TEXT NOT NULL,
+    "history" TEXT NOT NULL,
+    "indexId" TEXT NOT NULL,
+    "activeMessageId" INTEGER,
+    CONSTRAINT "Session_indexId_fkey" FOREIGN KEY ("indexId") REFERENCES "CollectionIndex" ("id") ON DELETE CASCADE ON UPDATE CASCADE,
+    CONSTRAINT "Session_activeMessageId_fkey" FOREIGN KEY ("activeMessageId") REFERENCES "SessionMessage" ("id") ON DELETE SET NULL ON UPDATE CASCADE
+);
+INSERT INTO "new_Session" ("history", "id", "indexId", "name") SELECT "history", "id", "indexId", "name" FROM "Session";
+DROP TABLE "Session";
+ALTER TABLE "new_Session" RENAME TO "Session";
+CREATE UNIQUE INDEX "Session_activeMessageId_key" ON "Session"("activeMessageId");
+PRAGMA foreign_key_check;
+PRAGMA foreign_keys=ON;
+
+-- MigrateData
+-- Existing messages form a single branch per session: chain each message to the previous one and
+-- make the last one active.
+UPDATE "SessionMessage" SET "parentId" = (
+    SELECT MAX(m."id") FROM "SessionMessage" m
+    WHERE m."sessionId" = "SessionMessage"."sessionId" AND m."id" < "SessionMessage"."id"
+);
+UPDATE "Session" SET "activeMessageId" = (
+    SELECT MAX(m."id") FROM "SessionMessage" m WHERE m."sessionId" = "Session"."id"
+);
@@ -0,0 +1,3 @@
+-- AlterTable
+ALTER TABLE "Session" ADD COLUMN "summarizedMessageId" INTEGER;
+ALTER TABLE "Session" ADD COLUMN "summary" TEXT NOT NULL DEFAULT '';
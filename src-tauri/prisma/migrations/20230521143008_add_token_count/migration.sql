@@ -0,0 +1,5 @@
+-- AlterTable
+ALTER TABLE "DocumentChunk" ADD COLUMN "tokenCount" INTEGER;
+
+-- AlterTable
+ALTER TABLE "SessionMessage" ADD COLUMN "tokenCount" INTEGER;
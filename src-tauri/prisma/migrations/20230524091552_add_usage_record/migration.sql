@@ -0,0 +1,17 @@
+-- CreateTable
+CREATE TABLE "UsageRecord" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "createTime" DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    "kind" TEXT NOT NULL,
+    "model" TEXT NOT NULL,
+    "promptTokens" INTEGER NOT NULL,
+    "completionTokens" INTEGER NOT NULL,
+    "cost" REAL NOT NULL,
+    "embeddingsClientId" INTEGER,
+    "completionClientId" INTEGER,
+    "indexProfileId" INTEGER,
+    "sessionId" INTEGER
+);
+
+-- CreateIndex
+CREATE INDEX "UsageRecord_createTime_idx" ON "UsageRecord"("createTime");
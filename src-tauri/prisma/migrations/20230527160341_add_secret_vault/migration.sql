@@ -0,0 +1,6 @@
+-- CreateTable
+CREATE TABLE "SecretVault" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "salt" TEXT NOT NULL,
+    "verifier" TEXT NOT NULL
+);
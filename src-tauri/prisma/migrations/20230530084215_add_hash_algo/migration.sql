@@ -0,0 +1,8 @@
+-- AlterTable
+ALTER TABLE "Document" ADD COLUMN "hashAlgo" TEXT NOT NULL DEFAULT 'md5';
+
+-- AlterTable
+ALTER TABLE "DocumentChunk" ADD COLUMN "hashAlgo" TEXT NOT NULL DEFAULT 'md5';
+
+-- AlterTable
+ALTER TABLE "EmbeddingVectorsOnDocumentChunks" ADD COLUMN "hashAlgo" TEXT NOT NULL DEFAULT 'md5';
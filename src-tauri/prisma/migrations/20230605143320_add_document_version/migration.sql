@@ -0,0 +1,29 @@
+-- CreateTable
+CREATE TABLE "DocumentVersion" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "lineageId" INTEGER NOT NULL,
+    "no" INTEGER NOT NULL,
+    "note" TEXT NOT NULL DEFAULT '',
+    "createTime" DATETIME NOT NULL,
+    "documentId" INTEGER NOT NULL,
+    CONSTRAINT "DocumentVersion_documentId_fkey" FOREIGN KEY ("documentId") REFERENCES "Document" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateTable
+CREATE TABLE "CollectionIndexVersionPin" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "lineageId" INTEGER NOT NULL,
+    "indexId" TEXT NOT NULL,
+    "documentId" INTEGER NOT NULL,
+    CONSTRAINT "CollectionIndexVersionPin_indexId_fkey" FOREIGN KEY ("indexId") REFERENCES "CollectionIndex" ("id") ON DELETE CASCADE ON UPDATE CASCADE,
+    CONSTRAINT "CollectionIndexVersionPin_documentId_fkey" FOREIGN KEY ("documentId") REFERENCES "Document" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- CreateIndex
+CREATE UNIQUE INDEX "DocumentVersion_documentId_key" ON "DocumentVersion"("documentId");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "DocumentVersion_lineageId_no_key" ON "DocumentVersion"("lineageId", "no");
+
+-- CreateIndex
+CREATE UNIQUE INDEX "CollectionIndexVersionPin_indexId_lineageId_key" ON "CollectionIndexVersionPin"("indexId", "lineageId");
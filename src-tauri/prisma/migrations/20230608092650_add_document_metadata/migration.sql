@@ -0,0 +1,14 @@
+-- CreateTable
+CREATE TABLE "DocumentMetadata" (
+    "title" TEXT,
+    "authors" TEXT NOT NULL DEFAULT '[]',
+    "year" INTEGER,
+    "venue" TEXT,
+    "doi" TEXT,
+    "arxivId" TEXT,
+    "abstractText" TEXT,
+    "keywords" TEXT NOT NULL DEFAULT '[]',
+    "updateTime" DATETIME NOT NULL,
+    "documentId" INTEGER NOT NULL PRIMARY KEY,
+    CONSTRAINT "DocumentMetadata_documentId_fkey" FOREIGN KEY ("documentId") REFERENCES "Document" ("id") ON DELETE CASCADE ON UPDATE CASCADE
+);
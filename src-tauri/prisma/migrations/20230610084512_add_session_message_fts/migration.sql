@@ -0,0 +1,28 @@
+-- CreateVirtualTable
+-- An external-content fts5 index over the content of session messages, which prisma cannot model.
+-- It may already have been created when it was set up at startup.
+CREATE VIRTUAL TABLE IF NOT EXISTS "SessionMessageFts" USING fts5(
+    content, content='SessionMessage', content_rowid='id', tokenize='unicode61'
+);
+
+-- CreateTrigger
+CREATE TRIGGER IF NOT EXISTS "SessionMessageFts_ai" AFTER INSERT ON "SessionMessage" BEGIN
+    INSERT INTO "SessionMessageFts" (rowid, content) VALUES (new.id, new.content);
+END;
+
+-- CreateTrigger
+CREATE TRIGGER IF NOT EXISTS "SessionMessageFts_ad" AFTER DELETE ON "SessionMessage" BEGIN
+    INSERT INTO "SessionMessageFts" ("SessionMessageFts", rowid, content)
+        VALUES ('delete', old.id, old.content);
+END;
+
+-- CreateTrigger
+CREATE TRIGGER IF NOT EXISTS "SessionMessageFts_au" AFTER UPDATE OF content ON "SessionMessage" BEGIN
+    INSERT INTO "SessionMessageFts" ("SessionMessageFts", rowid, content)
+        VALUES ('delete', old.id, old.content);
+    INSERT INTO "SessionMessageFts" (rowid, content) VALUES (new.id, new.content);
+END;
+
+-- MigrateData
+-- Index the messages written before the index existed.
+INSERT INTO "SessionMessageFts" ("SessionMessageFts") VALUES ('rebuild');
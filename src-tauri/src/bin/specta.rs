@@ -1,6 +1,14 @@
 extern crate app;
 
-use app::commands::{db, fs};
+use app::commands::{
+    backups, bibliography, collection_archive, completion, config_bundle, connection_test, db,
+    export, fs, maintenance, qa, retrieval, secrets, tokenizer, vectors, workspaces,
+};
+use app::core::client_types::{
+    CompletionClientInfo, EmbeddingsClientInfo, EmbeddingsConfigMeta, RerankerClientInfo,
+    VectorDbClientInfo, VectorDbConfigMeta,
+};
+use specta::{DefOpts, Type, TypeDefs};
 
 fn main() {
     generate_tauri_specta_bindings("./plugins/tauri/bindings.ts")
@@ -20,15 +28,43 @@ fn main() {
 fn generate_tauri_specta_bindings<P: AsRef<std::path::Path>>(
     export_path: P,
 ) -> Result<(), specta::ts::TsExportError> {
+    // the payloads of clients and configs are taken flattened by the commands, so they are
+    // exported explicitly for the frontend to refer to them by name
+    let mut type_map = TypeDefs::default();
+    add_type::<EmbeddingsClientInfo>(&mut type_map)?;
+    add_type::<EmbeddingsConfigMeta>(&mut type_map)?;
+    add_type::<VectorDbClientInfo>(&mut type_map)?;
+    add_type::<VectorDbConfigMeta>(&mut type_map)?;
+    add_type::<RerankerClientInfo>(&mut type_map)?;
+    add_type::<CompletionClientInfo>(&mut type_map)?;
+
     tauri_specta::ts::export(
         specta::collect_types![
+            type_map: type_map,
             db::splittings::get_or_create_splitting,
             db::splittings::get_or_create_splitting_id,
+            db::splittings::get_splittings,
+            db::splittings::delete_splitting,
             db::documents::get_documents,
             db::documents::get_documents_by_collection_id,
             db::documents::get_or_create_document,
             db::documents::add_documents,
             db::documents::delete_document,
+            db::documents::verify_documents,
+            db::documents::relink_document,
+            db::documents::link_document,
+            db::documents::unlink_document,
+            db::documents::sync_linked_documents,
+            db::document_versions::add_document_version,
+            db::document_versions::list_document_versions,
+            db::document_versions::diff_document_versions,
+            db::document_versions::pin_document_version,
+            db::document_versions::unpin_document_version,
+            db::document_versions::get_documents_by_collection_index_id,
+            db::document_metadata::get_document_metadata,
+            db::document_metadata::upsert_document_metadata,
+            db::document_metadata::delete_document_metadata,
+            db::document_metadata::extract_document_metadata,
             db::collections_on_documents::delete_collection_on_documents,
             db::collections_on_documents::delete_documents_in_collection,
             db::collections_on_documents::add_documents_to_collection,
@@ -42,19 +78,31 @@ fn generate_tauri_specta_bindings<P: AsRef<std::path::Path>>(
             db::vector_db_clients::get_vector_db_clients,
             db::vector_db_clients::create_vector_db_client,
             db::vector_db_clients::upsert_vector_db_client,
+            db::vector_db_clients::delete_vector_db_client,
             db::vector_db_configs::get_vector_db_config_by_id,
             db::vector_db_configs::get_vector_db_configs,
             db::vector_db_configs::create_vector_db_config,
             db::vector_db_configs::upsert_vector_db_config,
+            db::vector_db_configs::delete_vector_db_config,
             db::embeddings_clients::get_embeddings_clients,
             db::embeddings_clients::get_embeddings_client_by_id,
             db::embeddings_clients::create_embeddings_client,
             db::embeddings_clients::upsert_embeddings_client,
+            db::embeddings_clients::delete_embeddings_client,
             db::embeddings_configs::get_embeddings_configs_by_client_type,
             db::embeddings_configs::get_embeddings_config_by_id,
             db::embeddings_configs::get_embeddings_configs,
             db::embeddings_configs::create_embeddings_config,
             db::embeddings_configs::upsert_embeddings_config,
+            db::embeddings_configs::delete_embeddings_config,
+            db::reranker_clients::get_reranker_clients,
+            db::reranker_clients::get_reranker_client_by_id,
+            db::reranker_clients::create_reranker_client,
+            db::reranker_clients::upsert_reranker_client,
+            db::completion_clients::get_completion_clients,
+            db::completion_clients::get_completion_client_by_id,
+            db::completion_clients::create_completion_client,
+            db::completion_clients::upsert_completion_client,
             db::document_chunks::get_chunk_md5hashes_by_documents_and_splitting,
             db::document_chunks::get_document_chunks,
             db::document_chunks::create_chunks_by_document,
@@ -67,22 +115,83 @@ fn generate_tauri_specta_bindings<P: AsRef<std::path::Path>>(
             db::index_profiles::get_index_profile_by_id,
             db::index_profiles::create_index_profile_with_all,
             db::index_profiles::create_index_profile,
+            db::index_profiles::set_index_profile_reranker,
+            db::index_profiles::update_index_profile,
+            db::index_profiles::delete_index_profile,
             db::collection_indexes::delete_collection_indexes_by_id,
             db::collection_indexes::get_collection_indexes_by_collection_id,
             db::collection_indexes::get_collection_indexes_by_collection_id_with_all,
             db::collection_indexes::get_collection_index_by_collection_id_profile_id_with_all,
             db::collection_indexes::get_collection_index_by_id,
+            db::collection_indexes::get_collection_index_by_id_with_all,
             db::collection_indexes::create_collection_index,
             db::collection_indexes::upsert_documents_in_collection_index,
             db::collection_indexes::remove_documents_from_collection_index,
             db::sessions::delete_session_by_id,
             db::sessions::delete_sessions_by_index_id,
             db::sessions::get_sessions_by_index_id,
+            db::sessions::search_sessions,
             db::sessions::get_sessions,
             db::sessions::create_session,
             db::sessions::update_session,
-            fs::hash_str_in_md5
+            db::session_messages::get_session_messages,
+            db::session_messages::append_session_message,
+            db::session_messages::update_session_message,
+            db::session_messages::fork_session_message,
+            db::session_messages::list_session_branches,
+            db::session_messages::switch_session_branch,
+            db::session_messages::delete_session_message,
+            db::usage_records::get_usage_records,
+            db::usage_records::aggregate_usage,
+            fs::hash_str_in_md5,
+            fs::hash_str,
+            retrieval::retrieve_from_collection_index,
+            vectors::embed_texts_for_collection_index,
+            vectors::prepare_collection_index_vectors,
+            vectors::upsert_collection_index_vectors,
+            vectors::delete_collection_index_vectors,
+            vectors::query_collection_index_vectors,
+            completion::start_completion,
+            completion::cancel_completion,
+            qa::ask_collection,
+            export::export_session,
+            tokenizer::count_tokens,
+            tokenizer::count_chat_tokens,
+            secrets::get_secrets_status,
+            secrets::set_secrets_passphrase,
+            secrets::unlock_secrets,
+            secrets::lock_secrets,
+            connection_test::test_embeddings_client,
+            connection_test::test_vector_db_client,
+            config_bundle::export_config_bundle,
+            config_bundle::import_config_bundle,
+            collection_archive::export_collection,
+            collection_archive::import_collection,
+            bibliography::import_bibtex,
+            bibliography::export_collection_bibtex,
+            bibliography::export_session_bibtex,
+            backups::create_backup,
+            backups::list_backups,
+            backups::restore_backup,
+            workspaces::list_workspaces,
+            workspaces::create_workspace,
+            workspaces::switch_workspace,
+            maintenance::check_database,
+            maintenance::repair_database,
+            maintenance::vacuum_database
         ],
         export_path,
     )
 }
+
+/// Add a type, along with the types it refers to, to the types to be exported.
+fn add_type<T: Type>(type_map: &mut TypeDefs) -> Result<(), specta::ExportError> {
+    T::reference(
+        DefOpts {
+            parent_inline: false,
+            type_map,
+        },
+        &[],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use crate::commands::db::DbState;
+use crate::core::backup::{self, BackupInfo, BackupKind};
+use crate::core::workspace;
+
+/// Back up the database of the workspace while the app keeps running.
+#[tauri::command]
+#[specta::specta]
+pub async fn create_backup(db: DbState<'_>) -> crate::Result<BackupInfo> {
+    let db_path = db.db_path();
+    tokio::task::spawn_blocking(move || backup::create(&db_path, BackupKind::Manual)).await?
+}
+
+/// List the backups of the database of the workspace, the newest first.
+#[tauri::command]
+#[specta::specta]
+pub async fn list_backups(db: DbState<'_>) -> crate::Result<Vec<BackupInfo>> {
+    let db_path = db.db_path();
+    tokio::task::spawn_blocking(move || backup::list(&db_path)).await?
+}
+
+/// Restore the database of the workspace from a backup while the app keeps running.
+///
+/// The current database is backed up first, so that the restoring can be undone. The schema of
+/// the restored database is brought up to date, since the backup may be made by an older version.
+#[tauri::command]
+#[specta::specta]
+pub async fn restore_backup(db: DbState<'_>, filename: String) -> crate::Result<()> {
+    let (db, db_path) = db.active_with_db_path();
+    tokio::task::spawn_blocking(move || {
+        backup::create(&db_path, BackupKind::PreRestore)?;
+        backup::rotate(&db_path, BackupKind::PreRestore, backup::KEEP_PRE_RESTORE)?;
+        backup::restore(&db_path, &filename)
+    })
+    .await??;
+    workspace::prepare(db).await
+}
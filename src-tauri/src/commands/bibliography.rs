@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use specta::Type;
+
+use crate::commands::db::collections_on_documents::add_to_collection;
+use crate::commands::db::documents::{create_document, CreateDocumentData};
+use crate::commands::db::session_messages;
+use crate::commands::db::DbState;
+use crate::core::bibliography::{self, BibDocument, BibEntry, BibFormat};
+use crate::core::document_metadata::{self, Metadata};
+use crate::core::fs::{hash_file, HashAlgo};
+use crate::core::result::Error;
+use crate::prisma::{collection, collections_on_documents, document, PrismaClient};
+
+document::include!(document_with_metadata { metadata });
+
+#[derive(Serialize, Type, Debug)]
+pub struct BibImportReport {
+    /// The documents of the entries, created or found stored already, in the order of the file.
+    pub documents: Vec<document::Data>,
+    /// Keys of the entries whose files are not found locally, which are left out.
+    #[serde(rename = "missingFiles")]
+    pub missing_files: Vec<String>,
+    /// The entries failed to import, which are left out.
+    pub failed: Vec<FailedBibEntry>,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct FailedBibEntry {
+    pub key: String,
+    pub error: String,
+}
+
+/// Import the entries of a BibTeX (.bib) or RIS (.ris) file at `path` into a collection.
+///
+/// Each entry is imported with the local pdf of its `file` field, with relative paths resolved
+/// against the folder of the file. A pdf stored already is reused rather than stored again. The
+/// metadata of the entry takes the place of the one extracted from the pdf, field by field.
+///
+/// Each document is added to the collection once imported, and an entry failed to import is
+/// reported rather than failing the others.
+#[tauri::command]
+#[specta::specta]
+pub async fn import_bibtex(
+    db: DbState<'_>,
+    path: String,
+    collection_id: i32,
+) -> crate::Result<BibImportReport> {
+    let (db, uploaded_dir) = db.active_with_upload_dir();
+    db.collection()
+        .find_unique(collection::id::equals(collection_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Collection {} not found", collection_id)))?;
+    let path = PathBuf::from(path);
+    let content = tokio::fs::read_to_string(&path).await?;
+    let entries = bibliography::parse(&content, BibFormat::of_path(&path))?;
+    let bib_dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+    let mut documents = vec![];
+    let mut missing_files = vec![];
+    let mut failed = vec![];
+    for entry in entries {
+        let filepath = match entry.local_pdf(&bib_dir) {
+            Some(filepath) => filepath,
+            None => {
+                missing_files.push(entry.key);
+                continue;
+            }
+        };
+        let key = entry.key.clone();
+        match import_entry(db, &uploaded_dir, collection_id, entry, &filepath).await {
+            Ok(doc) => documents.push(doc),
+            Err(err) => failed.push(FailedBibEntry {
+                key,
+                error: err.to_string(),
+            }),
+        }
+    }
+    Ok(BibImportReport {
+        documents,
+        missing_files,
+        failed,
+    })
+}
+
+/// Import an entry with its local pdf at `filepath` into a collection.
+async fn import_entry(
+    db: &PrismaClient,
+    uploaded_dir: &Path,
+    collection_id: i32,
+    entry: BibEntry,
+    filepath: &Path,
+) -> crate::Result<document::Data> {
+    let doc = match find_stored(db, filepath).await? {
+        Some(doc) => doc,
+        None => {
+            let filename = filepath
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| entry.key.clone());
+            create_document(
+                db,
+                uploaded_dir,
+                CreateDocumentData::Path {
+                    filename,
+                    filepath: filepath.to_string_lossy().to_string(),
+                },
+            )
+            .await?
+        }
+    };
+    let stored = document_metadata::get(db, doc.id)
+        .await?
+        .unwrap_or_default();
+    document_metadata::save(db, doc.id, entry.metadata.without_invalid().or(stored)).await?;
+    add_to_collection(db, collection_id, vec![doc.id]).await?;
+    Ok(doc)
+}
+
+/// The document stored with the content of a file, if any, under any of the hash algorithms.
+async fn find_stored(db: &PrismaClient, filepath: &Path) -> crate::Result<Option<document::Data>> {
+    for hash_algo in HashAlgo::ALL {
+        let hash = hash_file(hash_algo, filepath).await?;
+        let doc = db
+            .document()
+            .find_first(vec![
+                document::md_5_hash::equals(hash),
+                document::hash_algo::equals(hash_algo.as_str().to_string()),
+            ])
+            .exec()
+            .await?;
+        if doc.is_some() {
+            return Ok(doc);
+        }
+    }
+    Ok(None)
+}
+
+/// Export the documents of a collection as a BibTeX file at `path`.
+#[tauri::command]
+#[specta::specta]
+pub async fn export_collection_bibtex(
+    db: DbState<'_>,
+    collection_id: i32,
+    path: String,
+) -> crate::Result<()> {
+    let db = db.active();
+    let document_ids = db
+        .collections_on_documents()
+        .find_many(vec![collections_on_documents::collection_id::equals(
+            collection_id,
+        )])
+        .exec()
+        .await?
+        .into_iter()
+        .map(|rel| rel.document_id)
+        .collect();
+    write_bibtex(db, document_ids, path).await
+}
+
+/// Export the documents cited in the active branch of a session as a BibTeX file at `path`, in
+/// the order they are first cited.
+#[tauri::command]
+#[specta::specta]
+pub async fn export_session_bibtex(
+    db: DbState<'_>,
+    session_id: i32,
+    path: String,
+) -> crate::Result<()> {
+    let db = db.active();
+    let messages = session_messages::active_branch(db, session_id, None, None).await?;
+    let mut seen = HashSet::new();
+    let document_ids = messages
+        .iter()
+        .flat_map(|message| &message.cited_chunks)
+        .map(|chunk_ref| chunk_ref.document_id)
+        .filter(|id| seen.insert(*id))
+        .collect();
+    write_bibtex(db, document_ids, path).await
+}
+
+async fn write_bibtex(
+    db: &PrismaClient,
+    document_ids: Vec<i32>,
+    path: String,
+) -> crate::Result<()> {
+    let docs = db
+        .document()
+        .find_many(vec![document::id::in_vec(document_ids.clone())])
+        .include(document_with_metadata::include())
+        .exec()
+        .await?;
+    // in the order of the ids, rather than the one of the database
+    let mut documents = vec![];
+    for id in document_ids {
+        if let Some(doc) = docs.iter().find(|doc| doc.id == id) {
+            documents.push(BibDocument {
+                filename: doc.filename.clone(),
+                filepath: doc
+                    .source_path
+                    .clone()
+                    .unwrap_or_else(|| doc.filepath.clone()),
+                metadata: doc.metadata.as_ref().map(Metadata::from_data).transpose()?,
+            });
+        }
+    }
+    tokio::fs::write(path, bibliography::write_bibtex(&documents)).await?;
+    Ok(())
+}
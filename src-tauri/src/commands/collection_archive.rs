@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use crate::commands::db::documents::prepare_upload_folder;
+use crate::commands::db::DbState;
+use crate::core::collection_archive::{self, CollectionArchive, CollectionImportReport};
+
+/// Long enough for merging the chunks and embeddings of a large collection.
+const IMPORT_TIMEOUT_MS: u64 = 10 * 60 * 1000;
+
+/// Export a collection to a zip archive at `path`, with its document files, chunks, cached
+/// embeddings, index profiles and sessions.
+///
+/// The secrets of the clients are stripped, so that the archive can be handed to others.
+#[tauri::command]
+#[specta::specta]
+pub async fn export_collection(
+    db: DbState<'_>,
+    collection_id: i32,
+    path: String,
+) -> crate::Result<()> {
+    let db = db.active();
+    let archive = collection_archive::export(db, collection_id).await?;
+    tokio::task::spawn_blocking(move || archive.write(&PathBuf::from(path))).await?
+}
+
+/// Import a collection archive at `path`, merging it into the local database.
+///
+/// The document files are extracted into the upload folder first, and the rows are merged in a
+/// transaction, so that nothing is left half imported on errors.
+#[tauri::command]
+#[specta::specta]
+pub async fn import_collection(
+    db: DbState<'_>,
+    path: String,
+) -> crate::Result<CollectionImportReport> {
+    let (db, upload_dir) = db.active_with_upload_dir();
+    prepare_upload_folder(&upload_dir).await?;
+    let archive = tokio::task::spawn_blocking(move || {
+        CollectionArchive::read(&PathBuf::from(path), &upload_dir)
+    })
+    .await??;
+    db._transaction()
+        .with_timeout(IMPORT_TIMEOUT_MS)
+        .run(|tx| async move { collection_archive::import(&tx, archive).await })
+        .await
+}
@@ -0,0 +1,119 @@
+use serde::Serialize;
+use specta::Type;
+use tauri::Manager;
+
+use crate::commands::db::DbState;
+use crate::core::completion::{ChatMessage, Completion, CompletionConfig, CompletionTasks};
+
+/// Event emitted for every token delta of a completion.
+pub const COMPLETION_DELTA_EVENT: &str = "completion://delta";
+/// Event emitted once a completion finishes, fails or gets cancelled.
+pub const COMPLETION_DONE_EVENT: &str = "completion://done";
+
+#[derive(Serialize, Type, Clone)]
+pub struct CompletionDeltaPayload {
+    #[serde(rename = "sessionId")]
+    pub session_id: i32,
+    #[serde(rename = "taskId")]
+    pub task_id: u32,
+    pub delta: String,
+}
+
+#[derive(Serialize, Type, Clone)]
+pub struct CompletionDonePayload {
+    #[serde(rename = "sessionId")]
+    pub session_id: i32,
+    /// The id of the completion, as returned by `start_completion`.
+    #[serde(rename = "taskId")]
+    pub task_id: u32,
+    pub content: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Start a streaming chat completion for a session.
+///
+/// The completion runs in the background so that it survives navigating away from the session.
+/// Token deltas are emitted as `completion://delta` events, and the whole answer (or the error)
+/// as a `completion://done` event. Starting a new completion for the same session cancels the
+/// previous one, whose `completion://done` event still comes, with the error of cancelling.
+///
+/// Returns the id of the completion, which the events of the completion carry as `taskId`.
+#[tauri::command]
+#[specta::specta]
+pub async fn start_completion(
+    app: tauri::AppHandle,
+    db: DbState<'_>,
+    tasks: tauri::State<'_, CompletionTasks>,
+    session_id: i32,
+    messages: Vec<ChatMessage>,
+    config: CompletionConfig,
+) -> crate::Result<u32> {
+    let db = db.active();
+    let completion = Completion::from_config(db, config).await?;
+    let task = tasks.register(session_id);
+    let task_id = task.id;
+
+    tauri::async_runtime::spawn(async move {
+        let tasks = app.state::<CompletionTasks>();
+        let result = tasks
+            .run(
+                task,
+                completion.stream(&messages, |delta| {
+                    let _ = app.emit_all(
+                        COMPLETION_DELTA_EVENT,
+                        CompletionDeltaPayload {
+                            session_id,
+                            task_id,
+                            delta: delta.to_string(),
+                        },
+                    );
+                }),
+            )
+            .await;
+        if let Err(err) = completion.record_usage(db, Some(session_id)).await {
+            log::error!(
+                "error while recording usage of session {}: {}",
+                session_id,
+                err
+            );
+        }
+        let _ = app.emit_all(
+            COMPLETION_DONE_EVENT,
+            done_payload(session_id, task_id, result),
+        );
+    });
+    Ok(task_id)
+}
+
+/// Cancel the completion in progress of a session.
+///
+/// Returns `false` if there is no completion in progress.
+#[tauri::command]
+#[specta::specta]
+pub async fn cancel_completion(
+    tasks: tauri::State<'_, CompletionTasks>,
+    session_id: i32,
+) -> crate::Result<bool> {
+    Ok(tasks.cancel(session_id))
+}
+
+pub(crate) fn done_payload<E: ToString>(
+    session_id: i32,
+    task_id: u32,
+    result: Result<String, E>,
+) -> CompletionDonePayload {
+    match result {
+        Ok(content) => CompletionDonePayload {
+            session_id,
+            task_id,
+            content: Some(content),
+            error: None,
+        },
+        Err(err) => CompletionDonePayload {
+            session_id,
+            task_id,
+            content: None,
+            error: Some(err.to_string()),
+        },
+    }
+}
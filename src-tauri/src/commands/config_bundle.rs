@@ -0,0 +1,40 @@
+use crate::commands::db::DbState;
+use crate::core::config_bundle::{self, ConfigBundleFormat, ConfigImportReport, ConflictStrategy};
+
+/// Export the clients, configs, splittings and index profiles to a bundle file at `path`.
+///
+/// Secrets are written in plain text unless `strip_secrets` is set, in which case whoever imports
+/// the bundle fills in their own.
+#[tauri::command]
+#[specta::specta]
+pub async fn export_config_bundle(
+    db: DbState<'_>,
+    path: String,
+    format: ConfigBundleFormat,
+    strip_secrets: bool,
+) -> crate::Result<()> {
+    let db = db.active();
+    let bundle = config_bundle::export(db, strip_secrets, None).await?;
+    tokio::fs::write(path, format.write(&bundle)?).await?;
+    Ok(())
+}
+
+/// Import a bundle file at `path` in `format`, the one it was exported in.
+///
+/// Everything is imported in a transaction, so that nothing is left half imported on errors.
+#[tauri::command]
+#[specta::specta]
+pub async fn import_config_bundle(
+    db: DbState<'_>,
+    path: String,
+    format: ConfigBundleFormat,
+    on_conflict: ConflictStrategy,
+) -> crate::Result<ConfigImportReport> {
+    let db = db.active();
+    let text = tokio::fs::read_to_string(&path).await?;
+    let bundle = format.read(&text)?;
+    db._transaction()
+        .run(|tx| async move { config_bundle::import(&tx, bundle, on_conflict).await })
+        .await
+        .map(|(report, _)| report)
+}
@@ -0,0 +1,40 @@
+use crate::commands::db::DbState;
+use crate::core::connection_test::{self, ConnectionReport};
+use crate::core::result::Error;
+use crate::prisma::{embeddings_client, vector_db_client};
+
+/// Test an embeddings client by embedding a short text, reporting the latency, the dimension of
+/// the model and what went wrong if the call fails.
+#[tauri::command]
+#[specta::specta]
+pub async fn test_embeddings_client(
+    db: DbState<'_>,
+    client_id: i32,
+) -> crate::Result<ConnectionReport> {
+    let db = db.active();
+    let client = db
+        .embeddings_client()
+        .find_unique(embeddings_client::id::equals(client_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Embeddings client {} not found", client_id)))?;
+    Ok(connection_test::test_embeddings_client(db, &client).await)
+}
+
+/// Test a vector db client by describing its index, reporting the latency, the dimension and
+/// the metric of the index and what went wrong if the call fails.
+#[tauri::command]
+#[specta::specta]
+pub async fn test_vector_db_client(
+    db: DbState<'_>,
+    client_id: i32,
+) -> crate::Result<ConnectionReport> {
+    let db = db.active();
+    let client = db
+        .vector_db_client()
+        .find_unique(vector_db_client::id::equals(client_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Vector db client {} not found", client_id)))?;
+    Ok(connection_test::test_vector_db_client(&client).await)
+}
@@ -2,24 +2,40 @@ use serde::Deserialize;
 use specta::Type;
 
 use crate::commands::db::DbState;
+use crate::core::secrets;
 use crate::prisma::{
     collection, collection_index, collection_index_on_document, document, index_profile,
 };
 
 collection_index::include!(collection_index_with_all {
     index: include {
-        embeddings_client embeddings_config vector_db_client vector_db_config splitting
+        embeddings_client embeddings_config vector_db_client vector_db_config reranker_client splitting
     }
     collection
     indexed_documents
 });
 
+impl collection_index_with_all::Data {
+    /// Redact the secrets of the clients, which are only used by the backend.
+    fn redacted(mut self) -> Self {
+        let profile = &mut self.index;
+        profile.embeddings_client.info =
+            secrets::redact_stored_info(&profile.embeddings_client.info);
+        profile.vector_db_client.info = secrets::redact_stored_info(&profile.vector_db_client.info);
+        if let Some(reranker_client) = profile.reranker_client.as_mut() {
+            reranker_client.info = secrets::redact_stored_info(&reranker_client.info);
+        }
+        self
+    }
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn delete_collection_indexes_by_id(
     db: DbState<'_>,
     collection_index_ids: Vec<String>,
 ) -> crate::Result<i32> {
+    let db = db.active();
     Ok(db
         .collection_index()
         .delete_many(vec![collection_index::id::in_vec(collection_index_ids)])
@@ -34,6 +50,7 @@ pub async fn remove_documents_from_collection_index(
     index_id: String,
     document_ids: Vec<i32>,
 ) -> crate::Result<i32> {
+    let db = db.active();
     Ok(db
         .collection_index_on_document()
         .delete_many(vec![
@@ -50,6 +67,7 @@ pub async fn get_collection_indexes_by_collection_id(
     db: DbState<'_>,
     collection_id: i32,
 ) -> crate::Result<Vec<collection_index::Data>> {
+    let db = db.active();
     Ok(db
         .collection_index()
         .find_many(vec![collection_index::collection_id::equals(collection_id)])
@@ -63,12 +81,16 @@ pub async fn get_collection_indexes_by_collection_id_with_all(
     db: DbState<'_>,
     collection_id: i32,
 ) -> crate::Result<Vec<collection_index_with_all::Data>> {
+    let db = db.active();
     Ok(db
         .collection_index()
         .find_many(vec![collection_index::collection_id::equals(collection_id)])
         .include(collection_index_with_all::include())
         .exec()
-        .await?)
+        .await?
+        .into_iter()
+        .map(collection_index_with_all::Data::redacted)
+        .collect())
 }
 
 #[tauri::command]
@@ -78,6 +100,7 @@ pub async fn get_collection_index_by_collection_id_profile_id_with_all(
     collection_id: i32,
     index_profile_id: i32,
 ) -> crate::Result<Option<collection_index_with_all::Data>> {
+    let db = db.active();
     Ok(db
         .collection_index()
         .find_unique(collection_index::collection_id_index_id(
@@ -86,7 +109,8 @@ pub async fn get_collection_index_by_collection_id_profile_id_with_all(
         ))
         .include(collection_index_with_all::include())
         .exec()
-        .await?)
+        .await?
+        .map(collection_index_with_all::Data::redacted))
 }
 
 #[tauri::command]
@@ -95,6 +119,7 @@ pub async fn get_collection_index_by_id(
     db: DbState<'_>,
     collection_index_id: String,
 ) -> crate::Result<Option<collection_index::Data>> {
+    let db = db.active();
     Ok(db
         .collection_index()
         .find_unique(collection_index::id::equals(collection_index_id))
@@ -102,6 +127,22 @@ pub async fn get_collection_index_by_id(
         .await?)
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn get_collection_index_by_id_with_all(
+    db: DbState<'_>,
+    collection_index_id: String,
+) -> crate::Result<Option<collection_index_with_all::Data>> {
+    let db = db.active();
+    Ok(db
+        .collection_index()
+        .find_unique(collection_index::id::equals(collection_index_id))
+        .include(collection_index_with_all::include())
+        .exec()
+        .await?
+        .map(collection_index_with_all::Data::redacted))
+}
+
 #[derive(Deserialize, Type)]
 pub struct CreateCollectionIndexData {
     name: String,
@@ -117,6 +158,7 @@ pub async fn create_collection_index(
     db: DbState<'_>,
     data: CreateCollectionIndexData,
 ) -> crate::Result<collection_index::Data> {
+    let db = db.active();
     Ok(db
         .collection_index()
         .create(
@@ -136,6 +178,7 @@ pub async fn upsert_documents_in_collection_index(
     id: String,
     document_ids: Vec<i32>,
 ) -> crate::Result<Vec<collection_index_on_document::Data>> {
+    let db = db.active();
     Ok(db
         ._batch(document_ids.into_iter().map(|document_id| {
             db.collection_index_on_document().upsert(
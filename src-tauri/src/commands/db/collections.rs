@@ -12,6 +12,7 @@ pub async fn delete_collection_by_id(
     db: DbState<'_>,
     collection_id: i32,
 ) -> crate::Result<collection::Data> {
+    let db = db.active();
     Ok(db
         .collection()
         .delete(collection::id::equals(collection_id))
@@ -25,6 +26,7 @@ pub async fn get_collection_by_id(
     db: DbState<'_>,
     collection_id: i32,
 ) -> crate::Result<Option<collection::Data>> {
+    let db = db.active();
     Ok(db
         .collection()
         .find_first(vec![collection::id::equals(collection_id)])
@@ -37,6 +39,7 @@ pub async fn get_collection_by_id(
 pub async fn get_collections_with_indexes(
     db: DbState<'_>,
 ) -> crate::Result<Vec<collection_with_indexes::Data>> {
+    let db = db.active();
     Ok(db
         .collection()
         .find_many(vec![])
@@ -48,6 +51,7 @@ pub async fn get_collections_with_indexes(
 #[tauri::command]
 #[specta::specta]
 pub async fn get_collections(db: DbState<'_>) -> crate::Result<Vec<collection::Data>> {
+    let db = db.active();
     Ok(db.collection().find_many(vec![]).exec().await?)
 }
 
@@ -61,14 +65,13 @@ pub struct CreateCollectionData {
 #[tauri::command]
 #[specta::specta]
 pub async fn create_collection(
-    app: tauri::AppHandle,
     db: DbState<'_>,
     data: CreateCollectionData,
 ) -> crate::Result<collection::Data> {
+    let (db, uploaded_dir) = db.active_with_upload_dir();
     let collection = db.collection().create(data.name, vec![]).exec().await?;
     for doc_create_data in data.documents {
-        let doc =
-            documents::get_or_create_document(app.clone(), db.clone(), doc_create_data).await?;
+        let doc = documents::create_document(db, &uploaded_dir, doc_create_data).await?;
         db.collections_on_documents()
             .create(
                 collection::id::equals(collection.id),
@@ -88,6 +91,7 @@ pub async fn update_collection_name(
     collection_id: i32,
     collection_name: String,
 ) -> crate::Result<collection::Data> {
+    let db = db.active();
     Ok(db
         .collection()
         .update(
@@ -1,5 +1,5 @@
 use crate::commands::db::DbState;
-use crate::prisma::{collection, collections_on_documents, document};
+use crate::prisma::{collection, collections_on_documents, document, PrismaClient};
 
 #[tauri::command]
 #[specta::specta]
@@ -7,6 +7,7 @@ pub async fn delete_collection_on_documents(
     db: DbState<'_>,
     collection_id: i32,
 ) -> crate::Result<i32> {
+    let db = db.active();
     Ok(db
         .collections_on_documents()
         .delete_many(vec![collections_on_documents::collection_id::equals(
@@ -23,6 +24,7 @@ pub async fn delete_documents_in_collection(
     collection_id: i32,
     document_ids: Vec<i32>,
 ) -> crate::Result<i32> {
+    let db = db.active();
     Ok(db
         .collections_on_documents()
         .delete_many(vec![
@@ -39,6 +41,15 @@ pub async fn add_documents_to_collection(
     db: DbState<'_>,
     collection_id: i32,
     document_ids: Vec<i32>,
+) -> crate::Result<Vec<collections_on_documents::Data>> {
+    add_to_collection(db.active(), collection_id, document_ids).await
+}
+
+/// Add documents to a collection, as `add_documents_to_collection` does.
+pub(crate) async fn add_to_collection(
+    db: &PrismaClient,
+    collection_id: i32,
+    document_ids: Vec<i32>,
 ) -> crate::Result<Vec<collections_on_documents::Data>> {
     Ok(db
         ._batch(
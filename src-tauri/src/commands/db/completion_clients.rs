@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::commands::db::DbState;
+use crate::core::client_types::{self, ClientPayload, CompletionClientInfo};
+use crate::core::secrets::{self, ClientKind};
+use crate::prisma::completion_client;
+
+#[derive(Serialize, Type)]
+pub struct CompletionClientExData {
+    id: i32,
+    name: String,
+    r#type: String,
+    info: serde_json::Value,
+}
+
+impl CompletionClientExData {
+    pub fn from_data(data: completion_client::Data) -> crate::Result<Self> {
+        Ok(Self {
+            id: data.id,
+            name: data.name,
+            r#type: data.r#type,
+            info: secrets::redact_info(serde_json::from_str(data.info.as_str())?),
+        })
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_completion_clients(db: DbState<'_>) -> crate::Result<Vec<CompletionClientExData>> {
+    let db = db.active();
+    db.completion_client()
+        .find_many(vec![])
+        .exec()
+        .await
+        .map(|data| {
+            data.into_iter()
+                .map(CompletionClientExData::from_data)
+                .collect::<Result<Vec<_>, _>>()
+        })?
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_completion_client_by_id(
+    db: DbState<'_>,
+    client_id: i32,
+) -> crate::Result<Option<CompletionClientExData>> {
+    let db = db.active();
+    db.completion_client()
+        .find_unique(completion_client::id::equals(client_id))
+        .exec()
+        .await?
+        .map(CompletionClientExData::from_data)
+        .transpose()
+}
+
+#[derive(Deserialize, Type)]
+pub struct CreateCompletionClientData {
+    name: String,
+    #[serde(flatten)]
+    client: CompletionClientInfo,
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn create_completion_client(
+    db: DbState<'_>,
+    data: CreateCompletionClientData,
+) -> crate::Result<CompletionClientExData> {
+    let db = db.active();
+    client_types::validate(&data.name, &data.client)?;
+    let (r#type, info) = data.client.to_stored()?;
+    db._transaction()
+        .run(|tx| async move {
+            secrets::check_duplicate(&tx, ClientKind::Completion, None, &r#type, &info).await?;
+            let info = secrets::seal_info(&tx, info).await?;
+            let client = tx
+                .completion_client()
+                .create(data.name, r#type, info, vec![])
+                .exec()
+                .await?;
+            CompletionClientExData::from_data(client)
+        })
+        .await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn upsert_completion_client(
+    db: DbState<'_>,
+    client_id: i32,
+    data: CreateCompletionClientData,
+) -> crate::Result<CompletionClientExData> {
+    let db = db.active();
+    client_types::validate(&data.name, &data.client)?;
+    let (r#type, mut info) = data.client.to_stored()?;
+    db._transaction()
+        .run(|tx| async move {
+            let stored = tx
+                .completion_client()
+                .find_unique(completion_client::id::equals(client_id))
+                .exec()
+                .await?;
+            if let Some(stored) = stored {
+                secrets::restore_redacted(&mut info, &stored.info)?;
+            }
+            secrets::check_duplicate(&tx, ClientKind::Completion, Some(client_id), &r#type, &info)
+                .await?;
+            let info = secrets::seal_info(&tx, info).await?;
+            let client = tx
+                .completion_client()
+                .upsert(
+                    completion_client::id::equals(client_id),
+                    (data.name.clone(), r#type.clone(), info.clone(), vec![]),
+                    vec![
+                        completion_client::name::set(data.name),
+                        completion_client::r#type::set(r#type),
+                        completion_client::info::set(info),
+                    ],
+                )
+                .exec()
+                .await?;
+            CompletionClientExData::from_data(client)
+        })
+        .await
+}
@@ -3,6 +3,9 @@ use specta::Type;
 
 use crate::commands::db::splittings::GetOrCreateSplittingData;
 use crate::commands::db::{splittings, DbState};
+use crate::core::fs::{hash_bytes, HashAlgo};
+use crate::core::result::Error;
+use crate::core::tokenizer::count_tokens;
 use crate::prisma::{document, document_chunk, splitting};
 
 document_chunk::select!(document_chunk_only_md5hash { md_5_hash });
@@ -14,7 +17,8 @@ pub async fn get_chunk_md5hashes_by_documents_and_splitting(
     document_ids: Vec<i32>,
     splitting: GetOrCreateSplittingData,
 ) -> crate::Result<Vec<document_chunk_only_md5hash::Data>> {
-    let splitting_id = splittings::get_or_create_splitting_id(db.clone(), splitting).await?;
+    let db = db.active();
+    let splitting_id = splittings::splitting_id_of(db, splitting).await?;
 
     Ok(db
         .document_chunk()
@@ -34,7 +38,8 @@ pub async fn get_document_chunks(
     document_id: i32,
     splitting: GetOrCreateSplittingData,
 ) -> crate::Result<Vec<document_chunk::Data>> {
-    let splitting_id = splittings::get_or_create_splitting_id(db.clone(), splitting).await?;
+    let db = db.active();
+    let splitting_id = splittings::splitting_id_of(db, splitting).await?;
 
     Ok(db
         .document_chunk()
@@ -60,25 +65,41 @@ pub struct CreateChunksByDocumentData {
     chunks: Vec<CreateChunkData>,
 }
 
+/// Create the chunks of a document, hashed by the hash algorithm of the document.
+///
+/// The chunks of the documents hashed by md5 stay md5 hashed, so that they keep matching the
+/// embeddings cached for them.
 #[tauri::command]
 #[specta::specta]
 pub async fn create_chunks_by_document(
     db: DbState<'_>,
     data: CreateChunksByDocumentData,
 ) -> crate::Result<Vec<document_chunk::Data>> {
-    let splitting_id = splittings::get_or_create_splitting_id(db.clone(), data.splitting).await?;
+    let db = db.active();
+    let splitting_id = splittings::splitting_id_of(db, data.splitting).await?;
+    let doc = db
+        .document()
+        .find_unique(document::id::equals(data.document_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Document {} not found", data.document_id)))?;
+    let hash_algo = HashAlgo::of_str(&doc.hash_algo)?;
 
     Ok(db
         ._batch(data.chunks.into_iter().enumerate().map(|(no, chunk_data)| {
-            let digest = md5::compute(chunk_data.content.as_str());
+            let hash = hash_bytes(hash_algo, &chunk_data.content);
+            let token_count = count_tokens(&chunk_data.content) as i32;
             db.document_chunk().create(
                 document::id::equals(data.document_id),
                 splitting::id::equals(splitting_id),
                 no as i32,
                 chunk_data.content,
                 chunk_data.metadata,
-                format!("{:x}", digest),
-                vec![],
+                hash,
+                vec![
+                    document_chunk::hash_algo::set(hash_algo.as_str().to_string()),
+                    document_chunk::token_count::set(Some(token_count)),
+                ],
             )
         }))
         .await?)
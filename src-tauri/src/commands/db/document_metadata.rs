@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use crate::commands::db::DbState;
+use crate::core::document_metadata::{self, Metadata};
+use crate::core::result::Error;
+use crate::prisma::document;
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_document_metadata(
+    db: DbState<'_>,
+    document_id: i32,
+) -> crate::Result<Option<Metadata>> {
+    let db = db.active();
+    document_metadata::get(db, document_id).await
+}
+
+/// Save the metadata of a document, replacing the one it has.
+#[tauri::command]
+#[specta::specta]
+pub async fn upsert_document_metadata(
+    db: DbState<'_>,
+    document_id: i32,
+    metadata: Metadata,
+) -> crate::Result<Metadata> {
+    let db = db.active();
+    document_metadata::save(db, document_id, metadata).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_document_metadata(db: DbState<'_>, document_id: i32) -> crate::Result<()> {
+    let db = db.active();
+    document_metadata::delete(db, document_id).await
+}
+
+/// Extract the metadata of a document from its file.
+///
+/// The fields extracted fill the missing ones of the metadata the document has, or replace them
+/// if `overwrite` is set.
+#[tauri::command]
+#[specta::specta]
+pub async fn extract_document_metadata(
+    db: DbState<'_>,
+    document_id: i32,
+    overwrite: bool,
+) -> crate::Result<Metadata> {
+    let db = db.active();
+    let doc = db
+        .document()
+        .find_unique(document::id::equals(document_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Document {} not found", document_id)))?;
+    let filepath = PathBuf::from(doc.filepath);
+    let extracted =
+        tokio::task::spawn_blocking(move || document_metadata::extract(&filepath, &doc.filename))
+            .await??
+            .without_invalid();
+    let current = document_metadata::get(db, document_id)
+        .await?
+        .unwrap_or_default();
+    let metadata = if overwrite {
+        extracted.or(current)
+    } else {
+        current.or(extracted)
+    };
+    document_metadata::save(db, document_id, metadata).await
+}
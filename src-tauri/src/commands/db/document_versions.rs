@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use crate::commands::db::documents::{self, CreateDocumentData};
+use crate::commands::db::DbState;
+use crate::core::document_versions::{self, document_version_with_document, DocumentDiff};
+use crate::core::result::Error;
+use crate::core::text::extract_text;
+use crate::prisma::{collection_index_version_pin, document, document_version};
+
+/// Add a new version of a document, given any of its versions, which replaces the latest version
+/// in its collections.
+#[tauri::command]
+#[specta::specta]
+pub async fn add_document_version(
+    db: DbState<'_>,
+    document_id: i32,
+    data: CreateDocumentData,
+    note: Option<String>,
+) -> crate::Result<document_version_with_document::Data> {
+    let (db, uploaded_dir) = db.active_with_upload_dir();
+    let version = db
+        ._transaction()
+        .run(|tx| async move {
+            // Check the document before storing the new version, so that a wrong one leaves no
+            // document behind.
+            document_versions::version_of(&tx, document_id).await?;
+            let doc = documents::create_document(&tx, &uploaded_dir, data).await?;
+            document_versions::add(&tx, document_id, &doc, note).await
+        })
+        .await?;
+    db.document_version()
+        .find_unique(document_version::id::equals(version.id))
+        .include(document_version_with_document::include())
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Document version {} not found", version.id)))
+}
+
+/// List the versions of a document, given any of its versions, the latest first.
+#[tauri::command]
+#[specta::specta]
+pub async fn list_document_versions(
+    db: DbState<'_>,
+    document_id: i32,
+) -> crate::Result<Vec<document_version_with_document::Data>> {
+    let db = db.active();
+    document_versions::list(db, document_id).await
+}
+
+/// Diff the text extracted from two versions of a document line by line.
+#[tauri::command]
+#[specta::specta]
+pub async fn diff_document_versions(
+    db: DbState<'_>,
+    from_document_id: i32,
+    to_document_id: i32,
+) -> crate::Result<DocumentDiff> {
+    let db = db.active();
+    let mut names = vec![];
+    let mut sources = vec![];
+    for id in [from_document_id, to_document_id] {
+        let doc = db
+            .document()
+            .find_unique(document::id::equals(id))
+            .exec()
+            .await?
+            .ok_or_else(|| Error::msg(format!("Document {} not found", id)))?;
+        let version = document_versions::version_of(db, id).await?;
+        names.push(format!("{} (version {})", doc.filename, version.no));
+        sources.push((PathBuf::from(doc.filepath), doc.filename));
+    }
+    let texts = tokio::task::spawn_blocking(move || {
+        sources
+            .iter()
+            .map(|(filepath, filename)| extract_text(filepath, filename))
+            .collect::<crate::Result<Vec<_>>>()
+    })
+    .await??;
+    Ok(document_versions::diff(
+        &names[0], &texts[0], &names[1], &texts[1],
+    ))
+}
+
+/// Pin a version of a document for a collection index, which indexes it instead of the latest
+/// version once synced.
+#[tauri::command]
+#[specta::specta]
+pub async fn pin_document_version(
+    db: DbState<'_>,
+    collection_index_id: String,
+    document_id: i32,
+) -> crate::Result<collection_index_version_pin::Data> {
+    let db = db.active();
+    document_versions::pin(db, collection_index_id, document_id).await
+}
+
+/// Let a collection index follow the latest version of a document again, given any of its
+/// versions.
+#[tauri::command]
+#[specta::specta]
+pub async fn unpin_document_version(
+    db: DbState<'_>,
+    collection_index_id: String,
+    document_id: i32,
+) -> crate::Result<()> {
+    let db = db.active();
+    document_versions::unpin(db, collection_index_id, document_id).await
+}
+
+/// Get the documents a collection index is to index, which are the documents of its collection
+/// with the versions pinned by the index in place of the latest ones.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_documents_by_collection_index_id(
+    db: DbState<'_>,
+    collection_index_id: String,
+) -> crate::Result<Vec<document::Data>> {
+    let db = db.active();
+    document_versions::documents_of_index(db, collection_index_id).await
+}
@@ -1,13 +1,21 @@
+use std::path::Path;
+
 use serde::Deserialize;
 use specta::Type;
 
 use crate::commands::db::DbState;
-use crate::core::fs::{hash_file_in_md5, hash_in_md5};
-use crate::prisma::{collections_on_documents, document};
+use crate::core::document_metadata;
+use crate::core::fs::{hash_bytes, hash_file, HashAlgo};
+use crate::core::linked_documents::{self, DocumentChange};
+use crate::core::result::Error;
+use crate::core::storage::{self, VerificationReport};
+use crate::core::watcher::DocumentWatcher;
+use crate::prisma::{collections_on_documents, document, PrismaClient};
 
 #[tauri::command]
 #[specta::specta]
 pub async fn get_documents(db: DbState<'_>) -> crate::Result<Vec<document::Data>> {
+    let db = db.active();
     Ok(db.document().find_many(vec![]).exec().await?)
 }
 
@@ -18,6 +26,7 @@ pub async fn get_documents_by_collection_id(
     db: DbState<'_>,
     collection_id: i32,
 ) -> crate::Result<Vec<document::Data>> {
+    let db = db.active();
     let doc_ids = db
         .collections_on_documents()
         .find_many(vec![collections_on_documents::collection_id::equals(
@@ -46,72 +55,85 @@ pub enum CreateDocumentData {
     File { filename: String, content: Vec<u8> },
 }
 
-/// Create a document and store it in `app_local_data_dir/upload` folder.
+/// Create a document and store it in the upload folder of the workspace.
 ///
-/// If the file already exists, it will not be copied again.  The file will be renamed to
-/// `<md5_hash>.<extension>`.
+/// If the file already exists, it will not be copied again.  The file will be renamed to its hash
+/// by the default hash algorithm.
 // noinspection RsWrongGenericArgumentsNumber
 #[tauri::command]
 #[specta::specta]
 pub async fn get_or_create_document(
-    app: tauri::AppHandle,
     db: DbState<'_>,
     data: CreateDocumentData,
+) -> crate::Result<document::Data> {
+    let (db, uploaded_dir) = db.active_with_upload_dir();
+    create_document(db, &uploaded_dir, data).await
+}
+
+/// Create a document and store it in `uploaded_dir`, as `get_or_create_document` does.
+pub(crate) async fn create_document(
+    db: &PrismaClient,
+    uploaded_dir: &Path,
+    data: CreateDocumentData,
 ) -> crate::Result<document::Data> {
     let update_time = prisma_client_rust::chrono::prelude::Local::now().into();
-    let uploaded_dir = prepare_upload_folder(app.config().as_ref()).await?;
-    let (filename, md5_hash, target_path) = match data {
+    prepare_upload_folder(uploaded_dir).await?;
+    let hash_algo = HashAlgo::default();
+    let (filename, hash, target_path) = match data {
         CreateDocumentData::Path { filename, filepath } => {
-            let md5_hash = hash_file_in_md5(&filepath).await?;
-            let target_path = uploaded_dir.join(&md5_hash);
+            let hash = hash_file(hash_algo, &filepath).await?;
+            let target_path = uploaded_dir.join(&hash);
             if !target_path.exists() {
                 tokio::fs::copy(&filepath, &target_path).await?;
             }
-            (filename, md5_hash, target_path)
+            (filename, hash, target_path)
         }
         CreateDocumentData::File { filename, content } => {
-            let md5_hash = hash_in_md5(&content).await?;
-            let target_path = uploaded_dir.join(&md5_hash);
+            let hash = hash_bytes(hash_algo, &content);
+            let target_path = uploaded_dir.join(&hash);
             if !target_path.exists() {
                 tokio::fs::write(&target_path, content).await?;
             }
-            (filename, md5_hash, target_path)
+            (filename, hash, target_path)
         }
     };
-    Ok(db
+    let doc = db
         .document()
         .create(
             filename,
             target_path.to_str().unwrap().to_string(),
-            md5_hash,
+            hash,
             update_time,
-            vec![],
+            vec![document::hash_algo::set(hash_algo.as_str().to_string())],
         )
         .exec()
-        .await?)
+        .await?;
+    document_metadata::extract_into(db, &doc).await;
+    Ok(doc)
 }
 
-/// Create documents and store them in `app_local_data_dir/upload` folder.
+/// Create documents and store them in the upload folder of the workspace.
 // noinspection RsWrongGenericArgumentsNumber
 #[tauri::command]
 #[specta::specta]
 pub async fn add_documents(
-    app: tauri::AppHandle,
     db: DbState<'_>,
     documents: Vec<CreateDocumentData>,
 ) -> crate::Result<Vec<document::Data>> {
+    let (db, uploaded_dir) = db.active_with_upload_dir();
     let mut docs = vec![];
     for document in documents {
-        let doc = get_or_create_document(app.clone(), db.clone(), document).await?;
+        let doc = create_document(db, &uploaded_dir, document).await?;
         docs.push(doc);
     }
     Ok(docs)
 }
 
-/// Delete a document and remove it from `app_local_data_dir/upload` folder.
+/// Delete a document and remove it from the upload folder of the workspace.
 #[tauri::command]
 #[specta::specta]
 pub async fn delete_document(db: DbState<'_>, id: i32) -> crate::Result<()> {
+    let db = db.active();
     let doc = db
         .document()
         .delete(document::id::equals(id))
@@ -121,10 +143,110 @@ pub async fn delete_document(db: DbState<'_>, id: i32) -> crate::Result<()> {
     Ok(())
 }
 
-async fn prepare_upload_folder(config: &tauri::Config) -> std::io::Result<std::path::PathBuf> {
-    let app_data_dir = tauri::api::path::app_local_data_dir(config).unwrap();
-    let uploaded_dir = app_data_dir.join("uploaded");
+/// Hash the stored files of all documents again, and report the ones missing or corrupted.
+#[tauri::command]
+#[specta::specta]
+pub async fn verify_documents(db: DbState<'_>) -> crate::Result<VerificationReport> {
+    let db = db.active();
+    let documents = db.document().find_many(vec![]).exec().await?;
+    storage::verify(documents).await
+}
+
+/// Relink a document whose stored file is missing or corrupted to a replacement file of the same
+/// content, which is copied into the upload folder.
+///
+/// The other documents of the same content are relinked as well.
+#[tauri::command]
+#[specta::specta]
+pub async fn relink_document(
+    db: DbState<'_>,
+    id: i32,
+    filepath: String,
+) -> crate::Result<document::Data> {
+    let (db, uploaded_dir) = db.active_with_upload_dir();
+    let doc = db
+        .document()
+        .find_unique(document::id::equals(id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Document {} not found", id)))?;
+    prepare_upload_folder(&uploaded_dir).await?;
+    let target_path = storage::store_replacement(
+        Path::new(&filepath),
+        HashAlgo::of_str(&doc.hash_algo)?,
+        &doc.md_5_hash,
+        &uploaded_dir,
+    )
+    .await?;
+    db.document()
+        .update_many(
+            vec![
+                document::md_5_hash::equals(doc.md_5_hash.clone()),
+                document::hash_algo::equals(doc.hash_algo.clone()),
+            ],
+            vec![document::filepath::set(target_path.clone())],
+        )
+        .exec()
+        .await?;
+    Ok(document::Data {
+        filepath: target_path,
+        ..doc
+    })
+}
+
+/// Create a document linked to its source file, which is watched for changes and synced into the
+/// document on change.
+#[tauri::command]
+#[specta::specta]
+pub async fn link_document(
+    db: DbState<'_>,
+    watcher: tauri::State<'_, DocumentWatcher>,
+    filename: String,
+    filepath: String,
+) -> crate::Result<document::Data> {
+    let (db, uploaded_dir) = db.active_with_upload_dir();
+    prepare_upload_folder(&uploaded_dir).await?;
+    let doc = linked_documents::create(db, &uploaded_dir, filename, Path::new(&filepath)).await?;
+    watcher.rewatch(db).await?;
+    Ok(doc)
+}
+
+/// Stop syncing a linked document with its source file, keeping the document as it is.
+#[tauri::command]
+#[specta::specta]
+pub async fn unlink_document(
+    db: DbState<'_>,
+    watcher: tauri::State<'_, DocumentWatcher>,
+    id: i32,
+) -> crate::Result<document::Data> {
+    let db = db.active();
+    let doc = db
+        .document()
+        .update(
+            document::id::equals(id),
+            vec![document::source_path::set(None)],
+        )
+        .exec()
+        .await?;
+    watcher.rewatch(db).await?;
+    Ok(doc)
+}
+
+/// Sync all linked documents with their source files, which catches up with the changes made
+/// while the app was not running.
+///
+/// A changed document is replaced by a new document in its collections, and reported by a
+/// `document://changed` event as well.
+#[tauri::command]
+#[specta::specta]
+pub async fn sync_linked_documents(
+    db: DbState<'_>,
+    watcher: tauri::State<'_, DocumentWatcher>,
+) -> crate::Result<Vec<DocumentChange>> {
+    let (db, uploaded_dir) = db.active_with_upload_dir();
+    watcher.sync(db, &uploaded_dir, vec![]).await
+}
 
-    tokio::fs::create_dir_all(&uploaded_dir).await?;
-    Ok(uploaded_dir)
+pub(crate) async fn prepare_upload_folder(uploaded_dir: &Path) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(uploaded_dir).await
 }
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use specta::Type;
 
 use crate::commands::db::DbState;
+use crate::core::fs::HashAlgo;
 use crate::prisma::{embedding_vectors_on_document_chunks, embeddings_config};
 
 #[derive(Serialize, Type)]
@@ -18,6 +19,7 @@ pub struct EmbeddingVectorData {
 pub struct GetEmbeddingVectorByMD5Hash {
     #[serde(rename = "embeddingsConfigId")]
     embeddings_config_id: i32,
+    /// Hash of the chunk content, whose algorithm is told by its length.
     #[serde(rename = "md5Hash")]
     md5_hash: String,
 }
@@ -28,6 +30,7 @@ pub async fn get_embedding_vector_by_md5hash(
     db: DbState<'_>,
     data: GetEmbeddingVectorByMD5Hash,
 ) -> crate::Result<Option<EmbeddingVectorData>> {
+    let db = db.active();
     Ok(db
         .embedding_vectors_on_document_chunks()
         .find_unique(
@@ -62,11 +65,13 @@ pub async fn upsert_embedding_vector_by_md5hash(
     db: DbState<'_>,
     data: UpsertEmbeddingVectorByMD5Hash,
 ) -> crate::Result<embedding_vectors_on_document_chunks::Data> {
+    let db = db.active();
     let vector = data
         .vector
         .into_iter()
         .flat_map(|tensor| tensor.to_be_bytes())
         .collect::<Vec<_>>();
+    let hash_algo = HashAlgo::of_hash(&data.identity.md5_hash)?;
     Ok(db
         .embedding_vectors_on_document_chunks()
         .upsert(
@@ -78,7 +83,9 @@ pub async fn upsert_embedding_vector_by_md5hash(
                 data.identity.md5_hash,
                 embeddings_config::id::equals(data.identity.embeddings_config_id),
                 vector,
-                vec![],
+                vec![embedding_vectors_on_document_chunks::hash_algo::set(
+                    hash_algo.as_str().to_string(),
+                )],
             ),
             vec![],
         )
@@ -92,8 +99,13 @@ pub async fn upsert_embedding_vector_by_md5hash_in_batch(
     db: DbState<'_>,
     data: Vec<UpsertEmbeddingVectorByMD5Hash>,
 ) -> crate::Result<i32> {
+    let db = db.active();
+    let hash_algos = data
+        .iter()
+        .map(|data| HashAlgo::of_hash(&data.identity.md5_hash))
+        .collect::<crate::Result<Vec<_>>>()?;
     Ok(db
-        ._batch(data.into_iter().map(|data| {
+        ._batch(data.into_iter().zip(hash_algos).map(|(data, hash_algo)| {
             db.embedding_vectors_on_document_chunks().upsert(
                 embedding_vectors_on_document_chunks::embeddings_config_id_md_5_hash(
                     data.identity.embeddings_config_id,
@@ -106,7 +118,9 @@ pub async fn upsert_embedding_vector_by_md5hash_in_batch(
                         .into_iter()
                         .flat_map(|tensor| tensor.to_be_bytes())
                         .collect(),
-                    vec![],
+                    vec![embedding_vectors_on_document_chunks::hash_algo::set(
+                        hash_algo.as_str().to_string(),
+                    )],
                 ),
                 vec![],
             )
@@ -1,8 +1,11 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+use crate::commands::db::index_profiles::DeletionReport;
 use crate::commands::db::DbState;
-use crate::prisma::embeddings_client;
+use crate::core::client_types::{self, ClientPayload, EmbeddingsClientInfo};
+use crate::core::secrets::{self, ClientKind};
+use crate::prisma::{embeddings_client, index_profile};
 
 #[derive(Serialize, Type)]
 pub struct EmbeddingsClientExData {
@@ -18,7 +21,7 @@ impl EmbeddingsClientExData {
             id: data.id,
             name: data.name,
             r#type: data.r#type,
-            info: serde_json::from_str(data.info.as_str())?,
+            info: secrets::redact_info(serde_json::from_str(data.info.as_str())?),
         })
     }
 }
@@ -26,6 +29,7 @@ impl EmbeddingsClientExData {
 #[tauri::command]
 #[specta::specta]
 pub async fn get_embeddings_clients(db: DbState<'_>) -> crate::Result<Vec<EmbeddingsClientExData>> {
+    let db = db.active();
     db.embeddings_client()
         .find_many(vec![])
         .exec()
@@ -43,6 +47,7 @@ pub async fn get_embeddings_client_by_id(
     db: DbState<'_>,
     client_id: i32,
 ) -> crate::Result<Option<EmbeddingsClientExData>> {
+    let db = db.active();
     db.embeddings_client()
         .find_unique(embeddings_client::id::equals(client_id))
         .exec()
@@ -54,8 +59,8 @@ pub async fn get_embeddings_client_by_id(
 #[derive(Deserialize, Type)]
 pub struct CreateEmbeddingsClientData {
     name: String,
-    r#type: String,
-    info: serde_json::Value,
+    #[serde(flatten)]
+    client: EmbeddingsClientInfo,
 }
 
 #[tauri::command]
@@ -64,12 +69,21 @@ pub async fn create_embeddings_client(
     db: DbState<'_>,
     data: CreateEmbeddingsClientData,
 ) -> crate::Result<EmbeddingsClientExData> {
-    let info = serde_json::to_string(&data.info)?;
-    db.embeddings_client()
-        .create(data.name, data.r#type, info, vec![])
-        .exec()
+    let db = db.active();
+    client_types::validate(&data.name, &data.client)?;
+    let (r#type, info) = data.client.to_stored()?;
+    db._transaction()
+        .run(|tx| async move {
+            secrets::check_duplicate(&tx, ClientKind::Embeddings, None, &r#type, &info).await?;
+            let info = secrets::seal_info(&tx, info).await?;
+            let client = tx
+                .embeddings_client()
+                .create(data.name, r#type, info, vec![])
+                .exec()
+                .await?;
+            EmbeddingsClientExData::from_data(client)
+        })
         .await
-        .map(EmbeddingsClientExData::from_data)?
 }
 
 #[tauri::command]
@@ -79,18 +93,78 @@ pub async fn upsert_embeddings_client(
     client_id: i32,
     data: CreateEmbeddingsClientData,
 ) -> crate::Result<EmbeddingsClientExData> {
-    let info = serde_json::to_string(&data.info)?;
-    db.embeddings_client()
-        .upsert(
-            embeddings_client::id::equals(client_id),
-            (data.name.clone(), data.r#type.clone(), info.clone(), vec![]),
-            vec![
-                embeddings_client::name::set(data.name),
-                embeddings_client::r#type::set(data.r#type),
-                embeddings_client::info::set(info),
-            ],
+    let db = db.active();
+    client_types::validate(&data.name, &data.client)?;
+    let (r#type, mut info) = data.client.to_stored()?;
+    db._transaction()
+        .run(|tx| async move {
+            let stored = tx
+                .embeddings_client()
+                .find_unique(embeddings_client::id::equals(client_id))
+                .exec()
+                .await?;
+            if let Some(stored) = stored {
+                secrets::restore_redacted(&mut info, &stored.info)?;
+            }
+            secrets::check_duplicate(&tx, ClientKind::Embeddings, Some(client_id), &r#type, &info)
+                .await?;
+            let info = secrets::seal_info(&tx, info).await?;
+            let client = tx
+                .embeddings_client()
+                .upsert(
+                    embeddings_client::id::equals(client_id),
+                    (data.name.clone(), r#type.clone(), info.clone(), vec![]),
+                    vec![
+                        embeddings_client::name::set(data.name),
+                        embeddings_client::r#type::set(r#type),
+                        embeddings_client::info::set(info),
+                    ],
+                )
+                .exec()
+                .await?;
+            EmbeddingsClientExData::from_data(client)
+        })
+        .await
+}
+
+/// Delete an embeddings client.
+///
+/// It is refused if index profiles use the client, unless `cascade` is set to delete them along
+/// with their collection indexes and their vectors.
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_embeddings_client(
+    db: DbState<'_>,
+    client_id: i32,
+    cascade: bool,
+) -> crate::Result<DeletionReport> {
+    let db = db.active();
+    if cascade {
+        DeletionReport::delete_remote_vectors(
+            db,
+            vec![index_profile::embeddings_client_id::equals(client_id)],
         )
-        .exec()
+        .await?;
+    }
+    db._transaction()
+        .run(|tx| async move {
+            let report = DeletionReport::of_profiles(
+                &tx,
+                vec![index_profile::embeddings_client_id::equals(client_id)],
+            )
+            .await?;
+            if report.is_refused(cascade) {
+                return Ok(report);
+            }
+            tx.index_profile()
+                .delete_many(vec![index_profile::embeddings_client_id::equals(client_id)])
+                .exec()
+                .await?;
+            tx.embeddings_client()
+                .delete(embeddings_client::id::equals(client_id))
+                .exec()
+                .await?;
+            crate::Result::Ok(report.done())
+        })
         .await
-        .map(EmbeddingsClientExData::from_data)?
 }
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+use crate::commands::db::index_profiles::DeletionReport;
 use crate::commands::db::DbState;
-use crate::prisma::embeddings_config;
+use crate::core::client_types::{self, ClientPayload, EmbeddingsConfigMeta};
+use crate::prisma::{embedding_vectors_on_document_chunks, embeddings_config, index_profile};
 
 #[derive(Serialize, Deserialize, Type)]
 pub struct EmbeddingsConfigExData {
@@ -30,6 +32,7 @@ pub async fn get_embeddings_configs_by_client_type(
     db: DbState<'_>,
     client_type: String,
 ) -> crate::Result<Vec<EmbeddingsConfigExData>> {
+    let db = db.active();
     db.embeddings_config()
         .find_many(vec![embeddings_config::client_type::equals(client_type)])
         .exec()
@@ -47,6 +50,7 @@ pub async fn get_embeddings_config_by_id(
     db: DbState<'_>,
     embeddings_config_id: i32,
 ) -> crate::Result<Option<EmbeddingsConfigExData>> {
+    let db = db.active();
     db.embeddings_config()
         .find_unique(embeddings_config::id::equals(embeddings_config_id))
         .exec()
@@ -57,6 +61,7 @@ pub async fn get_embeddings_config_by_id(
 #[tauri::command]
 #[specta::specta]
 pub async fn get_embeddings_configs(db: DbState<'_>) -> crate::Result<Vec<EmbeddingsConfigExData>> {
+    let db = db.active();
     db.embeddings_config()
         .find_many(vec![])
         .exec()
@@ -71,9 +76,8 @@ pub async fn get_embeddings_configs(db: DbState<'_>) -> crate::Result<Vec<Embedd
 #[derive(Deserialize, Type)]
 pub struct CreateEmbeddingsConfigData {
     name: String,
-    #[serde(rename = "clientType")]
-    client_type: String,
-    meta: serde_json::Value,
+    #[serde(flatten)]
+    config: EmbeddingsConfigMeta,
 }
 
 #[tauri::command]
@@ -82,9 +86,12 @@ pub async fn create_embeddings_config(
     db: DbState<'_>,
     data: CreateEmbeddingsConfigData,
 ) -> crate::Result<EmbeddingsConfigExData> {
-    let meta = serde_json::to_string(&data.meta)?;
+    let db = db.active();
+    client_types::validate(&data.name, &data.config)?;
+    let (client_type, meta) = data.config.to_stored()?;
+    let meta = serde_json::to_string(&meta)?;
     db.embeddings_config()
-        .create(data.name, data.client_type, meta, vec![])
+        .create(data.name, client_type, meta, vec![])
         .exec()
         .await
         .map(EmbeddingsConfigExData::from_data)?
@@ -97,19 +104,17 @@ pub async fn upsert_embeddings_config(
     config_id: i32,
     data: CreateEmbeddingsConfigData,
 ) -> crate::Result<EmbeddingsConfigExData> {
-    let meta = serde_json::to_string(&data.meta)?;
+    let db = db.active();
+    client_types::validate(&data.name, &data.config)?;
+    let (client_type, meta) = data.config.to_stored()?;
+    let meta = serde_json::to_string(&meta)?;
     db.embeddings_config()
         .upsert(
             embeddings_config::id::equals(config_id),
-            (
-                data.name.clone(),
-                data.client_type.clone(),
-                meta.clone(),
-                vec![],
-            ),
+            (data.name.clone(), client_type.clone(), meta.clone(), vec![]),
             vec![
                 embeddings_config::name::set(data.name),
-                embeddings_config::client_type::set(data.client_type),
+                embeddings_config::client_type::set(client_type),
                 embeddings_config::meta::set(meta),
             ],
         )
@@ -117,3 +122,51 @@ pub async fn upsert_embeddings_config(
         .await
         .map(EmbeddingsConfigExData::from_data)?
 }
+
+/// Delete an embeddings config, along with the embedding vectors cached by it.
+///
+/// It is refused if index profiles use the config, unless `cascade` is set to delete them along
+/// with their collection indexes and their vectors.
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_embeddings_config(
+    db: DbState<'_>,
+    config_id: i32,
+    cascade: bool,
+) -> crate::Result<DeletionReport> {
+    let db = db.active();
+    if cascade {
+        DeletionReport::delete_remote_vectors(
+            db,
+            vec![index_profile::embeddings_config_id::equals(config_id)],
+        )
+        .await?;
+    }
+    db._transaction()
+        .run(|tx| async move {
+            let report = DeletionReport::of_profiles(
+                &tx,
+                vec![index_profile::embeddings_config_id::equals(config_id)],
+            )
+            .await?;
+            if report.is_refused(cascade) {
+                return Ok(report);
+            }
+            tx.index_profile()
+                .delete_many(vec![index_profile::embeddings_config_id::equals(config_id)])
+                .exec()
+                .await?;
+            tx.embedding_vectors_on_document_chunks()
+                .delete_many(vec![
+                    embedding_vectors_on_document_chunks::embeddings_config_id::equals(config_id),
+                ])
+                .exec()
+                .await?;
+            tx.embeddings_config()
+                .delete(embeddings_config::id::equals(config_id))
+                .exec()
+                .await?;
+            crate::Result::Ok(report.done())
+        })
+        .await
+}
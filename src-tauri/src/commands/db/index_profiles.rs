@@ -1,32 +1,54 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use specta::Type;
 
 use crate::commands::db::DbState;
+use crate::core::result::Error;
+use crate::core::retrieval;
+use crate::core::secrets;
+use crate::core::validation::ValidationError;
+use crate::core::vector_db::VectorDb;
 use crate::prisma::{
-    embeddings_client, embeddings_config, index_profile, splitting, vector_db_client,
-    vector_db_config,
+    collection_index, embeddings_client, embeddings_config, index_profile, reranker_client,
+    splitting, vector_db_client, vector_db_config, PrismaClient,
 };
 
 index_profile::include!(index_profile_with_all {
-    embeddings_client embeddings_config vector_db_client vector_db_config splitting
+    embeddings_client embeddings_config vector_db_client vector_db_config reranker_client splitting
 });
 
+impl index_profile_with_all::Data {
+    /// Redact the secrets of the clients, which are only used by the backend.
+    fn redacted(mut self) -> Self {
+        self.embeddings_client.info = secrets::redact_stored_info(&self.embeddings_client.info);
+        self.vector_db_client.info = secrets::redact_stored_info(&self.vector_db_client.info);
+        if let Some(reranker_client) = self.reranker_client.as_mut() {
+            reranker_client.info = secrets::redact_stored_info(&reranker_client.info);
+        }
+        self
+    }
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn get_index_profiles_with_all(
     db: DbState<'_>,
 ) -> crate::Result<Vec<index_profile_with_all::Data>> {
+    let db = db.active();
     Ok(db
         .index_profile()
         .find_many(vec![])
         .include(index_profile_with_all::include())
         .exec()
-        .await?)
+        .await?
+        .into_iter()
+        .map(index_profile_with_all::Data::redacted)
+        .collect())
 }
 
 #[tauri::command]
 #[specta::specta]
 pub async fn get_index_profiles(db: DbState<'_>) -> crate::Result<Vec<index_profile::Data>> {
+    let db = db.active();
     Ok(db.index_profile().find_many(vec![]).exec().await?)
 }
 
@@ -36,6 +58,7 @@ pub async fn get_index_profile_by_id(
     db: DbState<'_>,
     index_profile_id: i32,
 ) -> crate::Result<Option<index_profile::Data>> {
+    let db = db.active();
     Ok(db
         .index_profile()
         .find_unique(index_profile::id::equals(index_profile_id))
@@ -49,12 +72,14 @@ pub async fn get_index_profile_with_all_by_id(
     db: DbState<'_>,
     index_profile_id: i32,
 ) -> crate::Result<Option<index_profile_with_all::Data>> {
+    let db = db.active();
     Ok(db
         .index_profile()
         .find_unique(index_profile::id::equals(index_profile_id))
         .include(index_profile_with_all::include())
         .exec()
-        .await?)
+        .await?
+        .map(index_profile_with_all::Data::redacted))
 }
 
 #[derive(Deserialize, Type)]
@@ -70,6 +95,17 @@ pub struct CreateIndexProfileData {
     vector_db_client_id: i32,
     #[serde(rename = "vectorDbConfigId")]
     vector_db_config_id: i32,
+    #[serde(rename = "rerankerClientId")]
+    reranker_client_id: Option<i32>,
+}
+
+impl CreateIndexProfileData {
+    fn optional_params(&self) -> Vec<index_profile::SetParam> {
+        self.reranker_client_id
+            .map(|id| index_profile::reranker_client::connect(reranker_client::id::equals(id)))
+            .into_iter()
+            .collect()
+    }
 }
 
 #[tauri::command]
@@ -78,6 +114,8 @@ pub async fn create_index_profile(
     db: DbState<'_>,
     data: CreateIndexProfileData,
 ) -> crate::Result<index_profile::Data> {
+    let db = db.active();
+    let params = data.optional_params();
     Ok(db
         .index_profile()
         .create(
@@ -87,7 +125,7 @@ pub async fn create_index_profile(
             embeddings_config::id::equals(data.embeddings_config_id),
             vector_db_client::id::equals(data.vector_db_client_id),
             vector_db_config::id::equals(data.vector_db_config_id),
-            vec![],
+            params,
         )
         .exec()
         .await?)
@@ -99,6 +137,8 @@ pub async fn create_index_profile_with_all(
     db: DbState<'_>,
     data: CreateIndexProfileData,
 ) -> crate::Result<index_profile_with_all::Data> {
+    let db = db.active();
+    let params = data.optional_params();
     Ok(db
         .index_profile()
         .create(
@@ -108,9 +148,307 @@ pub async fn create_index_profile_with_all(
             embeddings_config::id::equals(data.embeddings_config_id),
             vector_db_client::id::equals(data.vector_db_client_id),
             vector_db_config::id::equals(data.vector_db_config_id),
-            vec![],
+            params,
         )
         .include(index_profile_with_all::include())
         .exec()
+        .await?
+        .redacted())
+}
+
+/// Set or clear the reranker used by an index profile.
+///
+/// Passing `None` as `reranker_client_id` disables the reranking stage, so that the candidates
+/// are returned in the order given by the vector database.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_index_profile_reranker(
+    db: DbState<'_>,
+    index_profile_id: i32,
+    reranker_client_id: Option<i32>,
+) -> crate::Result<index_profile::Data> {
+    let db = db.active();
+    let param = match reranker_client_id {
+        Some(id) => index_profile::reranker_client::connect(reranker_client::id::equals(id)),
+        None => index_profile::reranker_client::disconnect(),
+    };
+    Ok(db
+        .index_profile()
+        .update(index_profile::id::equals(index_profile_id), vec![param])
+        .exec()
         .await?)
 }
+
+#[derive(Deserialize, Type)]
+pub struct UpdateIndexProfileData {
+    name: Option<String>,
+    #[serde(rename = "splittingId")]
+    splitting_id: Option<i32>,
+    #[serde(rename = "embeddingsClientId")]
+    embeddings_client_id: Option<i32>,
+    #[serde(rename = "embeddingsConfigId")]
+    embeddings_config_id: Option<i32>,
+    #[serde(rename = "vectorDbClientId")]
+    vector_db_client_id: Option<i32>,
+    #[serde(rename = "vectorDbConfigId")]
+    vector_db_config_id: Option<i32>,
+}
+
+/// Update the fields given of an index profile.
+///
+/// The splitting, the embeddings config and the vector db of a profile cannot be changed once
+/// collection indexes are built by it, since the indexed vectors would no longer match. The
+/// embeddings client can, for example to use another api key, as long as its type matches the
+/// embeddings config.
+#[tauri::command]
+#[specta::specta]
+pub async fn update_index_profile(
+    db: DbState<'_>,
+    index_profile_id: i32,
+    data: UpdateIndexProfileData,
+) -> crate::Result<index_profile_with_all::Data> {
+    let db = db.active();
+    let profile = db
+        .index_profile()
+        .find_unique(index_profile::id::equals(index_profile_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Index profile {} not found", index_profile_id)))?;
+    let num_indexes = db
+        .collection_index()
+        .count(vec![collection_index::index_id::equals(index_profile_id)])
+        .exec()
+        .await?;
+
+    let mut errors = ValidationError::default();
+    errors.require_some("name", data.name.as_deref());
+    if num_indexes > 0 {
+        let fixed = [
+            ("splittingId", data.splitting_id, profile.splitting_id),
+            (
+                "embeddingsConfigId",
+                data.embeddings_config_id,
+                profile.embeddings_config_id,
+            ),
+            (
+                "vectorDbClientId",
+                data.vector_db_client_id,
+                profile.vector_db_client_id,
+            ),
+            (
+                "vectorDbConfigId",
+                data.vector_db_config_id,
+                profile.vector_db_config_id,
+            ),
+        ];
+        for (field, new, current) in fixed {
+            if matches!(new, Some(new) if new != current) {
+                errors.add(
+                    field,
+                    format!(
+                        "cannot be changed while {} collection indexes are built by the profile",
+                        num_indexes
+                    ),
+                );
+            }
+        }
+    }
+    check_client_types(db, &data, &profile, &mut errors).await?;
+    errors.into_result()?;
+
+    let params = vec![
+        data.name.map(index_profile::name::set),
+        data.splitting_id
+            .map(|id| index_profile::splitting::connect(splitting::id::equals(id))),
+        data.embeddings_client_id
+            .map(|id| index_profile::embeddings_client::connect(embeddings_client::id::equals(id))),
+        data.embeddings_config_id
+            .map(|id| index_profile::embeddings_config::connect(embeddings_config::id::equals(id))),
+        data.vector_db_client_id
+            .map(|id| index_profile::vector_db_client::connect(vector_db_client::id::equals(id))),
+        data.vector_db_config_id
+            .map(|id| index_profile::vector_db_config::connect(vector_db_config::id::equals(id))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    Ok(db
+        .index_profile()
+        .update(index_profile::id::equals(index_profile_id), params)
+        .include(index_profile_with_all::include())
+        .exec()
+        .await?
+        .redacted())
+}
+
+/// Check that the clients of an updated profile are of the types of its configs.
+async fn check_client_types(
+    db: &PrismaClient,
+    data: &UpdateIndexProfileData,
+    profile: &index_profile::Data,
+    errors: &mut ValidationError,
+) -> crate::Result<()> {
+    if data.embeddings_client_id.is_some() || data.embeddings_config_id.is_some() {
+        let client = db
+            .embeddings_client()
+            .find_unique(embeddings_client::id::equals(
+                data.embeddings_client_id
+                    .unwrap_or(profile.embeddings_client_id),
+            ))
+            .exec()
+            .await?;
+        let config = db
+            .embeddings_config()
+            .find_unique(embeddings_config::id::equals(
+                data.embeddings_config_id
+                    .unwrap_or(profile.embeddings_config_id),
+            ))
+            .exec()
+            .await?;
+        match (client, config) {
+            (None, _) => errors.add("embeddingsClientId", "is not found"),
+            (_, None) => errors.add("embeddingsConfigId", "is not found"),
+            (Some(client), Some(config)) if client.r#type != config.client_type => errors.add(
+                "embeddingsClientId",
+                format!(
+                    "is a {} client, but the embeddings config is for {}",
+                    client.r#type, config.client_type
+                ),
+            ),
+            _ => {}
+        }
+    }
+    if data.vector_db_client_id.is_some() || data.vector_db_config_id.is_some() {
+        let client = db
+            .vector_db_client()
+            .find_unique(vector_db_client::id::equals(
+                data.vector_db_client_id
+                    .unwrap_or(profile.vector_db_client_id),
+            ))
+            .exec()
+            .await?;
+        let config = db
+            .vector_db_config()
+            .find_unique(vector_db_config::id::equals(
+                data.vector_db_config_id
+                    .unwrap_or(profile.vector_db_config_id),
+            ))
+            .exec()
+            .await?;
+        match (client, config) {
+            (None, _) => errors.add("vectorDbClientId", "is not found"),
+            (_, None) => errors.add("vectorDbConfigId", "is not found"),
+            (Some(client), Some(config)) if client.r#type != config.client_type => errors.add(
+                "vectorDbClientId",
+                format!(
+                    "is a {} client, but the vector db config is for {}",
+                    client.r#type, config.client_type
+                ),
+            ),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// What is deleted, or would be deleted by cascading, along with an index profile or a
+/// splitting, client or config referred by index profiles.
+#[derive(Serialize, Type)]
+pub struct DeletionReport {
+    /// Whether the deletion is done. It is refused if anything would be deleted along and
+    /// cascading is not requested.
+    deleted: bool,
+    /// Index profiles deleted along.
+    #[serde(rename = "indexProfiles")]
+    index_profiles: Vec<index_profile::Data>,
+    /// Collection indexes built by the index profiles, deleted along with their sessions and
+    /// their vectors in the vector databases.
+    #[serde(rename = "collectionIndexes")]
+    collection_indexes: Vec<collection_index::Data>,
+}
+
+impl DeletionReport {
+    /// Find the index profiles matching `filter` and the collection indexes built by them.
+    pub(crate) async fn of_profiles(
+        db: &PrismaClient,
+        filter: Vec<index_profile::WhereParam>,
+    ) -> crate::Result<Self> {
+        let index_profiles = db.index_profile().find_many(filter).exec().await?;
+        let collection_indexes = db
+            .collection_index()
+            .find_many(vec![collection_index::index_id::in_vec(
+                index_profiles.iter().map(|profile| profile.id).collect(),
+            )])
+            .exec()
+            .await?;
+        Ok(Self {
+            deleted: false,
+            index_profiles,
+            collection_indexes,
+        })
+    }
+
+    /// Delete the vectors of the collection indexes built by the index profiles matching
+    /// `filter` from the vector databases.
+    ///
+    /// The vectors are stored remotely in the namespaces named by the ids of the collection
+    /// indexes, so that they have to be deleted before the indexes are deleted along.
+    pub(crate) async fn delete_remote_vectors(
+        db: &PrismaClient,
+        filter: Vec<index_profile::WhereParam>,
+    ) -> crate::Result<()> {
+        for index in Self::of_profiles(db, filter).await?.collection_indexes {
+            let index = retrieval::find_index(db, index.id).await?;
+            VectorDb::from_data(&index.index.vector_db_client, &index.index.vector_db_config)?
+                .delete(&index.id, None)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Whether the deletion of what the index profiles refer to has to be refused.
+    pub(crate) fn is_refused(&self, cascade: bool) -> bool {
+        !cascade && !self.index_profiles.is_empty()
+    }
+
+    pub(crate) fn done(mut self) -> Self {
+        self.deleted = true;
+        self
+    }
+}
+
+/// Delete an index profile.
+///
+/// It is refused if collection indexes are built by the profile, unless `cascade` is set to
+/// delete them along with their vectors in the vector database.
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_index_profile(
+    db: DbState<'_>,
+    index_profile_id: i32,
+    cascade: bool,
+) -> crate::Result<DeletionReport> {
+    let db = db.active();
+    if cascade {
+        DeletionReport::delete_remote_vectors(
+            db,
+            vec![index_profile::id::equals(index_profile_id)],
+        )
+        .await?;
+    }
+    db._transaction()
+        .run(|tx| async move {
+            let report =
+                DeletionReport::of_profiles(&tx, vec![index_profile::id::equals(index_profile_id)])
+                    .await?;
+            if !cascade && !report.collection_indexes.is_empty() {
+                return Ok(report);
+            }
+            tx.index_profile()
+                .delete(index_profile::id::equals(index_profile_id))
+                .exec()
+                .await?;
+            crate::Result::Ok(report.done())
+        })
+        .await
+}
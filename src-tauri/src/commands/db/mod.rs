@@ -1,17 +1,24 @@
-use crate::prisma::PrismaClient;
+use crate::core::workspace::Workspaces;
 
 pub mod collection_indexes;
 pub mod collections;
 pub mod collections_on_documents;
+pub mod completion_clients;
 pub mod document_chunks;
+pub mod document_metadata;
+pub mod document_versions;
 pub mod documents;
 pub mod embedding_vectors;
 pub mod embeddings_clients;
 pub mod embeddings_configs;
 pub mod index_profiles;
+pub mod reranker_clients;
+pub mod session_messages;
 pub mod sessions;
 pub mod splittings;
+pub mod usage_records;
 pub mod vector_db_clients;
 pub mod vector_db_configs;
 
-pub(crate) type DbState<'a> = tauri::State<'a, std::sync::Arc<PrismaClient>>;
+/// The workspaces, of which commands bind the database of the active one by `Workspaces::active`.
+pub(crate) type DbState<'a> = tauri::State<'a, Workspaces>;
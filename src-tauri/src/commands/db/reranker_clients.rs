@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::commands::db::DbState;
+use crate::core::client_types::{self, ClientPayload, RerankerClientInfo};
+use crate::core::secrets::{self, ClientKind};
+use crate::prisma::reranker_client;
+
+#[derive(Serialize, Type)]
+pub struct RerankerClientExData {
+    id: i32,
+    name: String,
+    r#type: String,
+    info: serde_json::Value,
+}
+
+impl RerankerClientExData {
+    pub fn from_data(data: reranker_client::Data) -> crate::Result<Self> {
+        Ok(Self {
+            id: data.id,
+            name: data.name,
+            r#type: data.r#type,
+            info: secrets::redact_info(serde_json::from_str(data.info.as_str())?),
+        })
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_reranker_clients(db: DbState<'_>) -> crate::Result<Vec<RerankerClientExData>> {
+    let db = db.active();
+    db.reranker_client()
+        .find_many(vec![])
+        .exec()
+        .await
+        .map(|data| {
+            data.into_iter()
+                .map(RerankerClientExData::from_data)
+                .collect::<Result<Vec<_>, _>>()
+        })?
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_reranker_client_by_id(
+    db: DbState<'_>,
+    client_id: i32,
+) -> crate::Result<Option<RerankerClientExData>> {
+    let db = db.active();
+    db.reranker_client()
+        .find_unique(reranker_client::id::equals(client_id))
+        .exec()
+        .await?
+        .map(RerankerClientExData::from_data)
+        .transpose()
+}
+
+#[derive(Deserialize, Type)]
+pub struct CreateRerankerClientData {
+    name: String,
+    #[serde(flatten)]
+    client: RerankerClientInfo,
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn create_reranker_client(
+    db: DbState<'_>,
+    data: CreateRerankerClientData,
+) -> crate::Result<RerankerClientExData> {
+    let db = db.active();
+    client_types::validate(&data.name, &data.client)?;
+    let (r#type, info) = data.client.to_stored()?;
+    db._transaction()
+        .run(|tx| async move {
+            secrets::check_duplicate(&tx, ClientKind::Reranker, None, &r#type, &info).await?;
+            let info = secrets::seal_info(&tx, info).await?;
+            let client = tx
+                .reranker_client()
+                .create(data.name, r#type, info, vec![])
+                .exec()
+                .await?;
+            RerankerClientExData::from_data(client)
+        })
+        .await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn upsert_reranker_client(
+    db: DbState<'_>,
+    client_id: i32,
+    data: CreateRerankerClientData,
+) -> crate::Result<RerankerClientExData> {
+    let db = db.active();
+    client_types::validate(&data.name, &data.client)?;
+    let (r#type, mut info) = data.client.to_stored()?;
+    db._transaction()
+        .run(|tx| async move {
+            let stored = tx
+                .reranker_client()
+                .find_unique(reranker_client::id::equals(client_id))
+                .exec()
+                .await?;
+            if let Some(stored) = stored {
+                secrets::restore_redacted(&mut info, &stored.info)?;
+            }
+            secrets::check_duplicate(&tx, ClientKind::Reranker, Some(client_id), &r#type, &info)
+                .await?;
+            let info = secrets::seal_info(&tx, info).await?;
+            let client = tx
+                .reranker_client()
+                .upsert(
+                    reranker_client::id::equals(client_id),
+                    (data.name.clone(), r#type.clone(), info.clone(), vec![]),
+                    vec![
+                        reranker_client::name::set(data.name),
+                        reranker_client::r#type::set(r#type),
+                        reranker_client::info::set(info),
+                    ],
+                )
+                .exec()
+                .await?;
+            RerankerClientExData::from_data(client)
+        })
+        .await
+}
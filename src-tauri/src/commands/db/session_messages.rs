@@ -0,0 +1,527 @@
+use std::collections::{BTreeMap, HashSet};
+
+use prisma_client_rust::chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::commands::db::DbState;
+use crate::core::history::parse_history;
+use crate::core::result::Error;
+use crate::core::retrieval::ChunkRef;
+use crate::core::tokenizer::count_tokens;
+use crate::core::validation::ValidationError;
+use crate::prisma::{session, session_message, PrismaClient};
+
+#[derive(Serialize, Type)]
+pub struct SessionMessageExData {
+    pub id: i32,
+    #[serde(rename = "sessionId")]
+    pub session_id: i32,
+    #[serde(rename = "parentId")]
+    pub parent_id: Option<i32>,
+    pub role: String,
+    pub content: String,
+    #[serde(rename = "tokenCount")]
+    pub token_count: Option<i32>,
+    #[serde(rename = "createTime")]
+    pub create_time: DateTime<FixedOffset>,
+    #[serde(rename = "promptTokens")]
+    pub prompt_tokens: Option<i32>,
+    #[serde(rename = "completionTokens")]
+    pub completion_tokens: Option<i32>,
+    #[serde(rename = "citedChunks")]
+    pub cited_chunks: Vec<ChunkRef>,
+    pub model: Option<String>,
+}
+
+impl SessionMessageExData {
+    pub fn from_data(data: session_message::Data) -> crate::Result<Self> {
+        Ok(Self {
+            id: data.id,
+            session_id: data.session_id,
+            parent_id: data.parent_id,
+            role: data.role,
+            content: data.content,
+            token_count: data.token_count,
+            create_time: data.create_time,
+            prompt_tokens: data.prompt_tokens,
+            completion_tokens: data.completion_tokens,
+            cited_chunks: serde_json::from_str(data.cited_chunks.as_str())?,
+            model: data.model,
+        })
+    }
+
+    /// Number of tokens of the content, counted now if not counted before.
+    pub fn tokens(&self) -> usize {
+        self.token_count
+            .map(|count| count as usize)
+            .unwrap_or_else(|| count_tokens(&self.content))
+    }
+}
+
+/// Get messages on the active branch of a session in chronological order.
+///
+/// Messages are paginated backwards from the latest one: at most `limit` messages sent before the
+/// message `before_id` are returned. The whole branch is returned if neither is given, and a
+/// `before_id` not on the active branch is rejected.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_session_messages(
+    db: DbState<'_>,
+    session_id: i32,
+    before_id: Option<i32>,
+    limit: Option<i32>,
+) -> crate::Result<Vec<SessionMessageExData>> {
+    active_branch(db.active(), session_id, before_id, limit).await
+}
+
+/// Messages on the active branch of a session, as `get_session_messages` gets them.
+pub(crate) async fn active_branch(
+    db: &PrismaClient,
+    session_id: i32,
+    before_id: Option<i32>,
+    limit: Option<i32>,
+) -> crate::Result<Vec<SessionMessageExData>> {
+    let tree = SessionTree::load(db, session_id).await?;
+    let mut branch = tree.branch_to(tree.active_leaf_id());
+    if let Some(before_id) = before_id {
+        match branch.iter().position(|message| message.id == before_id) {
+            Some(pos) => branch.truncate(pos),
+            None => {
+                let mut errors = ValidationError::default();
+                errors.add("beforeId", "is not a message of the active branch");
+                errors.into_result()?;
+            }
+        }
+    }
+    if let Some(limit) = limit {
+        let skip = branch.len().saturating_sub(limit.max(0) as usize);
+        branch.drain(..skip);
+    }
+    branch
+        .into_iter()
+        .cloned()
+        .map(SessionMessageExData::from_data)
+        .collect()
+}
+
+#[derive(Deserialize, Type)]
+pub struct AppendSessionMessageData {
+    #[serde(rename = "sessionId")]
+    pub session_id: i32,
+    /// The message to reply to, or the last message of the active branch if not given.
+    #[serde(rename = "parentId")]
+    pub parent_id: Option<i32>,
+    pub role: String,
+    pub content: String,
+    #[serde(rename = "promptTokens")]
+    pub prompt_tokens: Option<i32>,
+    #[serde(rename = "completionTokens")]
+    pub completion_tokens: Option<i32>,
+    #[serde(rename = "citedChunks")]
+    pub cited_chunks: Vec<ChunkRef>,
+    pub model: Option<String>,
+}
+
+/// Append a message to a session and make its branch the active one.
+///
+/// Replying to a message other than the last one of a branch forks a new branch from it.
+#[tauri::command]
+#[specta::specta]
+pub async fn append_session_message(
+    db: DbState<'_>,
+    data: AppendSessionMessageData,
+) -> crate::Result<SessionMessageExData> {
+    append(db.active(), data).await
+}
+
+/// Append a message to a session, as `append_session_message` does.
+pub(crate) async fn append(
+    db: &PrismaClient,
+    data: AppendSessionMessageData,
+) -> crate::Result<SessionMessageExData> {
+    let tree = SessionTree::load(db, data.session_id).await?;
+    let parent_id = match data.parent_id {
+        Some(parent_id) => {
+            let mut errors = ValidationError::default();
+            if !tree.messages.contains_key(&parent_id) {
+                errors.add("parentId", "is not a message of the session");
+            }
+            errors.into_result()?;
+            Some(parent_id)
+        }
+        None => tree.active_leaf_id(),
+    };
+    let session_id = data.session_id;
+    let message = create_message(db, parent_id, data).await?;
+    activate_message(db, session_id, message.id).await?;
+    SessionMessageExData::from_data(message)
+}
+
+/// Sync the active branch of a session with the `history` saved by the chat UI, so that the
+/// messages stay the ones the history holds.
+///
+/// The messages the history starts with are kept. The ones departing from it are deleted along
+/// with their replies, and the rest of the history is appended in their place.
+pub(crate) async fn sync_history(
+    db: &PrismaClient,
+    session_id: i32,
+    history: &str,
+) -> crate::Result<()> {
+    let messages = parse_history(history)
+        .into_iter()
+        .flat_map(|pair| [("user", pair.question), ("assistant", pair.answer)])
+        .filter(|(_, content)| !content.is_empty())
+        .collect::<Vec<_>>();
+    let tree = SessionTree::load(db, session_id).await?;
+    let branch = tree.branch_to(tree.active_leaf_id());
+    let kept = branch
+        .iter()
+        .zip(&messages)
+        .take_while(|(message, (role, content))| {
+            message.role == *role && message.content == *content
+        })
+        .count();
+    if kept == branch.len() && kept == messages.len() {
+        return Ok(());
+    }
+    if let Some(departed) = branch.get(kept) {
+        db.session_message()
+            .delete(session_message::id::equals(departed.id))
+            .exec()
+            .await?;
+    }
+
+    let mut parent_id = kept.checked_sub(1).map(|i| branch[i].id);
+    for (role, content) in messages.into_iter().skip(kept) {
+        let message = create_message(
+            db,
+            parent_id,
+            AppendSessionMessageData {
+                session_id,
+                parent_id,
+                role: role.to_string(),
+                content,
+                prompt_tokens: None,
+                completion_tokens: None,
+                cited_chunks: vec![],
+                model: None,
+            },
+        )
+        .await?;
+        parent_id = Some(message.id);
+    }
+    if let Some(parent_id) = parent_id {
+        activate_message(db, session_id, parent_id).await?;
+    }
+    Ok(())
+}
+
+/// Create a message replying to `parent_id`, or a root one, without activating it.
+async fn create_message(
+    db: &PrismaClient,
+    parent_id: Option<i32>,
+    data: AppendSessionMessageData,
+) -> crate::Result<session_message::Data> {
+    let cited_chunks = serde_json::to_string(&data.cited_chunks)?;
+    let token_count = count_tokens(&data.content) as i32;
+    Ok(db
+        .session_message()
+        .create(
+            session::id::equals(data.session_id),
+            data.role,
+            data.content,
+            vec![
+                parent_id
+                    .map(|id| session_message::parent::connect(session_message::id::equals(id))),
+                Some(session_message::token_count::set(Some(token_count))),
+                Some(session_message::prompt_tokens::set(data.prompt_tokens)),
+                Some(session_message::completion_tokens::set(
+                    data.completion_tokens,
+                )),
+                Some(session_message::cited_chunks::set(cited_chunks)),
+                Some(session_message::model::set(data.model)),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        )
+        .exec()
+        .await?)
+}
+
+#[derive(Deserialize, Type)]
+pub struct ForkSessionMessageData {
+    content: String,
+    #[serde(rename = "promptTokens")]
+    prompt_tokens: Option<i32>,
+    #[serde(rename = "completionTokens")]
+    completion_tokens: Option<i32>,
+    #[serde(rename = "citedChunks")]
+    cited_chunks: Vec<ChunkRef>,
+    model: Option<String>,
+}
+
+/// Create an alternative version of a message, such as an edited question or a regenerated
+/// answer, and make it the active branch.
+///
+/// The new message shares the role and the parent of the original one, so that the original
+/// branch is kept and can be switched back to.
+#[tauri::command]
+#[specta::specta]
+pub async fn fork_session_message(
+    db: DbState<'_>,
+    message_id: i32,
+    data: ForkSessionMessageData,
+) -> crate::Result<SessionMessageExData> {
+    let db = db.active();
+    let original = db
+        .session_message()
+        .find_unique(session_message::id::equals(message_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Session message {} not found", message_id)))?;
+    let cited_chunks = serde_json::to_string(&data.cited_chunks)?;
+    let token_count = count_tokens(&data.content) as i32;
+    let message = db
+        .session_message()
+        .create(
+            session::id::equals(original.session_id),
+            original.role,
+            data.content,
+            vec![
+                original
+                    .parent_id
+                    .map(|id| session_message::parent::connect(session_message::id::equals(id))),
+                Some(session_message::token_count::set(Some(token_count))),
+                Some(session_message::prompt_tokens::set(data.prompt_tokens)),
+                Some(session_message::completion_tokens::set(
+                    data.completion_tokens,
+                )),
+                Some(session_message::cited_chunks::set(cited_chunks)),
+                Some(session_message::model::set(data.model)),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        )
+        .exec()
+        .await?;
+    activate_message(db, original.session_id, message.id).await?;
+    SessionMessageExData::from_data(message)
+}
+
+#[derive(Serialize, Type)]
+pub struct SessionBranchData {
+    /// The last message of the branch.
+    leaf: SessionMessageExData,
+    /// Number of messages from the root to the leaf.
+    length: i32,
+    active: bool,
+}
+
+/// List all branches of a session, one per leaf message, in the order of their creation.
+#[tauri::command]
+#[specta::specta]
+pub async fn list_session_branches(
+    db: DbState<'_>,
+    session_id: i32,
+) -> crate::Result<Vec<SessionBranchData>> {
+    let db = db.active();
+    let tree = SessionTree::load(db, session_id).await?;
+    let active_leaf_id = tree.active_leaf_id();
+    tree.leaves()
+        .into_iter()
+        .map(|leaf| {
+            Ok(SessionBranchData {
+                length: tree.branch_to(Some(leaf.id)).len() as i32,
+                active: Some(leaf.id) == active_leaf_id,
+                leaf: SessionMessageExData::from_data(leaf.clone())?,
+            })
+        })
+        .collect()
+}
+
+/// Switch the active branch of a session to the one passing through a message.
+///
+/// If the message has replies, the branch continues with the latest reply at each level. Returns
+/// the messages of the new active branch.
+#[tauri::command]
+#[specta::specta]
+pub async fn switch_session_branch(
+    db: DbState<'_>,
+    session_id: i32,
+    message_id: i32,
+) -> crate::Result<Vec<SessionMessageExData>> {
+    let db = db.active();
+    let tree = SessionTree::load(db, session_id).await?;
+    let leaf_id = tree.latest_leaf_under(message_id).ok_or_else(|| {
+        Error::msg(format!(
+            "Session message {} not found in session {}",
+            message_id, session_id
+        ))
+    })?;
+    activate_message(db, session_id, leaf_id).await?;
+    tree.branch_to(Some(leaf_id))
+        .into_iter()
+        .cloned()
+        .map(SessionMessageExData::from_data)
+        .collect()
+}
+
+#[derive(Deserialize, Type)]
+pub struct UpdateSessionMessageData {
+    id: i32,
+    content: Option<String>,
+    #[serde(rename = "citedChunks")]
+    cited_chunks: Option<Vec<ChunkRef>>,
+}
+
+/// Edit the content or the citations of a message.
+#[tauri::command]
+#[specta::specta]
+pub async fn update_session_message(
+    db: DbState<'_>,
+    data: UpdateSessionMessageData,
+) -> crate::Result<SessionMessageExData> {
+    let db = db.active();
+    let cited_chunks = data
+        .cited_chunks
+        .map(|chunks| serde_json::to_string(&chunks))
+        .transpose()?;
+    db.session_message()
+        .update(
+            session_message::id::equals(data.id),
+            vec![
+                data.content.as_ref().map(|content| {
+                    session_message::token_count::set(Some(count_tokens(content) as i32))
+                }),
+                data.content.map(session_message::content::set),
+                cited_chunks.map(session_message::cited_chunks::set),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        )
+        .exec()
+        .await
+        .map(SessionMessageExData::from_data)?
+}
+
+/// Delete a message together with all the replies following it.
+///
+/// If the active branch is deleted, its parent message becomes the active one.
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_session_message(
+    db: DbState<'_>,
+    message_id: i32,
+) -> crate::Result<SessionMessageExData> {
+    let db = db.active();
+    let message = db
+        .session_message()
+        .delete(session_message::id::equals(message_id))
+        .exec()
+        .await?;
+    let session = db
+        .session()
+        .find_unique(session::id::equals(message.session_id))
+        .exec()
+        .await?;
+    if let (Some(session), Some(parent_id)) = (session, message.parent_id) {
+        if session.active_message_id.is_none() {
+            activate_message(db, session.id, parent_id).await?;
+        }
+    }
+    SessionMessageExData::from_data(message)
+}
+
+async fn activate_message(
+    db: &PrismaClient,
+    session_id: i32,
+    message_id: i32,
+) -> crate::Result<()> {
+    db.session()
+        .update(
+            session::id::equals(session_id),
+            vec![session::active_message::connect(
+                session_message::id::equals(message_id),
+            )],
+        )
+        .exec()
+        .await?;
+    Ok(())
+}
+
+/// All messages of a session, linked into a tree by their parents.
+struct SessionTree {
+    active_message_id: Option<i32>,
+    messages: BTreeMap<i32, session_message::Data>,
+}
+
+impl SessionTree {
+    async fn load(db: &PrismaClient, session_id: i32) -> crate::Result<Self> {
+        let session = db
+            .session()
+            .find_unique(session::id::equals(session_id))
+            .exec()
+            .await?
+            .ok_or_else(|| Error::msg(format!("Session {} not found", session_id)))?;
+        let messages = db
+            .session_message()
+            .find_many(vec![session_message::session_id::equals(session_id)])
+            .exec()
+            .await?;
+        Ok(Self {
+            active_message_id: session.active_message_id,
+            messages: messages.into_iter().map(|m| (m.id, m)).collect(),
+        })
+    }
+
+    /// The active message, falling back to the latest message if none is active.
+    fn active_leaf_id(&self) -> Option<i32> {
+        self.active_message_id
+            .filter(|id| self.messages.contains_key(id))
+            .or_else(|| self.messages.keys().next_back().copied())
+    }
+
+    /// Messages from the root to `leaf_id` following the parents.
+    fn branch_to(&self, leaf_id: Option<i32>) -> Vec<&session_message::Data> {
+        let mut branch = vec![];
+        let mut next = leaf_id.and_then(|id| self.messages.get(&id));
+        while let Some(message) = next {
+            branch.push(message);
+            next = message.parent_id.and_then(|id| self.messages.get(&id));
+        }
+        branch.reverse();
+        branch
+    }
+
+    /// Messages without replies, ordered by id.
+    fn leaves(&self) -> Vec<&session_message::Data> {
+        let parents = self
+            .messages
+            .values()
+            .filter_map(|message| message.parent_id)
+            .collect::<HashSet<_>>();
+        self.messages
+            .values()
+            .filter(|message| !parents.contains(&message.id))
+            .collect()
+    }
+
+    /// The leaf reached from `message_id` by always following the latest reply.
+    fn latest_leaf_under(&self, message_id: i32) -> Option<i32> {
+        let mut current = self.messages.get(&message_id)?.id;
+        while let Some(reply) = self
+            .messages
+            .values()
+            .filter(|message| message.parent_id == Some(current))
+            .map(|message| message.id)
+            .max()
+        {
+            current = reply;
+        }
+        Some(current)
+    }
+}
@@ -1,12 +1,15 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use specta::Type;
 
+use crate::commands::db::session_messages;
 use crate::commands::db::DbState;
+use crate::core::session_search::{self, MessageSnippet};
 use crate::prisma::{collection_index, session};
 
 #[tauri::command]
 #[specta::specta]
 pub async fn delete_sessions_by_index_id(db: DbState<'_>, index_id: String) -> crate::Result<i32> {
+    let db = db.active();
     Ok(db
         .session()
         .delete_many(vec![session::index_id::equals(index_id)])
@@ -20,6 +23,7 @@ pub async fn delete_sessions_by_index_ids(
     db: DbState<'_>,
     index_ids: Vec<String>,
 ) -> crate::Result<i32> {
+    let db = db.active();
     Ok(db
         .session()
         .delete_many(vec![session::index_id::in_vec(index_ids)])
@@ -33,6 +37,7 @@ pub async fn delete_session_by_id(
     db: DbState<'_>,
     session_id: i32,
 ) -> crate::Result<session::Data> {
+    let db = db.active();
     Ok(db
         .session()
         .delete(session::id::equals(session_id))
@@ -43,6 +48,7 @@ pub async fn delete_session_by_id(
 #[tauri::command]
 #[specta::specta]
 pub async fn get_sessions(db: DbState<'_>) -> crate::Result<Vec<session::Data>> {
+    let db = db.active();
     Ok(db.session().find_many(vec![]).exec().await?)
 }
 
@@ -52,6 +58,7 @@ pub async fn get_sessions_by_index_id(
     db: DbState<'_>,
     index_id: String,
 ) -> crate::Result<Vec<session::Data>> {
+    let db = db.active();
     Ok(db
         .session()
         .find_many(vec![session::index_id::equals(index_id)])
@@ -59,6 +66,44 @@ pub async fn get_sessions_by_index_id(
         .await?)
 }
 
+#[derive(Serialize, Type)]
+pub struct SessionSearchResult {
+    session: session::Data,
+    /// The most relevant matched messages of the session, with the matched terms highlighted.
+    snippets: Vec<MessageSnippet>,
+}
+
+/// Full-text search the messages of all sessions, or of the sessions over a collection.
+///
+/// Sessions are ordered by their most relevant message.
+#[tauri::command]
+#[specta::specta]
+pub async fn search_sessions(
+    db: DbState<'_>,
+    query: String,
+    collection_id: Option<i32>,
+) -> crate::Result<Vec<SessionSearchResult>> {
+    let db = db.active();
+    let matches = session_search::search(db, &query, collection_id).await?;
+    let mut sessions = db
+        .session()
+        .find_many(vec![session::id::in_vec(
+            matches.iter().map(|m| m.session_id).collect(),
+        )])
+        .exec()
+        .await?;
+    Ok(matches
+        .into_iter()
+        .filter_map(|m| {
+            let pos = sessions.iter().position(|s| s.id == m.session_id)?;
+            Some(SessionSearchResult {
+                session: sessions.swap_remove(pos),
+                snippets: m.snippets,
+            })
+        })
+        .collect())
+}
+
 #[derive(Deserialize, Type)]
 pub struct CreateSessionData {
     name: String,
@@ -73,6 +118,7 @@ pub async fn create_session(
     db: DbState<'_>,
     data: CreateSessionData,
 ) -> crate::Result<session::Data> {
+    let db = db.active();
     Ok(db
         .session()
         .create(
@@ -92,24 +138,37 @@ pub struct UpdateSessionData {
     history: Option<String>,
 }
 
+/// Update the name or the history of a session.
+///
+/// The messages of the active branch of the session are synced with the history, so that
+/// whatever reads the messages sees the conversation of the chat UI.
 #[tauri::command]
 #[specta::specta]
 pub async fn update_session(
     db: DbState<'_>,
     data: UpdateSessionData,
 ) -> crate::Result<session::Data> {
-    Ok(db
-        .session()
-        .update(
-            session::id::equals(data.id),
-            vec![
-                data.history.map(session::history::set),
-                data.name.map(session::name::set),
-            ]
-            .into_iter()
-            .flatten()
-            .collect(),
-        )
-        .exec()
-        .await?)
+    let db = db.active();
+    db._transaction()
+        .run(|tx| async move {
+            if let Some(history) = &data.history {
+                session_messages::sync_history(&tx, data.id, history).await?;
+            }
+            let session = tx
+                .session()
+                .update(
+                    session::id::equals(data.id),
+                    vec![
+                        data.history.map(session::history::set),
+                        data.name.map(session::name::set),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+                )
+                .exec()
+                .await?;
+            crate::Result::Ok(session)
+        })
+        .await
 }
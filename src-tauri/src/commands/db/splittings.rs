@@ -1,5 +1,6 @@
+use crate::commands::db::index_profiles::DeletionReport;
 use crate::commands::db::DbState;
-use crate::prisma::splitting;
+use crate::prisma::{index_profile, splitting, PrismaClient};
 use serde::Deserialize;
 use specta::Type;
 
@@ -20,6 +21,13 @@ pub struct CreateGetOrCreateSplittingData {
 pub async fn get_or_create_splitting(
     db: DbState<'_>,
     data: CreateGetOrCreateSplittingData,
+) -> crate::Result<splitting::Data> {
+    find_or_create_splitting(db.active(), data).await
+}
+
+async fn find_or_create_splitting(
+    db: &PrismaClient,
+    data: CreateGetOrCreateSplittingData,
 ) -> crate::Result<splitting::Data> {
     let existing = db
         .splitting()
@@ -51,9 +59,66 @@ pub enum GetOrCreateSplittingData {
 pub async fn get_or_create_splitting_id(
     db: DbState<'_>,
     data: GetOrCreateSplittingData,
+) -> crate::Result<i32> {
+    splitting_id_of(db.active(), data).await
+}
+
+/// The id of a splitting, which is created first if given by its config and not existing yet.
+pub(crate) async fn splitting_id_of(
+    db: &PrismaClient,
+    data: GetOrCreateSplittingData,
 ) -> crate::Result<i32> {
     match data {
         GetOrCreateSplittingData::Id(id) => Ok(id),
-        GetOrCreateSplittingData::Config(data) => Ok(get_or_create_splitting(db, data).await?.id),
+        GetOrCreateSplittingData::Config(data) => Ok(find_or_create_splitting(db, data).await?.id),
+    }
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_splittings(db: DbState<'_>) -> crate::Result<Vec<splitting::Data>> {
+    let db = db.active();
+    Ok(db.splitting().find_many(vec![]).exec().await?)
+}
+
+/// Delete a splitting, along with the document chunks split by it.
+///
+/// It is refused if index profiles use the splitting, unless `cascade` is set to delete them
+/// along with their collection indexes and their vectors.
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_splitting(
+    db: DbState<'_>,
+    splitting_id: i32,
+    cascade: bool,
+) -> crate::Result<DeletionReport> {
+    let db = db.active();
+    if cascade {
+        DeletionReport::delete_remote_vectors(
+            db,
+            vec![index_profile::splitting_id::equals(splitting_id)],
+        )
+        .await?;
     }
+    db._transaction()
+        .run(|tx| async move {
+            let report = DeletionReport::of_profiles(
+                &tx,
+                vec![index_profile::splitting_id::equals(splitting_id)],
+            )
+            .await?;
+            if report.is_refused(cascade) {
+                return Ok(report);
+            }
+            tx.index_profile()
+                .delete_many(vec![index_profile::splitting_id::equals(splitting_id)])
+                .exec()
+                .await?;
+            tx.splitting()
+                .delete(splitting::id::equals(splitting_id))
+                .exec()
+                .await?;
+            crate::Result::Ok(report.done())
+        })
+        .await
 }
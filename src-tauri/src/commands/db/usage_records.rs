@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, HashMap};
+
+use prisma_client_rust::chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::commands::db::DbState;
+use crate::prisma::{embeddings_client, index_profile, session, usage_record, PrismaClient};
+
+fn time_filters(
+    from: Option<DateTime<FixedOffset>>,
+    to: Option<DateTime<FixedOffset>>,
+) -> Vec<usage_record::WhereParam> {
+    vec![
+        from.map(usage_record::create_time::gte),
+        to.map(usage_record::create_time::lt),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Get the usages recorded from `from` (inclusive) to `to` (exclusive), latest first.
+#[tauri::command]
+#[specta::specta]
+pub async fn get_usage_records(
+    db: DbState<'_>,
+    from: Option<DateTime<FixedOffset>>,
+    to: Option<DateTime<FixedOffset>>,
+) -> crate::Result<Vec<usage_record::Data>> {
+    let db = db.active();
+    Ok(db
+        .usage_record()
+        .find_many(time_filters(from, to))
+        .order_by(usage_record::create_time::order(
+            prisma_client_rust::Direction::Desc,
+        ))
+        .exec()
+        .await?)
+}
+
+#[derive(Deserialize, Type, Clone, Copy, Debug)]
+pub enum UsageGroupBy {
+    /// The local day the usages are recorded on.
+    Day,
+    EmbeddingsClient,
+    IndexProfile,
+    Session,
+}
+
+#[derive(Serialize, Type, Default)]
+pub struct UsageAggregate {
+    /// The day as `YYYY-MM-DD`, or the id of the embeddings client, index profile or session.
+    /// Usages not spent for any of them are grouped under no key.
+    key: Option<String>,
+    /// Name of the embeddings client, index profile or session, if it still exists.
+    name: Option<String>,
+    calls: i32,
+    #[serde(rename = "promptTokens")]
+    prompt_tokens: i32,
+    #[serde(rename = "completionTokens")]
+    completion_tokens: i32,
+    /// Cost in dollars.
+    cost: f64,
+}
+
+/// Sum up the usages recorded from `from` (inclusive) to `to` (exclusive) by day, embeddings
+/// client, index profile or session.
+#[tauri::command]
+#[specta::specta]
+pub async fn aggregate_usage(
+    db: DbState<'_>,
+    group_by: UsageGroupBy,
+    from: Option<DateTime<FixedOffset>>,
+    to: Option<DateTime<FixedOffset>>,
+) -> crate::Result<Vec<UsageAggregate>> {
+    let db = db.active();
+    let records = db
+        .usage_record()
+        .find_many(time_filters(from, to))
+        .exec()
+        .await?;
+
+    let mut groups: BTreeMap<Option<String>, UsageAggregate> = BTreeMap::new();
+    let mut ids = vec![];
+    for record in records {
+        let key = match group_by {
+            UsageGroupBy::Day => Some(
+                record
+                    .create_time
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d")
+                    .to_string(),
+            ),
+            UsageGroupBy::EmbeddingsClient => record.embeddings_client_id.map(|id| id.to_string()),
+            UsageGroupBy::IndexProfile => record.index_profile_id.map(|id| id.to_string()),
+            UsageGroupBy::Session => record.session_id.map(|id| id.to_string()),
+        };
+        let group = groups.entry(key.clone()).or_insert_with(|| {
+            if let Some(id) = key.as_ref().and_then(|key| key.parse::<i32>().ok()) {
+                ids.push(id);
+            }
+            UsageAggregate {
+                key,
+                ..Default::default()
+            }
+        });
+        group.calls += 1;
+        group.prompt_tokens = group.prompt_tokens.saturating_add(record.prompt_tokens);
+        group.completion_tokens = group
+            .completion_tokens
+            .saturating_add(record.completion_tokens);
+        group.cost += record.cost;
+    }
+
+    let names = names_of(db, group_by, ids).await?;
+    Ok(groups
+        .into_values()
+        .map(|mut group| {
+            group.name = group
+                .key
+                .as_ref()
+                .and_then(|key| key.parse::<i32>().ok())
+                .and_then(|id| names.get(&id).cloned());
+            group
+        })
+        .collect())
+}
+
+/// Names of the embeddings clients, index profiles or sessions of `ids` that still exist.
+async fn names_of(
+    db: &PrismaClient,
+    group_by: UsageGroupBy,
+    ids: Vec<i32>,
+) -> crate::Result<HashMap<i32, String>> {
+    Ok(match group_by {
+        UsageGroupBy::Day => HashMap::new(),
+        UsageGroupBy::EmbeddingsClient => db
+            .embeddings_client()
+            .find_many(vec![embeddings_client::id::in_vec(ids)])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|client| (client.id, client.name))
+            .collect(),
+        UsageGroupBy::IndexProfile => db
+            .index_profile()
+            .find_many(vec![index_profile::id::in_vec(ids)])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|profile| (profile.id, profile.name))
+            .collect(),
+        UsageGroupBy::Session => db
+            .session()
+            .find_many(vec![session::id::in_vec(ids)])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|session| (session.id, session.name))
+            .collect(),
+    })
+}
@@ -1,8 +1,11 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+use crate::commands::db::index_profiles::DeletionReport;
 use crate::commands::db::DbState;
-use crate::prisma::vector_db_client;
+use crate::core::client_types::{self, ClientPayload, VectorDbClientInfo};
+use crate::core::secrets::{self, ClientKind};
+use crate::prisma::{index_profile, vector_db_client};
 
 #[derive(Serialize, Type)]
 pub struct VectorDbClientExData {
@@ -18,7 +21,7 @@ impl VectorDbClientExData {
             id: data.id,
             name: data.name,
             r#type: data.r#type,
-            info: serde_json::from_str(data.info.as_str())?,
+            info: secrets::redact_info(serde_json::from_str(data.info.as_str())?),
         })
     }
 }
@@ -29,6 +32,7 @@ pub async fn get_vector_db_client_by_id(
     db: DbState<'_>,
     client_id: i32,
 ) -> crate::Result<Option<VectorDbClientExData>> {
+    let db = db.active();
     db.vector_db_client()
         .find_unique(vector_db_client::id::equals(client_id))
         .exec()
@@ -39,6 +43,7 @@ pub async fn get_vector_db_client_by_id(
 #[tauri::command]
 #[specta::specta]
 pub async fn get_vector_db_clients(db: DbState<'_>) -> crate::Result<Vec<VectorDbClientExData>> {
+    let db = db.active();
     db.vector_db_client()
         .find_many(vec![])
         .exec()
@@ -53,8 +58,8 @@ pub async fn get_vector_db_clients(db: DbState<'_>) -> crate::Result<Vec<VectorD
 #[derive(Deserialize, Type)]
 pub struct CreateVectorDbClientData {
     name: String,
-    r#type: String,
-    info: serde_json::Value,
+    #[serde(flatten)]
+    client: VectorDbClientInfo,
 }
 
 #[tauri::command]
@@ -63,12 +68,21 @@ pub async fn create_vector_db_client(
     db: DbState<'_>,
     data: CreateVectorDbClientData,
 ) -> crate::Result<VectorDbClientExData> {
-    let info = serde_json::to_string(&data.info)?;
-    db.vector_db_client()
-        .create(data.name, data.r#type, info, vec![])
-        .exec()
+    let db = db.active();
+    client_types::validate(&data.name, &data.client)?;
+    let (r#type, info) = data.client.to_stored()?;
+    db._transaction()
+        .run(|tx| async move {
+            secrets::check_duplicate(&tx, ClientKind::VectorDb, None, &r#type, &info).await?;
+            let info = secrets::seal_info(&tx, info).await?;
+            let client = tx
+                .vector_db_client()
+                .create(data.name, r#type, info, vec![])
+                .exec()
+                .await?;
+            VectorDbClientExData::from_data(client)
+        })
         .await
-        .map(VectorDbClientExData::from_data)?
 }
 
 #[tauri::command]
@@ -78,18 +92,78 @@ pub async fn upsert_vector_db_client(
     client_id: i32,
     data: CreateVectorDbClientData,
 ) -> crate::Result<VectorDbClientExData> {
-    let info = serde_json::to_string(&data.info)?;
-    db.vector_db_client()
-        .upsert(
-            vector_db_client::id::equals(client_id),
-            (data.name.clone(), data.r#type.clone(), info.clone(), vec![]),
-            vec![
-                vector_db_client::name::set(data.name),
-                vector_db_client::r#type::set(data.r#type),
-                vector_db_client::info::set(info),
-            ],
+    let db = db.active();
+    client_types::validate(&data.name, &data.client)?;
+    let (r#type, mut info) = data.client.to_stored()?;
+    db._transaction()
+        .run(|tx| async move {
+            let stored = tx
+                .vector_db_client()
+                .find_unique(vector_db_client::id::equals(client_id))
+                .exec()
+                .await?;
+            if let Some(stored) = stored {
+                secrets::restore_redacted(&mut info, &stored.info)?;
+            }
+            secrets::check_duplicate(&tx, ClientKind::VectorDb, Some(client_id), &r#type, &info)
+                .await?;
+            let info = secrets::seal_info(&tx, info).await?;
+            let client = tx
+                .vector_db_client()
+                .upsert(
+                    vector_db_client::id::equals(client_id),
+                    (data.name.clone(), r#type.clone(), info.clone(), vec![]),
+                    vec![
+                        vector_db_client::name::set(data.name),
+                        vector_db_client::r#type::set(r#type),
+                        vector_db_client::info::set(info),
+                    ],
+                )
+                .exec()
+                .await?;
+            VectorDbClientExData::from_data(client)
+        })
+        .await
+}
+
+/// Delete a vector db client.
+///
+/// It is refused if index profiles use the client, unless `cascade` is set to delete them along
+/// with their collection indexes and their vectors.
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_vector_db_client(
+    db: DbState<'_>,
+    client_id: i32,
+    cascade: bool,
+) -> crate::Result<DeletionReport> {
+    let db = db.active();
+    if cascade {
+        DeletionReport::delete_remote_vectors(
+            db,
+            vec![index_profile::vector_db_client_id::equals(client_id)],
         )
-        .exec()
+        .await?;
+    }
+    db._transaction()
+        .run(|tx| async move {
+            let report = DeletionReport::of_profiles(
+                &tx,
+                vec![index_profile::vector_db_client_id::equals(client_id)],
+            )
+            .await?;
+            if report.is_refused(cascade) {
+                return Ok(report);
+            }
+            tx.index_profile()
+                .delete_many(vec![index_profile::vector_db_client_id::equals(client_id)])
+                .exec()
+                .await?;
+            tx.vector_db_client()
+                .delete(vector_db_client::id::equals(client_id))
+                .exec()
+                .await?;
+            crate::Result::Ok(report.done())
+        })
         .await
-        .map(VectorDbClientExData::from_data)?
 }
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use specta::Type;
 
+use crate::commands::db::index_profiles::DeletionReport;
 use crate::commands::db::DbState;
-use crate::prisma::vector_db_config;
+use crate::core::client_types::{self, ClientPayload, VectorDbConfigMeta};
+use crate::prisma::{index_profile, vector_db_config};
 
 #[derive(Serialize, Type)]
 pub struct VectorDbConfigExData {
@@ -31,6 +33,7 @@ pub async fn get_vector_db_config_by_id(
     db: DbState<'_>,
     vector_db_config_id: i32,
 ) -> crate::Result<Option<VectorDbConfigExData>> {
+    let db = db.active();
     db.vector_db_config()
         .find_unique(vector_db_config::id::equals(vector_db_config_id))
         .exec()
@@ -41,6 +44,7 @@ pub async fn get_vector_db_config_by_id(
 #[tauri::command]
 #[specta::specta]
 pub async fn get_vector_db_configs(db: DbState<'_>) -> crate::Result<Vec<VectorDbConfigExData>> {
+    let db = db.active();
     db.vector_db_config()
         .find_many(vec![])
         .exec()
@@ -55,9 +59,8 @@ pub async fn get_vector_db_configs(db: DbState<'_>) -> crate::Result<Vec<VectorD
 #[derive(Deserialize, Type)]
 pub struct CreateVectorDbData {
     name: String,
-    #[serde(rename = "clientType")]
-    client_type: String,
-    meta: serde_json::Value,
+    #[serde(flatten)]
+    config: VectorDbConfigMeta,
 }
 
 #[tauri::command]
@@ -66,9 +69,12 @@ pub async fn create_vector_db_config(
     db: DbState<'_>,
     data: CreateVectorDbData,
 ) -> crate::Result<VectorDbConfigExData> {
-    let meta = serde_json::to_string(&data.meta)?;
+    let db = db.active();
+    client_types::validate(&data.name, &data.config)?;
+    let (client_type, meta) = data.config.to_stored()?;
+    let meta = serde_json::to_string(&meta)?;
     db.vector_db_config()
-        .create(data.name, data.client_type, meta, vec![])
+        .create(data.name, client_type, meta, vec![])
         .exec()
         .await
         .map(VectorDbConfigExData::from_data)?
@@ -81,19 +87,17 @@ pub async fn upsert_vector_db_config(
     config_id: i32,
     data: CreateVectorDbData,
 ) -> crate::Result<VectorDbConfigExData> {
-    let meta = serde_json::to_string(&data.meta)?;
+    let db = db.active();
+    client_types::validate(&data.name, &data.config)?;
+    let (client_type, meta) = data.config.to_stored()?;
+    let meta = serde_json::to_string(&meta)?;
     db.vector_db_config()
         .upsert(
             vector_db_config::id::equals(config_id),
-            (
-                data.name.clone(),
-                data.client_type.clone(),
-                meta.clone(),
-                vec![],
-            ),
+            (data.name.clone(), client_type.clone(), meta.clone(), vec![]),
             vec![
                 vector_db_config::name::set(data.name),
-                vector_db_config::client_type::set(data.client_type),
+                vector_db_config::client_type::set(client_type),
                 vector_db_config::meta::set(meta),
             ],
         )
@@ -101,3 +105,45 @@ pub async fn upsert_vector_db_config(
         .await
         .map(VectorDbConfigExData::from_data)?
 }
+
+/// Delete a vector db config.
+///
+/// It is refused if index profiles use the config, unless `cascade` is set to delete them along
+/// with their collection indexes and their vectors.
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_vector_db_config(
+    db: DbState<'_>,
+    config_id: i32,
+    cascade: bool,
+) -> crate::Result<DeletionReport> {
+    let db = db.active();
+    if cascade {
+        DeletionReport::delete_remote_vectors(
+            db,
+            vec![index_profile::vector_db_config_id::equals(config_id)],
+        )
+        .await?;
+    }
+    db._transaction()
+        .run(|tx| async move {
+            let report = DeletionReport::of_profiles(
+                &tx,
+                vec![index_profile::vector_db_config_id::equals(config_id)],
+            )
+            .await?;
+            if report.is_refused(cascade) {
+                return Ok(report);
+            }
+            tx.index_profile()
+                .delete_many(vec![index_profile::vector_db_config_id::equals(config_id)])
+                .exec()
+                .await?;
+            tx.vector_db_config()
+                .delete(vector_db_config::id::equals(config_id))
+                .exec()
+                .await?;
+            crate::Result::Ok(report.done())
+        })
+        .await
+}
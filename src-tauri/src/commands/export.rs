@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use prisma_client_rust::chrono::Local;
+
+use crate::commands::db::session_messages;
+use crate::commands::db::DbState;
+use crate::core::document_metadata::Metadata;
+use crate::core::result::Error;
+use crate::core::retrieval::{page_of, ChunkRef};
+use crate::core::session_export::{
+    self, excerpt, Citation, SessionExportFormat, Transcript, TranscriptMessage,
+};
+use crate::prisma::{document_chunk, session, PrismaClient};
+
+document_chunk::include!(cited_chunk {
+    document: include { metadata }
+});
+
+/// Export the active branch of a session as a transcript file at `path`.
+///
+/// The chunks cited by each message are resolved to the filename, the metadata, the page and an excerpt of
+/// their documents. Citations whose chunks no longer exist are left out.
+#[tauri::command]
+#[specta::specta]
+pub async fn export_session(
+    db: DbState<'_>,
+    session_id: i32,
+    format: SessionExportFormat,
+    path: String,
+) -> crate::Result<()> {
+    let db = db.active();
+    let session = db
+        .session()
+        .find_unique(session::id::equals(session_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Session {} not found", session_id)))?;
+    let messages = session_messages::active_branch(db, session_id, None, None).await?;
+
+    let mut citations = HashMap::new();
+    for chunk_ref in messages.iter().flat_map(|message| &message.cited_chunks) {
+        if !citations.contains_key(chunk_ref) {
+            let citation = resolve_citation(db, chunk_ref).await?;
+            citations.insert(chunk_ref.clone(), citation);
+        }
+    }
+
+    let transcript = Transcript {
+        name: session.name,
+        export_time: Local::now().to_rfc3339(),
+        messages: messages
+            .into_iter()
+            .map(|message| TranscriptMessage {
+                citations: message
+                    .cited_chunks
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, chunk_ref)| {
+                        let citation = citations.get(chunk_ref)?.as_ref()?;
+                        Some(Citation {
+                            number: i + 1,
+                            ..citation.clone()
+                        })
+                    })
+                    .collect(),
+                role: message.role,
+                content: message.content,
+                create_time: message.create_time.to_rfc3339(),
+                model: message.model,
+            })
+            .collect(),
+    };
+    let content = session_export::render(&transcript, format)?;
+    tokio::fs::write(path, content).await?;
+    Ok(())
+}
+
+/// Look up the document of a cited chunk, leaving the citation number to the caller.
+async fn resolve_citation(
+    db: &PrismaClient,
+    chunk_ref: &ChunkRef,
+) -> crate::Result<Option<Citation>> {
+    let chunk = db
+        .document_chunk()
+        .find_unique(document_chunk::document_id_splitting_id_no(
+            chunk_ref.document_id,
+            chunk_ref.splitting_id,
+            chunk_ref.no,
+        ))
+        .include(cited_chunk::include())
+        .exec()
+        .await?;
+    Ok(chunk.map(|chunk| {
+        let meta = serde_json::from_str(&chunk.meta).unwrap_or_default();
+        Citation {
+            number: 0,
+            document_id: chunk.document_id,
+            filename: chunk.document.filename,
+            metadata: chunk
+                .document
+                .metadata
+                .as_ref()
+                .and_then(|metadata| Metadata::from_data(metadata).ok()),
+            page: page_of(&meta),
+            excerpt: excerpt(&chunk.content),
+        }
+    }))
+}
@@ -1,6 +1,15 @@
+use crate::core::fs::{hash_bytes, HashAlgo};
+
 #[tauri::command]
 #[specta::specta]
 pub async fn hash_str_in_md5(data: String) -> crate::Result<String> {
     let digest = md5::compute(data);
     Ok(format!("{:x}", digest))
 }
+
+/// Hash a string by a hash algorithm, the default one if not given.
+#[tauri::command]
+#[specta::specta]
+pub async fn hash_str(data: String, hash_algo: Option<HashAlgo>) -> crate::Result<String> {
+    Ok(hash_bytes(hash_algo.unwrap_or_default(), data))
+}
@@ -0,0 +1,38 @@
+use crate::commands::db::DbState;
+use crate::core::backup::{self, BackupKind};
+use crate::core::maintenance::{self, DatabaseReport, RepairReport, VacuumReport};
+
+/// Check the integrity of the database of the workspace, and report the orphan rows, the sizes of
+/// the tables and the totals of the cached embedding vectors.
+#[tauri::command]
+#[specta::specta]
+pub async fn check_database(db: DbState<'_>) -> crate::Result<DatabaseReport> {
+    let db = db.active();
+    maintenance::check(db).await
+}
+
+/// Delete the orphan rows reported by `check_database`, and the cached embedding vectors not of
+/// any chunk if `prune_vectors` is set.
+///
+/// The database is backed up first, and repaired in a transaction.
+#[tauri::command]
+#[specta::specta]
+pub async fn repair_database(db: DbState<'_>, prune_vectors: bool) -> crate::Result<RepairReport> {
+    let (db, db_path) = db.active_with_db_path();
+    tokio::task::spawn_blocking(move || {
+        backup::create(&db_path, BackupKind::PreRepair)?;
+        backup::rotate(&db_path, BackupKind::PreRepair, backup::KEEP_PRE_REPAIR)
+    })
+    .await??;
+    db._transaction()
+        .run(|tx| async move { maintenance::repair(&tx, prune_vectors).await })
+        .await
+}
+
+/// Rebuild the database file of the workspace to give back the space of deleted rows.
+#[tauri::command]
+#[specta::specta]
+pub async fn vacuum_database(db: DbState<'_>) -> crate::Result<VacuumReport> {
+    let db = db.active();
+    maintenance::vacuum(db).await
+}
@@ -1,2 +1,16 @@
+pub mod backups;
+pub mod bibliography;
+pub mod collection_archive;
+pub mod completion;
+pub mod config_bundle;
+pub mod connection_test;
 pub mod db;
+pub mod export;
 pub mod fs;
+pub mod maintenance;
+pub mod qa;
+pub mod retrieval;
+pub mod secrets;
+pub mod tokenizer;
+pub mod vectors;
+pub mod workspaces;
@@ -0,0 +1,258 @@
+use tauri::Manager;
+
+use crate::commands::completion::{
+    done_payload, CompletionDeltaPayload, COMPLETION_DELTA_EVENT, COMPLETION_DONE_EVENT,
+};
+use crate::commands::db::session_messages::{self, AppendSessionMessageData, SessionMessageExData};
+use crate::commands::db::DbState;
+use crate::core::completion::{Completion, CompletionConfig, CompletionTasks};
+use crate::core::history::{pair_messages, parse_history, History, QaPair};
+use crate::core::qa::{self, QaAnswer, QaMode};
+use crate::core::result::Error;
+use crate::core::session_summary::{self, KEEP_RECENT_PAIRS};
+use crate::prisma::{session, PrismaClient};
+
+/// Event emitted with the updated session once its title or summary is regenerated.
+pub const SESSION_UPDATED_EVENT: &str = "session://updated";
+
+/// Answer a question over the collection index of a session.
+///
+/// The answer is streamed as `completion://delta` events like `start_completion`, and can be
+/// cancelled by `cancel_completion` as well. The whole answer is returned together with the
+/// chunks used as its sources, and the exchange is appended to the messages of the session.
+///
+/// Only the questions after the summary of the session are sent verbatim as the history. Once
+/// answered, the session is titled after its first exchange, and earlier questions are folded
+/// into the summary when the history grows too long. Both happen in the background and are
+/// notified by a `session://updated` event.
+#[tauri::command]
+#[specta::specta]
+pub async fn ask_collection(
+    app: tauri::AppHandle,
+    db: DbState<'_>,
+    tasks: tauri::State<'_, CompletionTasks>,
+    session_id: i32,
+    question: String,
+    mode: QaMode,
+    config: CompletionConfig,
+) -> crate::Result<QaAnswer> {
+    let db = db.active();
+    let session = db
+        .session()
+        .find_unique(session::id::equals(session_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Session {} not found", session_id)))?;
+    let messages = session_messages::active_branch(db, session_id, None, None).await?;
+    let first_exchange = messages.is_empty();
+    let history = if messages.is_empty() {
+        History {
+            summary: String::new(),
+            pairs: parse_history(&session.history),
+        }
+    } else {
+        let start = summarized_start(&session, &messages);
+        History {
+            summary: summary_before(&session, start),
+            pairs: pairs_of(&messages[start..]),
+        }
+    };
+    let completion = Completion::from_config(db, config).await?;
+    let task = tasks.register(session_id);
+    let task_id = task.id;
+
+    let result = tasks
+        .run(
+            task,
+            qa::ask(
+                db,
+                &session,
+                &history,
+                question.clone(),
+                mode,
+                &completion,
+                |delta| {
+                    let _ = app.emit_all(
+                        COMPLETION_DELTA_EVENT,
+                        CompletionDeltaPayload {
+                            session_id,
+                            task_id,
+                            delta: delta.to_string(),
+                        },
+                    );
+                },
+            ),
+        )
+        .await;
+
+    let _ = app.emit_all(
+        COMPLETION_DONE_EVENT,
+        done_payload(
+            session_id,
+            task_id,
+            result.as_ref().map(|answer| answer.answer.clone()),
+        ),
+    );
+    if let Err(err) = completion.record_usage(db, Some(session_id)).await {
+        log::error!(
+            "error while recording usage of session {}: {}",
+            session_id,
+            err
+        );
+    }
+    let answer = result?;
+    append_exchange(db, session_id, question, &answer, completion.model()).await?;
+
+    tauri::async_runtime::spawn(async move {
+        match maintain_session(db, session_id, first_exchange, &completion).await {
+            Ok(Some(session)) => {
+                let _ = app.emit_all(SESSION_UPDATED_EVENT, session);
+            }
+            Ok(None) => {}
+            Err(err) => log::error!("error while maintaining session {}: {}", session_id, err),
+        }
+        if let Err(err) = completion.record_usage(db, Some(session_id)).await {
+            log::error!(
+                "error while recording usage of session {}: {}",
+                session_id,
+                err
+            );
+        }
+    });
+    Ok(answer)
+}
+
+/// Record the question and its answer with the cited sources as messages of the session.
+async fn append_exchange(
+    db: &PrismaClient,
+    session_id: i32,
+    question: String,
+    answer: &QaAnswer,
+    model: &str,
+) -> crate::Result<()> {
+    session_messages::append(
+        db,
+        AppendSessionMessageData {
+            session_id,
+            parent_id: None,
+            role: "user".to_string(),
+            content: question,
+            prompt_tokens: None,
+            completion_tokens: None,
+            cited_chunks: vec![],
+            model: None,
+        },
+    )
+    .await?;
+    session_messages::append(
+        db,
+        AppendSessionMessageData {
+            session_id,
+            parent_id: None,
+            role: "assistant".to_string(),
+            content: answer.answer.clone(),
+            prompt_tokens: None,
+            completion_tokens: None,
+            cited_chunks: answer.sources.iter().map(|s| s.chunk_ref()).collect(),
+            model: Some(model.to_string()),
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// Title the session after its first exchange, and fold earlier questions into its summary if
+/// the history has grown too long. Returns the session if updated.
+async fn maintain_session(
+    db: &PrismaClient,
+    session_id: i32,
+    first_exchange: bool,
+    completion: &Completion,
+) -> crate::Result<Option<session::Data>> {
+    let session = db
+        .session()
+        .find_unique(session::id::equals(session_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Session {} not found", session_id)))?;
+    let messages = session_messages::active_branch(db, session_id, None, None).await?;
+
+    let mut params = vec![];
+    if first_exchange {
+        if let Some(pair) = pairs_of(&messages).first() {
+            let title = session_summary::generate_title(pair, completion).await?;
+            if !title.is_empty() {
+                params.push(session::name::set(title));
+            }
+        }
+    }
+
+    let start = summarized_start(&session, &messages);
+    let recent = &messages[start..];
+    let recent_tokens = recent.iter().map(|message| message.tokens()).sum();
+    if session_summary::needs_summary(pairs_of(recent).len(), recent_tokens) {
+        let cut = start_of_last_questions(recent, KEEP_RECENT_PAIRS);
+        if cut > 0 {
+            let summary = session_summary::summarize(
+                &summary_before(&session, start),
+                &pairs_of(&recent[..cut]),
+                completion,
+            )
+            .await?;
+            params.push(session::summary::set(summary));
+            params.push(session::summarized_message_id::set(Some(
+                recent[cut - 1].id,
+            )));
+        }
+    }
+
+    if params.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(
+        db.session()
+            .update(session::id::equals(session_id), params)
+            .exec()
+            .await?,
+    ))
+}
+
+/// Index of the first message not covered by the summary of the session.
+///
+/// The summary is ignored if it was made on another branch than the active one.
+fn summarized_start(session: &session::Data, messages: &[SessionMessageExData]) -> usize {
+    session
+        .summarized_message_id
+        .and_then(|id| messages.iter().position(|message| message.id == id))
+        .map(|pos| pos + 1)
+        .unwrap_or(0)
+}
+
+/// The summary of the messages before `start`.
+fn summary_before(session: &session::Data, start: usize) -> String {
+    if start > 0 {
+        session.summary.clone()
+    } else {
+        String::new()
+    }
+}
+
+/// Index of the first message of the last `n` questions, or 0 if there are not more questions.
+fn start_of_last_questions(messages: &[SessionMessageExData], n: usize) -> usize {
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| message.role == "user")
+        .map(|(i, _)| i)
+        .rev()
+        .nth(n.saturating_sub(1))
+        .unwrap_or(0)
+}
+
+fn pairs_of(messages: &[SessionMessageExData]) -> Vec<QaPair> {
+    pair_messages(
+        messages
+            .iter()
+            .map(|message| (message.role.as_str(), message.content.as_str())),
+    )
+}
@@ -0,0 +1,17 @@
+use crate::commands::db::DbState;
+use crate::core::retrieval::{self, RetrievedChunk};
+
+/// Retrieve the chunks of a collection index most relevant to a query.
+///
+/// The chunks are reranked if the index profile of the collection index has a reranker.
+#[tauri::command]
+#[specta::specta]
+pub async fn retrieve_from_collection_index(
+    db: DbState<'_>,
+    collection_index_id: String,
+    query: String,
+    top_k: i32,
+) -> crate::Result<Vec<RetrievedChunk>> {
+    let db = db.active();
+    retrieval::retrieve(db, collection_index_id, &query, top_k.max(0) as usize, None).await
+}
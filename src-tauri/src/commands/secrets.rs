@@ -0,0 +1,39 @@
+use crate::commands::db::DbState;
+use crate::core::secrets::{self, SecretsStatus};
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_secrets_status(db: DbState<'_>) -> crate::Result<SecretsStatus> {
+    let db = db.active();
+    secrets::status(db).await
+}
+
+/// Set the passphrase sealing the api keys and other secrets of clients.
+///
+/// `current` is the passphrase set before, if any. All the stored secrets are resealed by the new
+/// passphrase, and are left unlocked.
+#[tauri::command]
+#[specta::specta]
+pub async fn set_secrets_passphrase(
+    db: DbState<'_>,
+    current: Option<String>,
+    passphrase: String,
+) -> crate::Result<()> {
+    let db = db.active();
+    secrets::set_passphrase(db, current, passphrase).await
+}
+
+/// Unlock the secrets of clients for the backend to call the services with.
+#[tauri::command]
+#[specta::specta]
+pub async fn unlock_secrets(db: DbState<'_>, passphrase: String) -> crate::Result<()> {
+    let db = db.active();
+    secrets::unlock(db, &passphrase).await
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn lock_secrets() -> crate::Result<()> {
+    secrets::lock();
+    Ok(())
+}
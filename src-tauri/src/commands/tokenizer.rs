@@ -0,0 +1,37 @@
+use serde::Serialize;
+use specta::Type;
+
+use crate::core::completion::ChatMessage;
+use crate::core::tokenizer::{self, ContextBudget};
+
+/// Count the tokens of each text.
+#[tauri::command]
+#[specta::specta]
+pub async fn count_tokens(texts: Vec<String>) -> crate::Result<Vec<i32>> {
+    Ok(texts
+        .iter()
+        .map(|text| tokenizer::count_tokens(text) as i32)
+        .collect())
+}
+
+#[derive(Serialize, Type)]
+pub struct ChatTokenCount {
+    /// Tokens taken by the messages in a completion request.
+    tokens: i32,
+    /// Tokens the prompt may take in the context window of the model.
+    limit: i32,
+}
+
+/// Count the tokens of chat messages to be completed by a model, against the limit of the model.
+#[tauri::command]
+#[specta::specta]
+pub async fn count_chat_tokens(
+    messages: Vec<ChatMessage>,
+    model: String,
+    max_tokens: Option<i32>,
+) -> crate::Result<ChatTokenCount> {
+    Ok(ChatTokenCount {
+        tokens: tokenizer::count_message_tokens(&messages) as i32,
+        limit: ContextBudget::for_model(&model, max_tokens).prompt_tokens() as i32,
+    })
+}
@@ -0,0 +1,133 @@
+use crate::commands::db::DbState;
+use crate::core::client_types::{ClientPayload, EmbeddingsConfigMeta, VectorDbConfigMeta};
+use crate::core::embeddings::Embeddings;
+use crate::core::result::Error;
+use crate::core::retrieval::{self, collection_index_with_profile};
+use crate::core::usage::{self, UsageScope};
+use crate::core::vector_db::{ScoredVector, Vector, VectorDb};
+
+/// Number of texts embedded per request to the embeddings service.
+const EMBED_BATCH_SIZE: usize = 512;
+
+/// Embed texts with the embeddings client of a collection index.
+///
+/// The secrets of the client stay in the backend, and the usage is recorded for the index
+/// profile, and for the session if given.
+#[tauri::command]
+#[specta::specta]
+pub async fn embed_texts_for_collection_index(
+    db: DbState<'_>,
+    collection_index_id: String,
+    texts: Vec<String>,
+    session_id: Option<i32>,
+) -> crate::Result<Vec<Vec<f32>>> {
+    let db = db.active();
+    let index = retrieval::find_index(db, collection_index_id).await?;
+    let profile = &index.index;
+    let embeddings = Embeddings::from_data(&profile.embeddings_client, &profile.embeddings_config)?;
+    let vectors = async {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(EMBED_BATCH_SIZE) {
+            vectors.extend(embeddings.embed_documents(batch).await?);
+        }
+        crate::Result::Ok(vectors)
+    }
+    .await;
+    let scope = UsageScope {
+        embeddings_client_id: Some(profile.embeddings_client_id),
+        index_profile_id: Some(profile.id),
+        session_id,
+        ..Default::default()
+    };
+    if let Err(err) = usage::record(db, embeddings.take_usage(), &scope).await {
+        log::error!(
+            "error while recording usage of index profile {}: {}",
+            profile.id,
+            err
+        );
+    }
+    vectors
+}
+
+/// Create the vector index of a collection index if it does not exist yet.
+///
+/// The dimension is taken from the vector db config, or else from the embeddings config.
+#[tauri::command]
+#[specta::specta]
+pub async fn prepare_collection_index_vectors(
+    db: DbState<'_>,
+    collection_index_id: String,
+) -> crate::Result<()> {
+    let db = db.active();
+    let index = retrieval::find_index(db, collection_index_id).await?;
+    let profile = &index.index;
+    let meta = match VectorDbConfigMeta::from_stored(
+        &profile.vector_db_config.client_type,
+        &profile.vector_db_config.meta,
+    )? {
+        VectorDbConfigMeta::Pinecone(meta) => meta,
+    };
+    let embeddings_dimension = match EmbeddingsConfigMeta::from_stored(
+        &profile.embeddings_config.client_type,
+        &profile.embeddings_config.meta,
+    )? {
+        EmbeddingsConfigMeta::OpenAi(meta) => meta.dimension,
+    };
+    let dimension = meta.dimension.or(embeddings_dimension).ok_or_else(|| {
+        Error::msg(format!(
+            "Vector db config {} has no dimension to create the index with",
+            profile.vector_db_config.name
+        ))
+    })?;
+    vector_db_of(&index)?
+        .ensure_index(dimension, meta.metric.as_deref())
+        .await
+}
+
+/// Upsert vectors into the namespace of a collection index.
+#[tauri::command]
+#[specta::specta]
+pub async fn upsert_collection_index_vectors(
+    db: DbState<'_>,
+    collection_index_id: String,
+    vectors: Vec<Vector>,
+) -> crate::Result<()> {
+    let db = db.active();
+    let index = retrieval::find_index(db, collection_index_id).await?;
+    vector_db_of(&index)?.upsert(&index.id, vectors).await
+}
+
+/// Delete vectors from the namespace of a collection index, or all of them if no ids are given.
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_collection_index_vectors(
+    db: DbState<'_>,
+    collection_index_id: String,
+    ids: Option<Vec<String>>,
+) -> crate::Result<()> {
+    let db = db.active();
+    let index = retrieval::find_index(db, collection_index_id).await?;
+    vector_db_of(&index)?
+        .delete(&index.id, ids.as_deref())
+        .await
+}
+
+/// Query the vectors most similar to a vector in the namespace of a collection index.
+#[tauri::command]
+#[specta::specta]
+pub async fn query_collection_index_vectors(
+    db: DbState<'_>,
+    collection_index_id: String,
+    vector: Vec<f32>,
+    top_k: i32,
+) -> crate::Result<Vec<ScoredVector>> {
+    let db = db.active();
+    let index = retrieval::find_index(db, collection_index_id).await?;
+    vector_db_of(&index)?
+        .query(&index.id, &vector, top_k.max(0) as usize)
+        .await
+}
+
+fn vector_db_of(index: &collection_index_with_profile::Data) -> crate::Result<VectorDb> {
+    VectorDb::from_data(&index.index.vector_db_client, &index.index.vector_db_config)
+}
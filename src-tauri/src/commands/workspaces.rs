@@ -0,0 +1,36 @@
+use crate::core::watcher::DocumentWatcher;
+use crate::core::workspace::{WorkspaceInfo, Workspaces};
+
+/// List the workspaces, the default one first.
+#[tauri::command]
+#[specta::specta]
+pub async fn list_workspaces(
+    workspaces: tauri::State<'_, Workspaces>,
+) -> crate::Result<Vec<WorkspaceInfo>> {
+    workspaces.list()
+}
+
+/// Create a workspace with its own database and upload folder, without switching to it.
+#[tauri::command]
+#[specta::specta]
+pub async fn create_workspace(
+    workspaces: tauri::State<'_, Workspaces>,
+    name: String,
+) -> crate::Result<WorkspaceInfo> {
+    workspaces.create(name.trim()).await
+}
+
+/// Switch to a workspace, which is remembered across restarts.
+///
+/// The secrets are locked, and have to be unlocked by the passphrase of the workspace. The linked
+/// documents of the workspace are watched instead of the ones of the previous workspace.
+#[tauri::command]
+#[specta::specta]
+pub async fn switch_workspace(
+    workspaces: tauri::State<'_, Workspaces>,
+    watcher: tauri::State<'_, DocumentWatcher>,
+    name: String,
+) -> crate::Result<()> {
+    workspaces.switch(&name).await?;
+    watcher.rewatch(workspaces.active()).await
+}
@@ -0,0 +1,202 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use prisma_client_rust::chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::core::result::Error;
+
+/// Folder of the backups, next to the database file.
+const BACKUP_DIR: &str = "backups";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%3f";
+/// Pages copied at a time, between which other connections can access the database.
+const PAGES_PER_STEP: i32 = 1024;
+const PAUSE_BETWEEN_STEPS: Duration = Duration::from_millis(10);
+
+/// Number of backups of each kind kept by rotation. Manual backups are never rotated.
+pub const KEEP_PRE_MIGRATION: usize = 5;
+pub const KEEP_SCHEDULED: usize = 7;
+pub const KEEP_PRE_RESTORE: usize = 5;
+pub const KEEP_PRE_REPAIR: usize = 5;
+/// How often a scheduled backup is made.
+pub const SCHEDULE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How often the schedule is checked, so that a backup missed while the app was closed is made
+/// soon after the app starts.
+pub const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackupKind {
+    Manual,
+    /// Made on startup before the schema is migrated.
+    PreMigration,
+    Scheduled,
+    /// Made before restoring another backup, so that the restoring can be undone.
+    PreRestore,
+    /// Made before deleting the orphan rows found by a health check.
+    PreRepair,
+}
+
+impl BackupKind {
+    fn slug(&self) -> &'static str {
+        match self {
+            BackupKind::Manual => "manual",
+            BackupKind::PreMigration => "pre-migration",
+            BackupKind::Scheduled => "scheduled",
+            BackupKind::PreRestore => "pre-restore",
+            BackupKind::PreRepair => "pre-repair",
+        }
+    }
+
+    fn of_slug(slug: &str) -> Option<Self> {
+        [
+            BackupKind::Manual,
+            BackupKind::PreMigration,
+            BackupKind::Scheduled,
+            BackupKind::PreRestore,
+            BackupKind::PreRepair,
+        ]
+        .into_iter()
+        .find(|kind| kind.slug() == slug)
+    }
+}
+
+#[derive(Serialize, Type, Clone, Debug)]
+pub struct BackupInfo {
+    pub filename: String,
+    pub kind: BackupKind,
+    #[serde(rename = "createTime")]
+    pub create_time: DateTime<FixedOffset>,
+    /// Size of the backup file in bytes.
+    pub size: f64,
+}
+
+/// The folder of the backups of the database at `db_path`.
+pub fn backup_dir(db_path: &Path) -> PathBuf {
+    db_path
+        .parent()
+        .map(|dir| dir.join(BACKUP_DIR))
+        .unwrap_or_else(|| PathBuf::from(BACKUP_DIR))
+}
+
+fn db_stem(db_path: &Path) -> String {
+    db_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Parse a backup filename in the form of `<db stem>-<timestamp>-<kind>.db`.
+fn parse_filename(stem: &str, filename: &str) -> Option<(DateTime<FixedOffset>, BackupKind)> {
+    let rest = filename
+        .strip_prefix(stem)?
+        .strip_prefix('-')?
+        .strip_suffix(".db")?;
+    let mut parts = rest.splitn(3, '-');
+    let timestamp = format!("{}-{}", parts.next()?, parts.next()?);
+    let kind = BackupKind::of_slug(parts.next()?)?;
+    let naive = NaiveDateTime::parse_from_str(&timestamp, TIMESTAMP_FORMAT).ok()?;
+    let time = Local.from_local_datetime(&naive).earliest()?;
+    Some((time.into(), kind))
+}
+
+/// Copy the database at `db_path` into a new backup by the online backup api of sqlite, which
+/// blocks until done.
+///
+/// Other connections can keep reading and writing the database meanwhile.
+pub fn create(db_path: &Path, kind: BackupKind) -> crate::Result<BackupInfo> {
+    let dir = backup_dir(db_path);
+    std::fs::create_dir_all(&dir)?;
+    let now = Local::now();
+    let filename = format!(
+        "{}-{}-{}.db",
+        db_stem(db_path),
+        now.format(TIMESTAMP_FORMAT),
+        kind.slug()
+    );
+    let backup_path = dir.join(&filename);
+
+    let src = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut dst = Connection::open(&backup_path)?;
+    Backup::new(&src, &mut dst)?.run_to_completion(PAGES_PER_STEP, PAUSE_BETWEEN_STEPS, None)?;
+
+    Ok(BackupInfo {
+        filename,
+        kind,
+        create_time: now.into(),
+        size: std::fs::metadata(&backup_path)?.len() as f64,
+    })
+}
+
+/// List the backups of the database at `db_path`, the newest first.
+pub fn list(db_path: &Path) -> crate::Result<Vec<BackupInfo>> {
+    let dir = backup_dir(db_path);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let stem = db_stem(db_path);
+    let mut backups = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let filename = entry.file_name().to_string_lossy().to_string();
+        if let Some((create_time, kind)) = parse_filename(&stem, &filename) {
+            backups.push(BackupInfo {
+                filename,
+                kind,
+                create_time,
+                size: entry.metadata()?.len() as f64,
+            });
+        }
+    }
+    backups.sort_by(|a, b| b.create_time.cmp(&a.create_time));
+    Ok(backups)
+}
+
+/// Delete the backups of the given kind but the newest `keep` ones.
+pub fn rotate(db_path: &Path, kind: BackupKind, keep: usize) -> crate::Result<()> {
+    let dir = backup_dir(db_path);
+    for backup in list(db_path)?
+        .into_iter()
+        .filter(|backup| backup.kind == kind)
+        .skip(keep)
+    {
+        std::fs::remove_file(dir.join(backup.filename))?;
+    }
+    Ok(())
+}
+
+/// Copy a backup back into the database at `db_path` by the online backup api of sqlite, which
+/// blocks until done.
+///
+/// The database stays usable by other connections, which see the restored content once done.
+pub fn restore(db_path: &Path, filename: &str) -> crate::Result<()> {
+    // only the backups listed, which also rules out the paths out of the backup folder
+    if parse_filename(&db_stem(db_path), filename).is_none() {
+        return Err(Error::msg(format!("Invalid backup filename {}", filename)));
+    }
+    let backup_path = backup_dir(db_path).join(filename);
+    if !backup_path.exists() {
+        return Err(Error::msg(format!("Backup {} not found", filename)));
+    }
+
+    let src = Connection::open_with_flags(&backup_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut dst = Connection::open(db_path)?;
+    Backup::new(&src, &mut dst)?.run_to_completion(PAGES_PER_STEP, PAUSE_BETWEEN_STEPS, None)?;
+    Ok(())
+}
+
+/// Whether a scheduled backup is due, that is no scheduled backup is made within the interval.
+pub fn is_scheduled_backup_due(db_path: &Path) -> crate::Result<bool> {
+    let latest = list(db_path)?
+        .into_iter()
+        .find(|backup| backup.kind == BackupKind::Scheduled);
+    Ok(match latest {
+        Some(backup) => {
+            let elapsed = Local::now().signed_duration_since(backup.create_time);
+            elapsed.to_std().unwrap_or_default() >= SCHEDULE_INTERVAL
+        }
+        None => true,
+    })
+}
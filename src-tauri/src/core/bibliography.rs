@@ -0,0 +1,673 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::core::document_metadata::Metadata;
+use crate::core::result::Error;
+
+/// A LaTeX accent command on a letter, such as `\'{e}` or `\"o`.
+static LATEX_ACCENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"\\([`'^"~=.])\s*\{?\s*\\?([A-Za-z])\}?"#).unwrap());
+static YEAR: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b(\d{4})\b").unwrap());
+/// A tagged line of a RIS file.
+static RIS_TAG_LINE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^([A-Z][A-Z0-9])  -\s?(.*)$").unwrap());
+
+/// Format of a bibliography file, told by its extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BibFormat {
+    BibTex,
+    Ris,
+}
+
+impl BibFormat {
+    pub fn of_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("ris") => BibFormat::Ris,
+            _ => BibFormat::BibTex,
+        }
+    }
+}
+
+/// An entry of a bibliography file.
+#[derive(Clone, Debug)]
+pub struct BibEntry {
+    /// Citation key of the entry, or its position in the file if it has none.
+    pub key: String,
+    pub metadata: Metadata,
+    /// The files attached to the entry, as written in the file.
+    pub files: Vec<String>,
+}
+
+impl BibEntry {
+    /// The local pdf of the entry, with relative paths resolved against the folder of the
+    /// bibliography file.
+    ///
+    /// A file missing where it is said to be is also looked for by its name in that folder, since
+    /// the paths are often relative to another machine or library.
+    pub fn local_pdf(&self, bib_dir: &Path) -> Option<PathBuf> {
+        let candidates = self
+            .files
+            .iter()
+            .flat_map(|file| {
+                let path = PathBuf::from(file);
+                let in_dir = path.file_name().map(|name| bib_dir.join(name));
+                [
+                    Some(if path.is_absolute() {
+                        path.clone()
+                    } else {
+                        bib_dir.join(&path)
+                    }),
+                    in_dir,
+                ]
+            })
+            .flatten()
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        candidates
+            .iter()
+            .find(|path| {
+                path.extension()
+                    .map(|ext| ext.eq_ignore_ascii_case("pdf"))
+                    .unwrap_or(false)
+            })
+            .or_else(|| candidates.first())
+            .cloned()
+    }
+}
+
+pub fn parse(content: &str, format: BibFormat) -> crate::Result<Vec<BibEntry>> {
+    match format {
+        BibFormat::BibTex => parse_bibtex(content),
+        BibFormat::Ris => Ok(parse_ris(content)),
+    }
+}
+
+/// Parse the entries of a BibTeX file, expanding `@string` macros and skipping comments and
+/// preambles.
+pub fn parse_bibtex(content: &str) -> crate::Result<Vec<BibEntry>> {
+    let mut parser = BibTexParser {
+        chars: content.chars().collect(),
+        pos: 0,
+        macros: HashMap::new(),
+    };
+    let mut entries = vec![];
+    while parser.skip_to_entry() {
+        let entry_type = parser.identifier().to_lowercase();
+        parser.skip_whitespace();
+        let close = match parser.next() {
+            Some('{') => '}',
+            Some('(') => ')',
+            _ => return Err(parser.error("expected { or ( after the entry type")),
+        };
+        match entry_type.as_str() {
+            "comment" | "preamble" if close == '}' => {
+                parser.pos -= 1;
+                parser.braced()?;
+            }
+            "comment" | "preamble" => {
+                parser.until(&[close]);
+                parser.expect(close)?;
+            }
+            "string" => {
+                let (name, value) = parser.field()?;
+                parser.macros.insert(name, value);
+                parser.expect(close)?;
+            }
+            _ => {
+                let key = parser.until(&[',', close]).trim().to_string();
+                let mut fields = HashMap::new();
+                loop {
+                    parser.skip_whitespace();
+                    match parser.peek() {
+                        Some(c) if c == close => {
+                            parser.pos += 1;
+                            break;
+                        }
+                        Some(',') => parser.pos += 1,
+                        Some(_) => {
+                            let (name, value) = parser.field()?;
+                            fields.insert(name, value);
+                        }
+                        None => return Err(parser.error("unterminated entry")),
+                    }
+                }
+                let key = if key.is_empty() {
+                    format!("#{}", entries.len() + 1)
+                } else {
+                    key
+                };
+                entries.push(bibtex_entry(key, &fields));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+struct BibTexParser {
+    chars: Vec<char>,
+    pos: usize,
+    /// Values of the `@string` macros, by their lowercase names.
+    macros: HashMap<String, String>,
+}
+
+impl BibTexParser {
+    fn error(&self, message: &str) -> Error {
+        let line = self.chars[..self.pos.min(self.chars.len())]
+            .iter()
+            .filter(|&&c| c == '\n')
+            .count()
+            + 1;
+        Error::msg(format!("Invalid BibTeX at line {}: {}", line, message))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn expect(&mut self, expected: char) -> crate::Result<()> {
+        self.skip_whitespace();
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected {}", expected))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Move past the next `@`, since anything outside of the entries is a comment.
+    fn skip_to_entry(&mut self) -> bool {
+        while let Some(c) = self.next() {
+            if c == '@' {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn identifier(&mut self) -> String {
+        self.skip_whitespace();
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_alphanumeric() || "_-:.+/'".contains(c)) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn until(&mut self, stops: &[char]) -> String {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if !stops.contains(&c)) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// A value in braces, with the braces inside kept.
+    fn braced(&mut self) -> crate::Result<String> {
+        self.expect('{')?;
+        let start = self.pos;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next() {
+                Some('{') => depth += 1,
+                Some('}') => depth -= 1,
+                Some(_) => {}
+                None => return Err(self.error("unbalanced braces")),
+            }
+        }
+        Ok(self.chars[start..self.pos - 1].iter().collect())
+    }
+
+    /// A value in quotes, which may hold quotes in braces.
+    fn quoted(&mut self) -> crate::Result<String> {
+        self.expect('"')?;
+        let start = self.pos;
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Some('{') => depth += 1,
+                Some('}') => depth -= 1,
+                Some('"') if depth == 0 => break,
+                Some(_) => {}
+                None => return Err(self.error("unterminated quotes")),
+            }
+        }
+        Ok(self.chars[start..self.pos - 1].iter().collect())
+    }
+
+    /// A `name = value` field, whose value is made of parts joined by `#`.
+    fn field(&mut self) -> crate::Result<(String, String)> {
+        let name = self.identifier().to_lowercase();
+        if name.is_empty() {
+            return Err(self.error("expected a field name"));
+        }
+        self.expect('=')?;
+        let mut value = String::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('{') => value.push_str(&self.braced()?),
+                Some('"') => value.push_str(&self.quoted()?),
+                Some(_) => {
+                    let part = self.identifier();
+                    if part.is_empty() {
+                        return Err(self.error("expected a field value"));
+                    }
+                    let expanded = self.macros.get(&part.to_lowercase()).cloned();
+                    value.push_str(&expanded.unwrap_or(part));
+                }
+                None => return Err(self.error("expected a field value")),
+            }
+            self.skip_whitespace();
+            if self.peek() == Some('#') {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        Ok((name, value))
+    }
+}
+
+/// Split a list of names joined by `and`, leaving the ones in braces alone.
+fn split_names(value: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    let bytes = value.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            b' ' | b'\t' | b'\n' if depth == 0 => {
+                let rest = &value[i..];
+                let trimmed = rest.trim_start();
+                if trimmed.starts_with("and") && trimmed[3..].starts_with(char::is_whitespace) {
+                    names.push(value[start..i].to_string());
+                    i += rest.len() - trimmed.len() + 3;
+                    start = i;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    names.push(value[start..].to_string());
+    names
+}
+
+/// Turn a LaTeX value into plain text, decoding the common accents and escapes and dropping the
+/// braces.
+fn latex_to_text(value: &str) -> String {
+    let text = LATEX_ACCENT.replace_all(value, |captures: &regex::Captures| {
+        let mark = match &captures[1] {
+            "`" => '\u{300}',
+            "'" => '\u{301}',
+            "^" => '\u{302}',
+            "~" => '\u{303}',
+            "=" => '\u{304}',
+            "." => '\u{307}',
+            _ => '\u{308}',
+        };
+        format!("{}{}", &captures[2], mark)
+    });
+    let mut text = text
+        .replace("\\ss", "ß")
+        .replace("\\&", "&")
+        .replace("\\%", "%")
+        .replace("\\$", "$")
+        .replace("\\#", "#")
+        .replace("\\_", "_")
+        .replace("---", "—")
+        .replace("--", "–")
+        .replace('~', " ");
+    text.retain(|c| c != '{' && c != '}');
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Escape plain text to be written as a BibTeX value in braces.
+fn text_to_latex(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            // unbalanced braces would end the value
+            '{' | '}' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The paths of a `file` field, as written by JabRef, Zotero and Mendeley:
+/// `description:path:type` items separated by `;`, with the colons and backslashes of the paths
+/// escaped.
+fn file_paths(value: &str) -> Vec<String> {
+    let value = value.replace("$\\backslash$", "\\");
+    let mut paths = vec![];
+    for item in split_unescaped(&value, ';') {
+        let parts = split_unescaped(&item, ':');
+        let path = match parts.len() {
+            0 => continue,
+            1 | 2 => parts.last().unwrap().clone(),
+            // a colon of a drive letter which is not escaped
+            n => parts[1..n - 1].join(":"),
+        };
+        let path = path.trim().to_string();
+        if !path.is_empty() {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+fn split_unescaped(value: &str, separator: char) -> Vec<String> {
+    let mut parts = vec![];
+    let mut part = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && matches!(chars.peek(), Some(&next) if next == separator || next == '\\') {
+            part.push(chars.next().unwrap());
+        } else if c == separator {
+            parts.push(std::mem::take(&mut part));
+        } else {
+            part.push(c);
+        }
+    }
+    parts.push(part);
+    parts
+}
+
+fn split_keywords(value: &str) -> Vec<String> {
+    value
+        .split(|c| c == ',' || c == ';')
+        .map(|keyword| keyword.trim().to_string())
+        .filter(|keyword| !keyword.is_empty())
+        .collect()
+}
+
+fn year_of(value: &str) -> Option<i32> {
+    YEAR.captures(value)
+        .and_then(|captures| captures[1].parse().ok())
+}
+
+fn strip_doi_prefix(doi: &str) -> String {
+    let doi = doi.trim();
+    [
+        "https://doi.org/",
+        "http://doi.org/",
+        "https://dx.doi.org/",
+        "doi:",
+    ]
+    .iter()
+    .find_map(|prefix| doi.strip_prefix(prefix))
+    .unwrap_or(doi)
+    .to_string()
+}
+
+fn bibtex_entry(key: String, fields: &HashMap<String, String>) -> BibEntry {
+    let text = |name: &str| fields.get(name).map(|value| latex_to_text(value));
+    let is_arxiv = ["archiveprefix", "eprinttype"].iter().any(|name| {
+        text(name)
+            .map(|value| value.eq_ignore_ascii_case("arxiv"))
+            .unwrap_or(false)
+    });
+    let metadata = Metadata {
+        title: text("title"),
+        authors: fields
+            .get("author")
+            .map(|authors| {
+                split_names(authors)
+                    .iter()
+                    .map(|a| latex_to_text(a))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        year: text("year")
+            .or_else(|| text("date"))
+            .and_then(|year| year_of(&year)),
+        venue: text("journal")
+            .or_else(|| text("journaltitle"))
+            .or_else(|| text("booktitle")),
+        doi: fields.get("doi").map(|doi| strip_doi_prefix(doi)),
+        arxiv_id: fields
+            .get("eprint")
+            .filter(|_| is_arxiv)
+            .or_else(|| fields.get("arxivid"))
+            .map(|id| id.trim().to_string()),
+        abstract_text: text("abstract"),
+        keywords: text("keywords")
+            .map(|keywords| split_keywords(&keywords))
+            .unwrap_or_default(),
+    };
+    BibEntry {
+        key,
+        metadata: metadata.normalized(),
+        files: fields
+            .get("file")
+            .map(|file| file_paths(file))
+            .unwrap_or_default(),
+    }
+}
+
+/// Parse the entries of a RIS file, which are made of `TY  - ` to `ER  - ` lines.
+pub fn parse_ris(content: &str) -> Vec<BibEntry> {
+    let mut entries = vec![];
+    let mut fields: Vec<(String, String)> = vec![];
+    for line in content.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim_end();
+        match RIS_TAG_LINE.captures(line) {
+            Some(captures) => {
+                let tag = captures[1].to_string();
+                if tag == "ER" {
+                    let key = format!("#{}", entries.len() + 1);
+                    entries.push(ris_entry(key, &std::mem::take(&mut fields)));
+                } else {
+                    fields.push((tag, captures[2].trim().to_string()));
+                }
+            }
+            // a value continued on the next line
+            None if !line.trim().is_empty() => {
+                if let Some((_, value)) = fields.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            }
+            None => {}
+        }
+    }
+    entries
+}
+
+fn ris_entry(key: String, fields: &[(String, String)]) -> BibEntry {
+    let values = |tags: &[&str]| {
+        fields
+            .iter()
+            .filter(|(tag, _)| tags.contains(&tag.as_str()))
+            .map(|(_, value)| value.clone())
+            .collect::<Vec<_>>()
+    };
+    let first = |tags: &[&str]| {
+        // the tags are in the order of preference
+        tags.iter()
+            .find_map(|tag| values(&[*tag]).into_iter().next())
+    };
+    let metadata = Metadata {
+        title: first(&["TI", "T1", "CT"]),
+        authors: values(&["AU", "A1"]),
+        year: first(&["PY", "Y1", "DA"]).and_then(|year| year_of(&year)),
+        venue: first(&["JF", "JO", "T2", "BT", "JA"]),
+        doi: first(&["DO"]).map(|doi| strip_doi_prefix(&doi)),
+        arxiv_id: None,
+        abstract_text: first(&["AB", "N2"]),
+        keywords: values(&["KW"])
+            .iter()
+            .flat_map(|keywords| split_keywords(keywords))
+            .collect(),
+    };
+    BibEntry {
+        key: first(&["ID"]).unwrap_or(key),
+        metadata: metadata.normalized(),
+        files: values(&["L1", "L4"])
+            .into_iter()
+            .map(|file| {
+                file.strip_prefix("file://")
+                    .map(str::to_string)
+                    .unwrap_or(file)
+            })
+            .collect(),
+    }
+}
+
+/// A document to write as a BibTeX entry.
+pub struct BibDocument {
+    pub filename: String,
+    pub filepath: String,
+    pub metadata: Option<Metadata>,
+}
+
+/// Write documents as BibTeX entries, keyed by the first author, the year and the first word of
+/// the title, such as `vaswani2017attention`.
+///
+/// The documents with no metadata are titled after their files. The stored files are attached
+/// in the `file` field, so that the bibliography can be imported again.
+pub fn write_bibtex(documents: &[BibDocument]) -> String {
+    let mut keys = HashSet::new();
+    let mut out = String::new();
+    for doc in documents {
+        let metadata = doc.metadata.clone().unwrap_or_default();
+        let title = metadata.title.clone().unwrap_or_else(|| {
+            Path::new(&doc.filename)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| doc.filename.clone())
+        });
+        let key = unique_key(&mut keys, citation_key(&metadata, &title));
+        let entry_type = if metadata.venue.is_some() {
+            "article"
+        } else {
+            "misc"
+        };
+
+        let mut fields = vec![("title", text_to_latex(&title))];
+        if !metadata.authors.is_empty() {
+            let authors = metadata
+                .authors
+                .iter()
+                .map(|author| match text_to_latex(author) {
+                    // a name of an organization, which would be split
+                    author if author.contains(" and ") => format!("{{{}}}", author),
+                    author => author,
+                })
+                .collect::<Vec<_>>();
+            fields.push(("author", authors.join(" and ")));
+        }
+        if let Some(year) = metadata.year {
+            fields.push(("year", year.to_string()));
+        }
+        if let Some(venue) = &metadata.venue {
+            fields.push(("journal", text_to_latex(venue)));
+        }
+        if let Some(doi) = &metadata.doi {
+            fields.push(("doi", doi.clone()));
+        }
+        if let Some(arxiv_id) = &metadata.arxiv_id {
+            fields.push(("eprint", arxiv_id.clone()));
+            fields.push(("archiveprefix", "arXiv".to_string()));
+        }
+        if let Some(abstract_text) = &metadata.abstract_text {
+            fields.push(("abstract", text_to_latex(abstract_text)));
+        }
+        if !metadata.keywords.is_empty() {
+            fields.push(("keywords", text_to_latex(&metadata.keywords.join(", "))));
+        }
+        let escape_file = |text: &str| {
+            text.replace('\\', "\\\\")
+                .replace(':', "\\:")
+                .replace(';', "\\;")
+        };
+        fields.push((
+            "file",
+            format!(
+                "{}:{}:PDF",
+                escape_file(&doc.filename),
+                escape_file(&doc.filepath)
+            ),
+        ));
+
+        out += &format!("@{}{{{},\n", entry_type, key);
+        for (name, value) in fields {
+            out += &format!("  {} = {{{}}},\n", name, value);
+        }
+        out += "}\n\n";
+    }
+    out
+}
+
+fn citation_key(metadata: &Metadata, title: &str) -> String {
+    let word = |text: &str| {
+        text.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let author = metadata
+        .authors
+        .first()
+        .map(|author| match author.split_once(',') {
+            Some((last, _)) => word(last),
+            None => word(author.split_whitespace().last().unwrap_or("")),
+        })
+        .unwrap_or_default();
+    let title_word = title
+        .split_whitespace()
+        .map(word)
+        .find(|word| {
+            word.len() > 3 && !["with", "from", "into", "towards", "about"].contains(&word.as_str())
+        })
+        .unwrap_or_default();
+    let key = format!(
+        "{}{}{}",
+        author,
+        metadata
+            .year
+            .map(|year| year.to_string())
+            .unwrap_or_default(),
+        title_word
+    );
+    if key.is_empty() {
+        "document".to_string()
+    } else {
+        key
+    }
+}
+
+/// Make a key unique among the keys taken, by suffixing it with `a`, `b` and so on.
+fn unique_key(keys: &mut HashSet<String>, key: String) -> String {
+    let mut unique = key.clone();
+    let mut suffixes = ('a'..='z')
+        .map(|c| c.to_string())
+        .chain((1..).map(|n| n.to_string()));
+    while keys.contains(&unique) {
+        unique = format!("{}{}", key, suffixes.next().unwrap());
+    }
+    keys.insert(unique.clone());
+    unique
+}
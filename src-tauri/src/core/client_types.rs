@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::core::result::Error;
+use crate::core::usage::Price;
+use crate::core::validation::ValidationError;
+
+/// Distance metrics of pinecone indexes.
+const PINECONE_METRICS: [&str; 3] = ["cosine", "euclidean", "dotproduct"];
+
+/// A payload tagged by a supported client type.
+///
+/// A client is stored as its `type` and a JSON text of its `info`, and a config as its
+/// `clientType` and a JSON text of its `meta`. The payloads are tagged the same way, so that they
+/// are (de)serialized in the shape the frontend sends them in, while unsupported types and
+/// missing fields are caught when they are created rather than deep inside indexing.
+pub trait ClientPayload: Serialize + DeserializeOwned {
+    /// Name of the field holding the client type.
+    const TAG: &'static str;
+    /// Name of the field holding the rest of the payload.
+    const CONTENT: &'static str;
+
+    /// Collect the problems of the fields, with paths prefixed by `CONTENT`.
+    fn validate(&self, errors: &mut ValidationError);
+
+    /// Parse a stored payload.
+    fn from_stored(client_type: &str, content: &str) -> crate::Result<Self> {
+        let mut tagged = serde_json::Map::new();
+        tagged.insert(Self::TAG.to_string(), client_type.into());
+        tagged.insert(Self::CONTENT.to_string(), serde_json::from_str(content)?);
+        serde_json::from_value(tagged.into()).map_err(|err| {
+            Error::msg(format!(
+                "Invalid {} of {} client: {}",
+                Self::CONTENT,
+                client_type,
+                err
+            ))
+        })
+    }
+
+    /// Split the payload into the client type and the rest for storing.
+    fn to_stored(&self) -> crate::Result<(String, serde_json::Value)> {
+        let mut tagged = serde_json::to_value(self)?;
+        let client_type = tagged[Self::TAG].as_str().unwrap_or_default().to_string();
+        let content = tagged[Self::CONTENT].take();
+        Ok((client_type, content))
+    }
+}
+
+/// Info of a client of an OpenAI-compatible api, which may be served locally at `baseUrl`.
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct OpenAiClientInfo {
+    #[serde(rename = "apiKey", default)]
+    pub api_key: String,
+    #[serde(rename = "baseUrl", default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+}
+
+impl OpenAiClientInfo {
+    fn validate(&self, errors: &mut ValidationError) {
+        errors.require("info.apiKey", &self.api_key);
+        errors.check_url("info.baseUrl", self.base_url.as_deref());
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct PineconeClientInfo {
+    #[serde(rename = "apiKey", default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub environment: String,
+    #[serde(rename = "indexName", default)]
+    pub index_name: String,
+}
+
+/// Info of a client of the Cohere/Jina rerank api.
+///
+/// The api key may be left out for a self-hosted model at `url`.
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct RerankApiInfo {
+    #[serde(rename = "apiKey", default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug, Default)]
+pub struct OpenAiEmbeddingsMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<i32>,
+    /// Prices per model, if not the ones known by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prices: Option<HashMap<String, Price>>,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug, Default)]
+pub struct PineconeConfigMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<i32>,
+    /// One of `cosine`, `euclidean` or `dotproduct`.
+    #[serde(alias = "metrics", default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+#[serde(tag = "type", content = "info")]
+pub enum EmbeddingsClientInfo {
+    #[serde(rename = "openai")]
+    OpenAi(OpenAiClientInfo),
+}
+
+impl ClientPayload for EmbeddingsClientInfo {
+    const TAG: &'static str = "type";
+    const CONTENT: &'static str = "info";
+
+    fn validate(&self, errors: &mut ValidationError) {
+        match self {
+            EmbeddingsClientInfo::OpenAi(info) => info.validate(errors),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+#[serde(tag = "type", content = "info")]
+pub enum VectorDbClientInfo {
+    #[serde(rename = "pinecone")]
+    Pinecone(PineconeClientInfo),
+}
+
+impl ClientPayload for VectorDbClientInfo {
+    const TAG: &'static str = "type";
+    const CONTENT: &'static str = "info";
+
+    fn validate(&self, errors: &mut ValidationError) {
+        match self {
+            VectorDbClientInfo::Pinecone(info) => {
+                errors.require("info.apiKey", &info.api_key);
+                errors.require("info.environment", &info.environment);
+                errors.require("info.indexName", &info.index_name);
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+#[serde(tag = "type", content = "info")]
+pub enum RerankerClientInfo {
+    #[serde(rename = "cohere")]
+    Cohere(RerankApiInfo),
+    #[serde(rename = "jina")]
+    Jina(RerankApiInfo),
+}
+
+impl RerankerClientInfo {
+    pub fn info(&self) -> &RerankApiInfo {
+        match self {
+            RerankerClientInfo::Cohere(info) | RerankerClientInfo::Jina(info) => info,
+        }
+    }
+}
+
+impl ClientPayload for RerankerClientInfo {
+    const TAG: &'static str = "type";
+    const CONTENT: &'static str = "info";
+
+    fn validate(&self, errors: &mut ValidationError) {
+        let info = self.info();
+        match &info.url {
+            // the hosted services cannot be called without a key
+            None => errors.require("info.apiKey", info.api_key.as_deref().unwrap_or_default()),
+            Some(url) => errors.check_url("info.url", Some(url)),
+        }
+        errors.require_some("info.model", info.model.as_deref());
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+#[serde(tag = "type", content = "info")]
+pub enum CompletionClientInfo {
+    #[serde(rename = "openai")]
+    OpenAi(OpenAiClientInfo),
+}
+
+impl ClientPayload for CompletionClientInfo {
+    const TAG: &'static str = "type";
+    const CONTENT: &'static str = "info";
+
+    fn validate(&self, errors: &mut ValidationError) {
+        match self {
+            CompletionClientInfo::OpenAi(info) => info.validate(errors),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+#[serde(tag = "clientType", content = "meta")]
+pub enum EmbeddingsConfigMeta {
+    #[serde(rename = "openai")]
+    OpenAi(OpenAiEmbeddingsMeta),
+}
+
+impl ClientPayload for EmbeddingsConfigMeta {
+    const TAG: &'static str = "clientType";
+    const CONTENT: &'static str = "meta";
+
+    fn validate(&self, errors: &mut ValidationError) {
+        match self {
+            EmbeddingsConfigMeta::OpenAi(meta) => {
+                errors.require_some("meta.model", meta.model.as_deref());
+                errors.check_positive("meta.dimension", meta.dimension);
+                for (model, price) in meta.prices.iter().flatten() {
+                    if price.prompt_per_1k < 0.0 || price.completion_per_1k < 0.0 {
+                        errors.add(format!("meta.prices.{}", model), "must not be negative");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+#[serde(tag = "clientType", content = "meta")]
+pub enum VectorDbConfigMeta {
+    #[serde(rename = "pinecone")]
+    Pinecone(PineconeConfigMeta),
+}
+
+impl ClientPayload for VectorDbConfigMeta {
+    const TAG: &'static str = "clientType";
+    const CONTENT: &'static str = "meta";
+
+    fn validate(&self, errors: &mut ValidationError) {
+        match self {
+            VectorDbConfigMeta::Pinecone(meta) => {
+                errors.check_positive("meta.dimension", meta.dimension);
+                if let Some(metric) = &meta.metric {
+                    if !PINECONE_METRICS.contains(&metric.as_str()) {
+                        errors.add(
+                            "meta.metric",
+                            format!("must be one of {}", PINECONE_METRICS.join(", ")),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Validate the name and the payload of a client or config to be stored.
+pub fn validate<P: ClientPayload>(name: &str, payload: &P) -> crate::Result<()> {
+    let mut errors = ValidationError::default();
+    errors.require("name", name);
+    payload.validate(&mut errors);
+    Ok(errors.into_result()?)
+}
@@ -0,0 +1,771 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use prisma_client_rust::chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::core::config_bundle::{
+    self, ConfigBundle, ConfigImportReport, ConflictStrategy, ImportCounts,
+};
+use crate::core::fs::{hash_file_blocking, HashAlgo};
+use crate::core::result::Error;
+use crate::core::retrieval::ChunkRef;
+use crate::prisma::{
+    collection, collection_index, collections_on_documents, document, document_chunk,
+    embedding_vectors_on_document_chunks, embeddings_config, index_profile, session,
+    session_message, splitting, PrismaClient,
+};
+
+pub const ARCHIVE_VERSION: i32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const CHUNKS_ENTRY: &str = "chunks.jsonl";
+const EMBEDDINGS_ENTRY: &str = "embeddings.jsonl";
+const SESSIONS_ENTRY: &str = "sessions.json";
+
+fn blob_entry(md5_hash: &str) -> String {
+    format!("blobs/{}", md5_hash)
+}
+
+/// The hash algorithm of the rows archived before the hash algorithm is archived.
+fn md5_hash_algo() -> String {
+    HashAlgo::Md5.as_str().to_string()
+}
+
+/// What a collection archive holds besides the rows of chunks, embeddings and sessions.
+///
+/// The ids are the ones in the exporting database, only used for the references between rows.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: i32,
+    #[serde(rename = "exportTime")]
+    pub export_time: String,
+    /// Name of the collection, which the archive is merged into on importing.
+    pub name: String,
+    pub documents: Vec<ArchivedDocument>,
+    /// The index profiles of the collection indexes, with what they refer to and no secrets.
+    pub config: ConfigBundle,
+    pub indexes: Vec<ArchivedIndex>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedDocument {
+    pub id: i32,
+    pub filename: String,
+    #[serde(rename = "md5Hash")]
+    pub md5_hash: String,
+    #[serde(rename = "hashAlgo", default = "md5_hash_algo")]
+    pub hash_algo: String,
+    #[serde(rename = "updateTime")]
+    pub update_time: DateTime<FixedOffset>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedIndex {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "indexProfileId")]
+    pub index_profile_id: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedChunk {
+    #[serde(rename = "documentId")]
+    pub document_id: i32,
+    #[serde(rename = "splittingId")]
+    pub splitting_id: i32,
+    pub no: i32,
+    pub content: String,
+    pub meta: String,
+    #[serde(rename = "md5Hash")]
+    pub md5_hash: String,
+    #[serde(rename = "hashAlgo", default = "md5_hash_algo")]
+    pub hash_algo: String,
+    #[serde(rename = "tokenCount")]
+    pub token_count: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedVector {
+    #[serde(rename = "embeddingsConfigId")]
+    pub embeddings_config_id: i32,
+    #[serde(rename = "md5Hash")]
+    pub md5_hash: String,
+    #[serde(rename = "hashAlgo", default = "md5_hash_algo")]
+    pub hash_algo: String,
+    /// Base64 of the vector bytes as stored.
+    pub vector: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedSession {
+    pub id: i32,
+    #[serde(rename = "indexId")]
+    pub index_id: String,
+    pub name: String,
+    pub history: String,
+    pub summary: String,
+    #[serde(rename = "summarizedMessageId")]
+    pub summarized_message_id: Option<i32>,
+    #[serde(rename = "activeMessageId")]
+    pub active_message_id: Option<i32>,
+    pub messages: Vec<ArchivedMessage>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedMessage {
+    pub id: i32,
+    #[serde(rename = "parentId")]
+    pub parent_id: Option<i32>,
+    pub role: String,
+    pub content: String,
+    #[serde(rename = "tokenCount")]
+    pub token_count: Option<i32>,
+    #[serde(rename = "createTime")]
+    pub create_time: DateTime<FixedOffset>,
+    #[serde(rename = "promptTokens")]
+    pub prompt_tokens: Option<i32>,
+    #[serde(rename = "completionTokens")]
+    pub completion_tokens: Option<i32>,
+    #[serde(rename = "citedChunks")]
+    pub cited_chunks: Vec<ChunkRef>,
+    pub model: Option<String>,
+}
+
+/// A collection with everything needed to use it in another database: the documents and their
+/// files, the chunks, the cached embeddings, the index profiles and the sessions.
+pub struct CollectionArchive {
+    pub manifest: Manifest,
+    pub chunks: Vec<ArchivedChunk>,
+    pub vectors: Vec<ArchivedVector>,
+    pub sessions: Vec<ArchivedSession>,
+    /// Files of the documents by their hashes, which are the stored files when exporting and
+    /// the extracted files when importing.
+    pub blobs: HashMap<String, PathBuf>,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct CollectionImportReport {
+    #[serde(rename = "collectionId")]
+    collection_id: i32,
+    config: ConfigImportReport,
+    documents: ImportCounts,
+    /// Number of chunks created, where the documents are not split by the same splitting yet.
+    chunks: i32,
+    embeddings: ImportCounts,
+    #[serde(rename = "collectionIndexes")]
+    collection_indexes: ImportCounts,
+    sessions: i32,
+}
+
+impl CollectionArchive {
+    /// Write the archive as a zip file at `path`, which blocks on reading the blobs.
+    pub fn write(&self, path: &Path) -> crate::Result<()> {
+        let mut zip = ZipWriter::new(File::create(path)?);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        // the documents are mostly compressed already, such as pdfs
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+        zip.start_file(MANIFEST_ENTRY, deflated)?;
+        serde_json::to_writer_pretty(&mut zip, &self.manifest)?;
+        zip.start_file(CHUNKS_ENTRY, deflated)?;
+        write_lines(&mut zip, &self.chunks)?;
+        zip.start_file(EMBEDDINGS_ENTRY, deflated)?;
+        write_lines(&mut zip, &self.vectors)?;
+        zip.start_file(SESSIONS_ENTRY, deflated)?;
+        serde_json::to_writer(&mut zip, &self.sessions)?;
+
+        for (md5_hash, path) in &self.blobs {
+            let mut file = File::open(path).map_err(|err| {
+                Error::msg(format!(
+                    "Failed to read the file of document {}: {}",
+                    path.display(),
+                    err
+                ))
+            })?;
+            zip.start_file(blob_entry(md5_hash), stored)?;
+            std::io::copy(&mut file, &mut zip)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// Read the zip file at `path`, extracting the blobs into `upload_dir`, which blocks.
+    ///
+    /// A blob is not extracted again if a file of the same hash is already uploaded. The hashes of
+    /// the documents are checked, and the blobs extracted are verified against them.
+    pub fn read(path: &Path, upload_dir: &Path) -> crate::Result<Self> {
+        let mut zip = ZipArchive::new(File::open(path)?)?;
+        let manifest: Manifest = serde_json::from_reader(zip.by_name(MANIFEST_ENTRY)?)?;
+        if manifest.version > ARCHIVE_VERSION {
+            return Err(Error::msg(format!(
+                "Collection archive version {} is newer than the supported version {}",
+                manifest.version, ARCHIVE_VERSION
+            )));
+        }
+        let chunks = read_lines(BufReader::new(zip.by_name(CHUNKS_ENTRY)?))?;
+        let vectors = read_lines(BufReader::new(zip.by_name(EMBEDDINGS_ENTRY)?))?;
+        let sessions = serde_json::from_reader(zip.by_name(SESSIONS_ENTRY)?)?;
+
+        let mut blobs = HashMap::new();
+        for doc in &manifest.documents {
+            // the hash names the file in the upload folder, so that it must not be a path
+            let hash_algo = HashAlgo::of_str(&doc.hash_algo)?;
+            if !hash_algo.is_hash(&doc.md5_hash) {
+                return Err(Error::msg(format!(
+                    "Invalid {} hash of document {} in the archive",
+                    doc.hash_algo, doc.filename
+                )));
+            }
+            let target_path = upload_dir.join(&doc.md5_hash);
+            if !target_path.exists() {
+                // extract to a partial file first, so that no broken or forged file is taken as
+                // uploaded
+                let partial_path = target_path.with_extension("part");
+                std::io::copy(
+                    &mut zip.by_name(&blob_entry(&doc.md5_hash))?,
+                    &mut File::create(&partial_path)?,
+                )?;
+                if hash_file_blocking(hash_algo, &partial_path)? != doc.md5_hash {
+                    std::fs::remove_file(&partial_path)?;
+                    return Err(Error::msg(format!(
+                        "The file of document {} does not match its hash in the archive",
+                        doc.filename
+                    )));
+                }
+                std::fs::rename(&partial_path, &target_path)?;
+            }
+            blobs.insert(doc.md5_hash.clone(), target_path);
+        }
+
+        Ok(Self {
+            manifest,
+            chunks,
+            vectors,
+            sessions,
+            blobs,
+        })
+    }
+}
+
+fn write_lines<W: Write, T: Serialize>(writer: &mut W, rows: &[T]) -> crate::Result<()> {
+    for row in rows {
+        serde_json::to_writer(&mut *writer, row)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn read_lines<R: BufRead, T: for<'de> Deserialize<'de>>(reader: R) -> crate::Result<Vec<T>> {
+    let mut rows = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            rows.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(rows)
+}
+
+/// Collect a collection into an archive.
+///
+/// Only the chunks and the embeddings of the index profiles used by the collection indexes are
+/// collected. The secrets of the clients are stripped.
+pub async fn export(db: &PrismaClient, collection_id: i32) -> crate::Result<CollectionArchive> {
+    let collection = db
+        .collection()
+        .find_unique(collection::id::equals(collection_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Collection {} not found", collection_id)))?;
+    let document_ids = db
+        .collections_on_documents()
+        .find_many(vec![collections_on_documents::collection_id::equals(
+            collection_id,
+        )])
+        .exec()
+        .await?
+        .into_iter()
+        .map(|rel| rel.document_id)
+        .collect::<Vec<_>>();
+    let documents = db
+        .document()
+        .find_many(vec![document::id::in_vec(document_ids.clone())])
+        .exec()
+        .await?;
+    let indexes = db
+        .collection_index()
+        .find_many(vec![collection_index::collection_id::equals(collection_id)])
+        .exec()
+        .await?;
+    let config = config_bundle::export(
+        db,
+        true,
+        Some(indexes.iter().map(|index| index.index_id).collect()),
+    )
+    .await?;
+
+    let chunks = db
+        .document_chunk()
+        .find_many(vec![
+            document_chunk::document_id::in_vec(document_ids),
+            document_chunk::splitting_id::in_vec(
+                config
+                    .splittings
+                    .iter()
+                    .map(|splitting| splitting.id)
+                    .collect(),
+            ),
+        ])
+        .exec()
+        .await?;
+    let chunk_hashes = chunks
+        .iter()
+        .map(|chunk| chunk.md_5_hash.clone())
+        .collect::<HashSet<_>>();
+    let vectors = db
+        .embedding_vectors_on_document_chunks()
+        .find_many(vec![
+            embedding_vectors_on_document_chunks::embeddings_config_id::in_vec(
+                config
+                    .embeddings_configs
+                    .iter()
+                    .map(|config| config.id)
+                    .collect(),
+            ),
+            embedding_vectors_on_document_chunks::md_5_hash::in_vec(
+                chunk_hashes.into_iter().collect(),
+            ),
+        ])
+        .exec()
+        .await?
+        .into_iter()
+        .map(|vector| ArchivedVector {
+            embeddings_config_id: vector.embeddings_config_id,
+            md5_hash: vector.md_5_hash,
+            hash_algo: vector.hash_algo,
+            vector: BASE64.encode(vector.vector),
+        })
+        .collect();
+
+    let sessions = db
+        .session()
+        .find_many(vec![session::index_id::in_vec(
+            indexes.iter().map(|index| index.id.clone()).collect(),
+        )])
+        .exec()
+        .await?;
+    let mut messages = BTreeMap::<i32, Vec<ArchivedMessage>>::new();
+    for message in db
+        .session_message()
+        .find_many(vec![session_message::session_id::in_vec(
+            sessions.iter().map(|session| session.id).collect(),
+        )])
+        .order_by(session_message::id::order(
+            prisma_client_rust::Direction::Asc,
+        ))
+        .exec()
+        .await?
+    {
+        messages
+            .entry(message.session_id)
+            .or_default()
+            .push(ArchivedMessage {
+                id: message.id,
+                parent_id: message.parent_id,
+                role: message.role,
+                content: message.content,
+                token_count: message.token_count,
+                create_time: message.create_time,
+                prompt_tokens: message.prompt_tokens,
+                completion_tokens: message.completion_tokens,
+                cited_chunks: serde_json::from_str(&message.cited_chunks)?,
+                model: message.model,
+            });
+    }
+    let sessions = sessions
+        .into_iter()
+        .map(|session| ArchivedSession {
+            messages: messages.remove(&session.id).unwrap_or_default(),
+            id: session.id,
+            index_id: session.index_id,
+            name: session.name,
+            history: session.history,
+            summary: session.summary,
+            summarized_message_id: session.summarized_message_id,
+            active_message_id: session.active_message_id,
+        })
+        .collect();
+
+    Ok(CollectionArchive {
+        manifest: Manifest {
+            version: ARCHIVE_VERSION,
+            export_time: Local::now().to_rfc3339(),
+            name: collection.name,
+            documents: documents
+                .iter()
+                .map(|doc| ArchivedDocument {
+                    id: doc.id,
+                    filename: doc.filename.clone(),
+                    md5_hash: doc.md_5_hash.clone(),
+                    hash_algo: doc.hash_algo.clone(),
+                    update_time: doc.update_time,
+                })
+                .collect(),
+            config,
+            indexes: indexes
+                .into_iter()
+                .map(|index| ArchivedIndex {
+                    id: index.id,
+                    name: index.name,
+                    index_profile_id: index.index_id,
+                })
+                .collect(),
+        },
+        chunks: chunks
+            .into_iter()
+            .map(|chunk| ArchivedChunk {
+                document_id: chunk.document_id,
+                splitting_id: chunk.splitting_id,
+                no: chunk.no,
+                content: chunk.content,
+                meta: chunk.meta,
+                md5_hash: chunk.md_5_hash,
+                hash_algo: chunk.hash_algo,
+                token_count: chunk.token_count,
+            })
+            .collect(),
+        vectors,
+        sessions,
+        blobs: documents
+            .into_iter()
+            .map(|doc| (doc.md_5_hash, PathBuf::from(doc.filepath)))
+            .collect(),
+    })
+}
+
+fn mapped<K, V>(ids: &HashMap<K, V>, id: &K, what: &str) -> crate::Result<V>
+where
+    K: std::hash::Hash + Eq + std::fmt::Display,
+    V: Clone,
+{
+    ids.get(id)
+        .cloned()
+        .ok_or_else(|| Error::msg(format!("Archive refers to missing {} {}", what, id)))
+}
+
+/// Merge an archive read by `CollectionArchive::read` into the database.
+///
+/// The archive is merged into the collection of the same name if there is one. Documents are
+/// deduplicated by their hashes, embeddings by their configs and hashes, and the chunks of
+/// a document are only created if it is not split by the same splitting yet. The collection
+/// indexes are left with no indexed documents, since the vectors have to be upserted into the
+/// local vector dbs, which reuses the imported embeddings. Sessions are always created.
+pub async fn import(
+    db: &PrismaClient,
+    archive: CollectionArchive,
+) -> crate::Result<CollectionImportReport> {
+    let CollectionArchive {
+        manifest,
+        chunks,
+        vectors,
+        sessions,
+        blobs,
+    } = archive;
+    let (config, ids) =
+        config_bundle::import(db, manifest.config, ConflictStrategy::KeepExisting).await?;
+
+    let collection = match db
+        .collection()
+        .find_first(vec![collection::name::equals(manifest.name.clone())])
+        .exec()
+        .await?
+    {
+        Some(collection) => collection,
+        None => db.collection().create(manifest.name, vec![]).exec().await?,
+    };
+    let mut report = CollectionImportReport {
+        collection_id: collection.id,
+        config,
+        documents: Default::default(),
+        chunks: 0,
+        embeddings: Default::default(),
+        collection_indexes: Default::default(),
+        sessions: 0,
+    };
+
+    let mut document_ids = HashMap::new();
+    for doc in manifest.documents {
+        let existing = db
+            .document()
+            .find_first(vec![
+                document::md_5_hash::equals(doc.md5_hash.clone()),
+                document::hash_algo::equals(doc.hash_algo.clone()),
+            ])
+            .exec()
+            .await?;
+        let id = match existing {
+            Some(existing) => {
+                report.documents.reused += 1;
+                existing.id
+            }
+            None => {
+                let filepath = mapped(&blobs, &doc.md5_hash, "file of document")?;
+                report.documents.created += 1;
+                db.document()
+                    .create(
+                        doc.filename,
+                        filepath.to_string_lossy().to_string(),
+                        doc.md5_hash,
+                        doc.update_time,
+                        vec![document::hash_algo::set(doc.hash_algo)],
+                    )
+                    .exec()
+                    .await?
+                    .id
+            }
+        };
+        let linked = db
+            .collections_on_documents()
+            .find_unique(collections_on_documents::collection_id_document_id(
+                collection.id,
+                id,
+            ))
+            .exec()
+            .await?;
+        if linked.is_none() {
+            db.collections_on_documents()
+                .create(
+                    collection::id::equals(collection.id),
+                    document::id::equals(id),
+                    vec![],
+                )
+                .exec()
+                .await?;
+        }
+        document_ids.insert(doc.id, id);
+    }
+
+    let mut chunk_groups = BTreeMap::<(i32, i32), Vec<ArchivedChunk>>::new();
+    for chunk in chunks {
+        let key = (
+            mapped(&document_ids, &chunk.document_id, "document")?,
+            mapped(&ids.splittings, &chunk.splitting_id, "splitting")?,
+        );
+        chunk_groups.entry(key).or_default().push(chunk);
+    }
+    for ((document_id, splitting_id), chunks) in chunk_groups {
+        let existing = db
+            .document_chunk()
+            .count(vec![
+                document_chunk::document_id::equals(document_id),
+                document_chunk::splitting_id::equals(splitting_id),
+            ])
+            .exec()
+            .await?;
+        if existing > 0 {
+            continue;
+        }
+        report.chunks += chunks.len() as i32;
+        db._batch(chunks.into_iter().map(|chunk| {
+            db.document_chunk().create(
+                document::id::equals(document_id),
+                splitting::id::equals(splitting_id),
+                chunk.no,
+                chunk.content,
+                chunk.meta,
+                chunk.md5_hash,
+                vec![
+                    document_chunk::hash_algo::set(chunk.hash_algo),
+                    document_chunk::token_count::set(chunk.token_count),
+                ],
+            )
+        }))
+        .await?;
+    }
+
+    let mut vector_groups = HashMap::<i32, Vec<ArchivedVector>>::new();
+    for vector in vectors {
+        let config_id = mapped(
+            &ids.embeddings_configs,
+            &vector.embeddings_config_id,
+            "embeddings config",
+        )?;
+        vector_groups.entry(config_id).or_default().push(vector);
+    }
+    for (config_id, vectors) in vector_groups {
+        let existing = db
+            .embedding_vectors_on_document_chunks()
+            .find_many(vec![
+                embedding_vectors_on_document_chunks::embeddings_config_id::equals(config_id),
+                embedding_vectors_on_document_chunks::md_5_hash::in_vec(
+                    vectors
+                        .iter()
+                        .map(|vector| vector.md5_hash.clone())
+                        .collect(),
+                ),
+            ])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|vector| vector.md_5_hash)
+            .collect::<HashSet<_>>();
+        let missing = vectors
+            .into_iter()
+            .filter(|vector| !existing.contains(&vector.md5_hash))
+            .map(|vector| {
+                Ok((
+                    vector.md5_hash,
+                    vector.hash_algo,
+                    BASE64.decode(vector.vector)?,
+                ))
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        report.embeddings.reused += existing.len() as i32;
+        report.embeddings.created += missing.len() as i32;
+        db._batch(missing.into_iter().map(|(md5_hash, hash_algo, vector)| {
+            db.embedding_vectors_on_document_chunks().create(
+                md5_hash,
+                embeddings_config::id::equals(config_id),
+                vector,
+                vec![embedding_vectors_on_document_chunks::hash_algo::set(
+                    hash_algo,
+                )],
+            )
+        }))
+        .await?;
+    }
+
+    let mut index_ids = HashMap::new();
+    for index in manifest.indexes {
+        let profile_id = mapped(
+            &ids.index_profiles,
+            &index.index_profile_id,
+            "index profile",
+        )?;
+        let existing = db
+            .collection_index()
+            .find_unique(collection_index::collection_id_index_id(
+                collection.id,
+                profile_id,
+            ))
+            .exec()
+            .await?;
+        let id = match existing {
+            Some(existing) => {
+                report.collection_indexes.reused += 1;
+                existing.id
+            }
+            None => {
+                report.collection_indexes.created += 1;
+                db.collection_index()
+                    .create(
+                        index.name,
+                        collection::id::equals(collection.id),
+                        index_profile::id::equals(profile_id),
+                        vec![],
+                    )
+                    .exec()
+                    .await?
+                    .id
+            }
+        };
+        index_ids.insert(index.id, id);
+    }
+
+    for archived in sessions {
+        let index_id = mapped(&index_ids, &archived.index_id, "collection index")?;
+        let session = db
+            .session()
+            .create(
+                archived.name,
+                collection_index::id::equals(index_id),
+                archived.history,
+                vec![session::summary::set(archived.summary)],
+            )
+            .exec()
+            .await?;
+
+        // parents are created before their children, which have greater ids
+        let mut messages = archived.messages;
+        messages.sort_by_key(|message| message.id);
+        let mut message_ids = HashMap::new();
+        for message in messages {
+            let cited_chunks = message
+                .cited_chunks
+                .into_iter()
+                .filter_map(|chunk| {
+                    Some(ChunkRef {
+                        document_id: *document_ids.get(&chunk.document_id)?,
+                        splitting_id: *ids.splittings.get(&chunk.splitting_id)?,
+                        no: chunk.no,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let parent_id = message
+                .parent_id
+                .and_then(|id| message_ids.get(&id).copied());
+            let created = db
+                .session_message()
+                .create(
+                    session::id::equals(session.id),
+                    message.role,
+                    message.content,
+                    vec![
+                        parent_id.map(|id| {
+                            session_message::parent::connect(session_message::id::equals(id))
+                        }),
+                        Some(session_message::token_count::set(message.token_count)),
+                        Some(session_message::create_time::set(message.create_time)),
+                        Some(session_message::prompt_tokens::set(message.prompt_tokens)),
+                        Some(session_message::completion_tokens::set(
+                            message.completion_tokens,
+                        )),
+                        Some(session_message::cited_chunks::set(serde_json::to_string(
+                            &cited_chunks,
+                        )?)),
+                        Some(session_message::model::set(message.model)),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+                )
+                .exec()
+                .await?;
+            message_ids.insert(message.id, created.id);
+        }
+
+        db.session()
+            .update(
+                session::id::equals(session.id),
+                vec![
+                    Some(session::summarized_message_id::set(
+                        archived
+                            .summarized_message_id
+                            .and_then(|id| message_ids.get(&id).copied()),
+                    )),
+                    archived
+                        .active_message_id
+                        .and_then(|id| message_ids.get(&id).copied())
+                        .map(|id| {
+                            session::active_message::connect(session_message::id::equals(id))
+                        }),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+            )
+            .exec()
+            .await?;
+        report.sessions += 1;
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tokio::sync::oneshot;
+
+use crate::core::client_types::{ClientPayload, CompletionClientInfo};
+use crate::core::http::ensure_success;
+use crate::core::result::Error;
+use crate::core::secrets;
+use crate::core::tokenizer::{count_message_tokens, count_tokens, ContextBudget};
+use crate::core::usage::{self, Price, Usage, UsageKind, UsageLog, UsageScope};
+use crate::prisma::{completion_client, PrismaClient};
+
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// A message of a chat completion, in the OpenAI chat format.
+#[derive(Serialize, Deserialize, Type, Clone, Debug)]
+pub struct ChatMessage {
+    /// One of `system`, `user` or `assistant`.
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system<S: Into<String>>(content: S) -> Self {
+        Self {
+            role: "system".to_string(),
+            content: content.into(),
+        }
+    }
+
+    pub fn user<S: Into<String>>(content: S) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant<S: Into<String>>(content: S) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+/// Fill the `{name}` placeholders of a prompt template with their values in a single pass, so
+/// that a placeholder inside a value, which may come from the user, is left as is.
+pub fn fill_prompt(template: &str, values: &[(&str, &str)]) -> String {
+    let mut prompt = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        prompt.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            values
+                .iter()
+                .find(|(name, _)| *name == &rest[1..end])
+                .map(|(_, value)| (end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                prompt.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                prompt.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    prompt.push_str(rest);
+    prompt
+}
+
+/// Which completion client and model to complete with.
+#[derive(Deserialize, Type, Clone, Debug)]
+pub struct CompletionConfig {
+    #[serde(rename = "clientId")]
+    pub client_id: i32,
+    pub model: String,
+    pub temperature: Option<f32>,
+    #[serde(rename = "maxTokens")]
+    pub max_tokens: Option<i32>,
+    /// Price of the model, if not the one known by default.
+    pub price: Option<Price>,
+}
+
+/// A client of an OpenAI-compatible chat completion api, bound to the model to complete with.
+///
+/// The tokens of every call are logged until recorded by `record_usage`.
+pub struct Completion {
+    base_url: String,
+    api_key: String,
+    config: CompletionConfig,
+    http: reqwest::Client,
+    usage: UsageLog,
+}
+
+#[derive(Serialize)]
+struct ChatCompletionRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<i32>,
+    stream: bool,
+}
+
+impl<'a> ChatCompletionRequest<'a> {
+    fn new(config: &'a CompletionConfig, messages: &'a [ChatMessage], stream: bool) -> Self {
+        Self {
+            model: &config.model,
+            messages,
+            temperature: config.temperature,
+            max_tokens: config.max_tokens,
+            stream,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatCompletionChoice>,
+    usage: Option<ChatCompletionUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionUsage {
+    prompt_tokens: i32,
+    completion_tokens: i32,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionChunk {
+    choices: Vec<ChatCompletionChunkChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionChunkChoice {
+    #[serde(default)]
+    delta: ChatCompletionDelta,
+}
+
+#[derive(Default, Deserialize)]
+struct ChatCompletionDelta {
+    content: Option<String>,
+}
+
+impl Completion {
+    pub fn from_data(
+        client: &completion_client::Data,
+        config: CompletionConfig,
+    ) -> crate::Result<Self> {
+        let info = CompletionClientInfo::from_stored(
+            &client.r#type,
+            &secrets::reveal_info(&client.info)?,
+        )?;
+        match info {
+            CompletionClientInfo::OpenAi(info) => Ok(Completion {
+                base_url: info
+                    .base_url
+                    .unwrap_or_else(|| OPENAI_BASE_URL.to_string())
+                    .trim_end_matches('/')
+                    .to_string(),
+                api_key: info.api_key,
+                config,
+                http: reqwest::Client::new(),
+                usage: UsageLog::default(),
+            }),
+        }
+    }
+
+    /// Build the client referred by `config`.
+    pub async fn from_config(db: &PrismaClient, config: CompletionConfig) -> crate::Result<Self> {
+        let client = db
+            .completion_client()
+            .find_unique(completion_client::id::equals(config.client_id))
+            .exec()
+            .await?
+            .ok_or_else(|| {
+                Error::msg(format!("Completion client {} not found", config.client_id))
+            })?;
+        Self::from_data(&client, config)
+    }
+
+    /// The model completed with.
+    pub fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    /// Record the usages of the calls made so far, for the session if given.
+    pub async fn record_usage(
+        &self,
+        db: &PrismaClient,
+        session_id: Option<i32>,
+    ) -> crate::Result<()> {
+        let scope = UsageScope {
+            completion_client_id: Some(self.config.client_id),
+            session_id,
+            ..Default::default()
+        };
+        usage::record(db, self.usage.take(), &scope).await
+    }
+
+    fn log_usage(&self, prompt_tokens: i32, completion_tokens: i32) {
+        let price = self
+            .config
+            .price
+            .unwrap_or_else(|| Price::of_model(None, &self.config.model));
+        self.usage.push(Usage::new(
+            UsageKind::Completion,
+            &self.config.model,
+            &price,
+            prompt_tokens,
+            completion_tokens,
+        ));
+    }
+
+    /// The token budget of prompts sent to the model.
+    pub fn budget(&self) -> ContextBudget {
+        ContextBudget::for_model(&self.config.model, self.config.max_tokens)
+    }
+
+    /// Fail early if the messages would overflow the context window of the model.
+    fn check_context(&self, messages: &[ChatMessage]) -> crate::Result<()> {
+        let tokens = count_message_tokens(messages);
+        let limit = self.budget().prompt_tokens();
+        if tokens > limit {
+            return Err(Error::msg(format!(
+                "Prompt of {} tokens exceeds the limit of {} tokens of model {}",
+                tokens, limit, self.config.model
+            )));
+        }
+        Ok(())
+    }
+
+    /// Complete the chat and return the whole answer at once.
+    pub async fn complete(&self, messages: &[ChatMessage]) -> crate::Result<String> {
+        self.check_context(messages)?;
+        let response = self
+            .http
+            .post(format!("{}/chat/completions", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&ChatCompletionRequest::new(&self.config, messages, false))
+            .send()
+            .await?;
+        let response = ensure_success(response)
+            .await?
+            .json::<ChatCompletionResponse>()
+            .await?;
+        let content = response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .ok_or_else(|| Error::msg("Completion service returned no choice"))?;
+        match response.usage {
+            Some(usage) => self.log_usage(usage.prompt_tokens, usage.completion_tokens),
+            None => self.log_usage(
+                count_message_tokens(messages) as i32,
+                count_tokens(&content) as i32,
+            ),
+        }
+        Ok(content)
+    }
+
+    /// Complete the chat in streaming mode.
+    ///
+    /// The answer is read from the server-sent events of the response, and each token delta is
+    /// passed to `on_delta` as soon as it arrives. The whole answer is returned at the end.
+    ///
+    /// Streamed responses come without usage, so the tokens are counted locally. A stream failed
+    /// or cancelled halfway is still logged, with the tokens of the deltas received so far.
+    pub async fn stream<F>(
+        &self,
+        messages: &[ChatMessage],
+        mut on_delta: F,
+    ) -> crate::Result<String>
+    where
+        F: FnMut(&str),
+    {
+        self.check_context(messages)?;
+        let response = self
+            .http
+            .post(format!("{}/chat/completions", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&ChatCompletionRequest::new(&self.config, messages, true))
+            .send()
+            .await?;
+        let mut response = ensure_success(response).await?;
+
+        let mut usage = StreamUsage {
+            completion: self,
+            prompt_tokens: count_message_tokens(messages) as i32,
+            content: String::new(),
+        };
+        let mut buffer = Vec::new();
+        'read: while let Some(bytes) = response.chunk().await? {
+            buffer.extend_from_slice(&bytes);
+            while let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                let line = buffer.drain(..=pos).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&line);
+                let data = match line.trim().strip_prefix("data:") {
+                    Some(data) => data.trim(),
+                    None => continue,
+                };
+                if data == "[DONE]" {
+                    break 'read;
+                }
+                let chunk: ChatCompletionChunk = serde_json::from_str(data)?;
+                for delta in chunk.choices.into_iter().filter_map(|c| c.delta.content) {
+                    on_delta(&delta);
+                    usage.content.push_str(&delta);
+                }
+            }
+        }
+        Ok(usage.content.clone())
+    }
+}
+
+/// The usage of a streamed completion, which is logged once dropped, whether the stream finishes,
+/// fails or gets cancelled.
+struct StreamUsage<'a> {
+    completion: &'a Completion,
+    prompt_tokens: i32,
+    /// The answer received so far.
+    content: String,
+}
+
+impl Drop for StreamUsage<'_> {
+    fn drop(&mut self) {
+        self.completion
+            .log_usage(self.prompt_tokens, count_tokens(&self.content) as i32);
+    }
+}
+
+/// Completions in progress, at most one per session.
+///
+/// Each completion can be cancelled by its session id. Starting a new completion for a session
+/// cancels the one in progress.
+#[derive(Default)]
+pub struct CompletionTasks {
+    next_task_id: AtomicU32,
+    tasks: Mutex<HashMap<i32, (u32, oneshot::Sender<()>)>>,
+}
+
+/// A completion of a session registered by `CompletionTasks::register`, to be run by
+/// `CompletionTasks::run`.
+pub struct CompletionTask {
+    /// Tells the events of the completion from the ones of the completions it replaces.
+    pub id: u32,
+    session_id: i32,
+    cancelled: oneshot::Receiver<()>,
+}
+
+impl CompletionTasks {
+    /// Register a completion for the session, which cancels the one in progress.
+    ///
+    /// Register it before spawning the completion, so that cancelling the completion right after
+    /// starting it finds it.
+    pub fn register(&self, session_id: i32) -> CompletionTask {
+        let id = self.next_task_id.fetch_add(1, Ordering::Relaxed);
+        let (cancel, cancelled) = oneshot::channel();
+        // dropping the sender of the replaced task cancels it
+        self.tasks.lock().unwrap().insert(session_id, (id, cancel));
+        CompletionTask {
+            id,
+            session_id,
+            cancelled,
+        }
+    }
+
+    /// Run `completion` of a registered task until it finishes or gets cancelled.
+    pub async fn run<T, F>(&self, task: CompletionTask, completion: F) -> crate::Result<T>
+    where
+        F: Future<Output = crate::Result<T>>,
+    {
+        let result = tokio::select! {
+            result = completion => result,
+            _ = task.cancelled => Err(Error::msg("Completion cancelled")),
+        };
+
+        let mut tasks = self.tasks.lock().unwrap();
+        if matches!(tasks.get(&task.session_id), Some((id, _)) if *id == task.id) {
+            tasks.remove(&task.session_id);
+        }
+        result
+    }
+
+    /// Cancel the completion in progress of the session.
+    ///
+    /// Returns `false` if there is no completion in progress.
+    pub fn cancel(&self, session_id: i32) -> bool {
+        match self.tasks.lock().unwrap().remove(&session_id) {
+            Some((_, cancel)) => {
+                let _ = cancel.send(());
+                true
+            }
+            None => false,
+        }
+    }
+}
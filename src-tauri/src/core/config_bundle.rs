@@ -0,0 +1,702 @@
+use std::collections::HashMap;
+
+use prisma_client_rust::chrono::Local;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::core::client_types::{
+    self, ClientPayload, EmbeddingsClientInfo, EmbeddingsConfigMeta, VectorDbClientInfo,
+    VectorDbConfigMeta,
+};
+use crate::core::result::Error;
+use crate::core::secrets;
+use crate::core::validation::ValidationError;
+use crate::prisma::{
+    embeddings_client, embeddings_config, index_profile, splitting, vector_db_client,
+    vector_db_config, PrismaClient,
+};
+
+/// Version of the layout of the bundles written, bumped on incompatible changes.
+pub const BUNDLE_VERSION: i32 = 1;
+
+#[derive(Deserialize, Type, Clone, Copy, Debug)]
+pub enum ConfigBundleFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigBundleFormat {
+    pub fn write(&self, bundle: &ConfigBundle) -> crate::Result<String> {
+        Ok(match self {
+            ConfigBundleFormat::Json => serde_json::to_string_pretty(bundle)?,
+            ConfigBundleFormat::Toml => toml::to_string_pretty(bundle)?,
+        })
+    }
+
+    pub fn read(&self, text: &str) -> crate::Result<ConfigBundle> {
+        Ok(match self {
+            ConfigBundleFormat::Json => serde_json::from_str(text)?,
+            ConfigBundleFormat::Toml => toml::from_str(text)?,
+        })
+    }
+}
+
+/// What to do with a client of a bundle matching an existing one.
+#[derive(Deserialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// Use the existing client as is.
+    KeepExisting,
+    /// Update the name and the secrets of the existing client by the bundled one.
+    Overwrite,
+}
+
+/// The clients, configs, splittings and index profiles of a database, for setting up another.
+///
+/// The ids are the ones in the exporting database, only used for the references between them.
+#[derive(Serialize, Deserialize)]
+pub struct ConfigBundle {
+    pub version: i32,
+    #[serde(rename = "exportTime")]
+    pub export_time: String,
+    #[serde(rename = "embeddingsClients", default)]
+    pub embeddings_clients: Vec<BundledClient>,
+    #[serde(rename = "embeddingsConfigs", default)]
+    pub embeddings_configs: Vec<BundledConfig>,
+    #[serde(rename = "vectorDbClients", default)]
+    pub vector_db_clients: Vec<BundledClient>,
+    #[serde(rename = "vectorDbConfigs", default)]
+    pub vector_db_configs: Vec<BundledConfig>,
+    #[serde(default)]
+    pub splittings: Vec<BundledSplitting>,
+    #[serde(rename = "indexProfiles", default)]
+    pub index_profiles: Vec<BundledIndexProfile>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundledClient {
+    pub id: i32,
+    pub name: String,
+    pub r#type: String,
+    /// The info with plain secrets, or without the secret fields if stripped.
+    pub info: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundledConfig {
+    pub id: i32,
+    pub name: String,
+    #[serde(rename = "clientType")]
+    pub client_type: String,
+    pub meta: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundledSplitting {
+    pub id: i32,
+    #[serde(rename = "chunkSize")]
+    pub chunk_size: i32,
+    #[serde(rename = "chunkOverlap")]
+    pub chunk_overlap: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundledIndexProfile {
+    pub id: i32,
+    pub name: String,
+    #[serde(rename = "splittingId")]
+    pub splitting_id: i32,
+    #[serde(rename = "embeddingsClientId")]
+    pub embeddings_client_id: i32,
+    #[serde(rename = "embeddingsConfigId")]
+    pub embeddings_config_id: i32,
+    #[serde(rename = "vectorDbClientId")]
+    pub vector_db_client_id: i32,
+    #[serde(rename = "vectorDbConfigId")]
+    pub vector_db_config_id: i32,
+}
+
+/// Ids of the imported or reused rows by the ids in a bundle.
+#[derive(Default, Debug)]
+pub struct ImportedIds {
+    pub splittings: HashMap<i32, i32>,
+    pub embeddings_configs: HashMap<i32, i32>,
+    pub index_profiles: HashMap<i32, i32>,
+}
+
+#[derive(Serialize, Type, Default, Clone, Copy, Debug)]
+pub struct ImportCounts {
+    pub(crate) created: i32,
+    pub(crate) reused: i32,
+    pub(crate) updated: i32,
+}
+
+#[derive(Serialize, Type, Default, Debug)]
+pub struct ConfigImportReport {
+    #[serde(rename = "embeddingsClients")]
+    embeddings_clients: ImportCounts,
+    #[serde(rename = "embeddingsConfigs")]
+    embeddings_configs: ImportCounts,
+    #[serde(rename = "vectorDbClients")]
+    vector_db_clients: ImportCounts,
+    #[serde(rename = "vectorDbConfigs")]
+    vector_db_configs: ImportCounts,
+    splittings: ImportCounts,
+    #[serde(rename = "indexProfiles")]
+    index_profiles: ImportCounts,
+    /// Ids of the embeddings clients created without the secrets stripped from the bundle, which
+    /// have to be filled in before use.
+    #[serde(rename = "embeddingsClientsNeedingSecrets")]
+    embeddings_clients_needing_secrets: Vec<i32>,
+    /// Ids of the vector db clients created without the secrets stripped from the bundle.
+    #[serde(rename = "vectorDbClientsNeedingSecrets")]
+    vector_db_clients_needing_secrets: Vec<i32>,
+}
+
+/// Bundle the clients, configs, splittings and index profiles of the database.
+///
+/// If `profile_ids` is given, only the index profiles of them and what they refer to are
+/// bundled. Secrets are revealed, which requires them to be unlocked, unless `strip_secrets` is
+/// set to leave the secret fields out.
+pub async fn export(
+    db: &PrismaClient,
+    strip_secrets: bool,
+    profile_ids: Option<Vec<i32>>,
+) -> crate::Result<ConfigBundle> {
+    let bundle_info = |info: &str| -> crate::Result<serde_json::Value> {
+        if strip_secrets {
+            Ok(secrets::strip_info(serde_json::from_str(info)?))
+        } else {
+            Ok(serde_json::from_str(&secrets::reveal_info(info)?)?)
+        }
+    };
+
+    let profiles = db
+        .index_profile()
+        .find_many(
+            profile_ids
+                .clone()
+                .map(|ids| vec![index_profile::id::in_vec(ids)])
+                .unwrap_or_default(),
+        )
+        .exec()
+        .await?;
+    // only what the profiles refer to, if the profiles are given
+    let referred = |ids: Vec<i32>| profile_ids.as_ref().map(|_| ids);
+    let embeddings_client_filter = referred(
+        profiles
+            .iter()
+            .map(|profile| profile.embeddings_client_id)
+            .collect(),
+    )
+    .map(|ids| vec![embeddings_client::id::in_vec(ids)])
+    .unwrap_or_default();
+    let embeddings_config_filter = referred(
+        profiles
+            .iter()
+            .map(|profile| profile.embeddings_config_id)
+            .collect(),
+    )
+    .map(|ids| vec![embeddings_config::id::in_vec(ids)])
+    .unwrap_or_default();
+    let vector_db_client_filter = referred(
+        profiles
+            .iter()
+            .map(|profile| profile.vector_db_client_id)
+            .collect(),
+    )
+    .map(|ids| vec![vector_db_client::id::in_vec(ids)])
+    .unwrap_or_default();
+    let vector_db_config_filter = referred(
+        profiles
+            .iter()
+            .map(|profile| profile.vector_db_config_id)
+            .collect(),
+    )
+    .map(|ids| vec![vector_db_config::id::in_vec(ids)])
+    .unwrap_or_default();
+    let splitting_filter = referred(
+        profiles
+            .iter()
+            .map(|profile| profile.splitting_id)
+            .collect(),
+    )
+    .map(|ids| vec![splitting::id::in_vec(ids)])
+    .unwrap_or_default();
+
+    let embeddings_clients = db
+        .embeddings_client()
+        .find_many(embeddings_client_filter)
+        .exec()
+        .await?
+        .into_iter()
+        .map(|client| {
+            Ok(BundledClient {
+                info: bundle_info(&client.info)?,
+                id: client.id,
+                name: client.name,
+                r#type: client.r#type,
+            })
+        })
+        .collect::<crate::Result<_>>()?;
+    let vector_db_clients = db
+        .vector_db_client()
+        .find_many(vector_db_client_filter)
+        .exec()
+        .await?
+        .into_iter()
+        .map(|client| {
+            Ok(BundledClient {
+                info: bundle_info(&client.info)?,
+                id: client.id,
+                name: client.name,
+                r#type: client.r#type,
+            })
+        })
+        .collect::<crate::Result<_>>()?;
+    let embeddings_configs = db
+        .embeddings_config()
+        .find_many(embeddings_config_filter)
+        .exec()
+        .await?
+        .into_iter()
+        .map(|config| {
+            Ok(BundledConfig {
+                meta: serde_json::from_str(&config.meta)?,
+                id: config.id,
+                name: config.name,
+                client_type: config.client_type,
+            })
+        })
+        .collect::<crate::Result<_>>()?;
+    let vector_db_configs = db
+        .vector_db_config()
+        .find_many(vector_db_config_filter)
+        .exec()
+        .await?
+        .into_iter()
+        .map(|config| {
+            Ok(BundledConfig {
+                meta: serde_json::from_str(&config.meta)?,
+                id: config.id,
+                name: config.name,
+                client_type: config.client_type,
+            })
+        })
+        .collect::<crate::Result<_>>()?;
+    let splittings = db
+        .splitting()
+        .find_many(splitting_filter)
+        .exec()
+        .await?
+        .into_iter()
+        .map(|splitting| BundledSplitting {
+            id: splitting.id,
+            chunk_size: splitting.chunk_size,
+            chunk_overlap: splitting.chunk_overlap,
+        })
+        .collect();
+
+    let index_profiles = profiles
+        .into_iter()
+        .map(|profile| BundledIndexProfile {
+            id: profile.id,
+            name: profile.name,
+            splitting_id: profile.splitting_id,
+            embeddings_client_id: profile.embeddings_client_id,
+            embeddings_config_id: profile.embeddings_config_id,
+            vector_db_client_id: profile.vector_db_client_id,
+            vector_db_config_id: profile.vector_db_config_id,
+        })
+        .collect();
+
+    Ok(ConfigBundle {
+        version: BUNDLE_VERSION,
+        export_time: Local::now().to_rfc3339(),
+        embeddings_clients,
+        embeddings_configs,
+        vector_db_clients,
+        vector_db_configs,
+        splittings,
+        index_profiles,
+    })
+}
+
+/// Validate a bundled client, returning whether it lacks secrets stripped from the bundle, which
+/// are left for the user to fill in rather than failing the import.
+fn validate_client<P: ClientPayload>(client: &BundledClient) -> crate::Result<bool> {
+    let payload = P::from_stored(&client.r#type, &client.info.to_string())?;
+    let mut errors = ValidationError::default();
+    errors.require("name", &client.name);
+    payload.validate(&mut errors);
+    let mut needs_secrets = false;
+    let mut invalid = ValidationError::default();
+    for error in errors.errors() {
+        let stripped = error.field.strip_prefix("info.").map_or(false, |name| {
+            secrets::is_secret_field(name) && client.info.get(name).is_none()
+        });
+        if stripped {
+            needs_secrets = true;
+        } else {
+            invalid.add(error.field.clone(), error.message.clone());
+        }
+    }
+    invalid.into_result().map_err(|err| {
+        Error::msg(format!(
+            "Client {} of the bundle is invalid: {}",
+            client.name, err
+        ))
+    })?;
+    Ok(needs_secrets)
+}
+
+/// Find the stored client a bundled client stands for.
+///
+/// A bundled client with secrets matches the client with the same info, secrets included, as the
+/// `@@unique([type, info])` constraints mean, which requires the stored secrets to be unlocked. A
+/// bundled client with stripped secrets matches by the other fields, such as `baseUrl`, and
+/// matches none if it has none of them, as any client of its type would do otherwise.
+fn find_matching_client<'a, I>(
+    candidates: I,
+    info: &serde_json::Value,
+) -> crate::Result<Option<(i32, &'a str)>>
+where
+    I: IntoIterator<Item = (i32, &'a str)>,
+{
+    let public = secrets::strip_info(info.clone());
+    let has_secrets = public != *info;
+    let identified = public.as_object().map_or(false, |fields| {
+        fields.values().any(|value| !value.is_null())
+    });
+    if !has_secrets && !identified {
+        return Ok(None);
+    }
+    for (id, stored) in candidates {
+        let matches = if has_secrets {
+            serde_json::from_str::<serde_json::Value>(&secrets::reveal_info(stored)?)? == *info
+        } else {
+            serde_json::from_str(stored)
+                .map(|stored| secrets::strip_info(stored) == public)
+                .unwrap_or(false)
+        };
+        if matches {
+            return Ok(Some((id, stored)));
+        }
+    }
+    Ok(None)
+}
+
+/// The info of a bundled client to overwrite a stored one by, keeping the stored fields left out
+/// of the bundle, such as stripped secrets.
+fn overwriting_info(info: &serde_json::Value, stored: &str) -> crate::Result<serde_json::Value> {
+    let mut merged: serde_json::Value = serde_json::from_str(stored)?;
+    if let (Some(merged), Some(fields)) = (merged.as_object_mut(), info.as_object()) {
+        for (name, value) in fields {
+            merged.insert(name.clone(), value.clone());
+        }
+    }
+    Ok(merged)
+}
+
+fn missing_reference(profile: &BundledIndexProfile, what: &str, id: i32) -> Error {
+    Error::msg(format!(
+        "Index profile {} refers to {} {} missing in the bundle",
+        profile.name, what, id
+    ))
+}
+
+/// Import a bundle, reusing the clients, configs, splittings and index profiles matching
+/// existing ones rather than duplicating them.
+///
+/// Clients match by their type and their info, secrets included if the bundle has them, which is
+/// what the `@@unique([type, info])` constraints stand for. Configs match by their client type
+/// and meta, and index profiles by their name and what they refer to.
+///
+/// Clients and configs are validated as when created. Clients created without the secrets
+/// stripped from the bundle are reported, so that the user fills them in.
+pub async fn import(
+    db: &PrismaClient,
+    bundle: ConfigBundle,
+    on_conflict: ConflictStrategy,
+) -> crate::Result<(ConfigImportReport, ImportedIds)> {
+    if bundle.version > BUNDLE_VERSION {
+        return Err(Error::msg(format!(
+            "Bundle version {} is newer than the supported version {}",
+            bundle.version, BUNDLE_VERSION
+        )));
+    }
+    let mut report = ConfigImportReport::default();
+
+    let mut embeddings_client_ids = HashMap::new();
+    for client in bundle.embeddings_clients {
+        let needs_secrets = validate_client::<EmbeddingsClientInfo>(&client)?;
+        let candidates = db
+            .embeddings_client()
+            .find_many(vec![embeddings_client::r#type::equals(
+                client.r#type.clone(),
+            )])
+            .exec()
+            .await?;
+        let matched = find_matching_client(
+            candidates
+                .iter()
+                .map(|stored| (stored.id, stored.info.as_str())),
+            &client.info,
+        )?;
+        let id = match matched {
+            Some((id, stored)) if on_conflict == ConflictStrategy::Overwrite => {
+                let info = secrets::seal_info(db, overwriting_info(&client.info, stored)?).await?;
+                db.embeddings_client()
+                    .update(
+                        embeddings_client::id::equals(id),
+                        vec![
+                            embeddings_client::name::set(client.name),
+                            embeddings_client::info::set(info),
+                        ],
+                    )
+                    .exec()
+                    .await?;
+                report.embeddings_clients.updated += 1;
+                id
+            }
+            Some((id, _)) => {
+                report.embeddings_clients.reused += 1;
+                id
+            }
+            None => {
+                let info = secrets::seal_info(db, client.info).await?;
+                report.embeddings_clients.created += 1;
+                let id = db
+                    .embeddings_client()
+                    .create(client.name, client.r#type, info, vec![])
+                    .exec()
+                    .await?
+                    .id;
+                if needs_secrets {
+                    report.embeddings_clients_needing_secrets.push(id);
+                }
+                id
+            }
+        };
+        embeddings_client_ids.insert(client.id, id);
+    }
+
+    let mut vector_db_client_ids = HashMap::new();
+    for client in bundle.vector_db_clients {
+        let needs_secrets = validate_client::<VectorDbClientInfo>(&client)?;
+        let candidates = db
+            .vector_db_client()
+            .find_many(vec![vector_db_client::r#type::equals(
+                client.r#type.clone(),
+            )])
+            .exec()
+            .await?;
+        let matched = find_matching_client(
+            candidates
+                .iter()
+                .map(|stored| (stored.id, stored.info.as_str())),
+            &client.info,
+        )?;
+        let id = match matched {
+            Some((id, stored)) if on_conflict == ConflictStrategy::Overwrite => {
+                let info = secrets::seal_info(db, overwriting_info(&client.info, stored)?).await?;
+                db.vector_db_client()
+                    .update(
+                        vector_db_client::id::equals(id),
+                        vec![
+                            vector_db_client::name::set(client.name),
+                            vector_db_client::info::set(info),
+                        ],
+                    )
+                    .exec()
+                    .await?;
+                report.vector_db_clients.updated += 1;
+                id
+            }
+            Some((id, _)) => {
+                report.vector_db_clients.reused += 1;
+                id
+            }
+            None => {
+                let info = secrets::seal_info(db, client.info).await?;
+                report.vector_db_clients.created += 1;
+                let id = db
+                    .vector_db_client()
+                    .create(client.name, client.r#type, info, vec![])
+                    .exec()
+                    .await?
+                    .id;
+                if needs_secrets {
+                    report.vector_db_clients_needing_secrets.push(id);
+                }
+                id
+            }
+        };
+        vector_db_client_ids.insert(client.id, id);
+    }
+
+    let mut embeddings_config_ids = HashMap::new();
+    for config in bundle.embeddings_configs {
+        let meta =
+            EmbeddingsConfigMeta::from_stored(&config.client_type, &config.meta.to_string())?;
+        client_types::validate(&config.name, &meta)?;
+        let matched = db
+            .embeddings_config()
+            .find_many(vec![embeddings_config::client_type::equals(
+                config.client_type.clone(),
+            )])
+            .exec()
+            .await?
+            .into_iter()
+            .find(|stored| serde_json::from_str(&stored.meta).ok().as_ref() == Some(&config.meta));
+        let id = match matched {
+            Some(stored) => {
+                report.embeddings_configs.reused += 1;
+                stored.id
+            }
+            None => {
+                report.embeddings_configs.created += 1;
+                db.embeddings_config()
+                    .create(
+                        config.name,
+                        config.client_type,
+                        config.meta.to_string(),
+                        vec![],
+                    )
+                    .exec()
+                    .await?
+                    .id
+            }
+        };
+        embeddings_config_ids.insert(config.id, id);
+    }
+
+    let mut vector_db_config_ids = HashMap::new();
+    for config in bundle.vector_db_configs {
+        let meta = VectorDbConfigMeta::from_stored(&config.client_type, &config.meta.to_string())?;
+        client_types::validate(&config.name, &meta)?;
+        let matched = db
+            .vector_db_config()
+            .find_many(vec![vector_db_config::client_type::equals(
+                config.client_type.clone(),
+            )])
+            .exec()
+            .await?
+            .into_iter()
+            .find(|stored| serde_json::from_str(&stored.meta).ok().as_ref() == Some(&config.meta));
+        let id = match matched {
+            Some(stored) => {
+                report.vector_db_configs.reused += 1;
+                stored.id
+            }
+            None => {
+                report.vector_db_configs.created += 1;
+                db.vector_db_config()
+                    .create(
+                        config.name,
+                        config.client_type,
+                        config.meta.to_string(),
+                        vec![],
+                    )
+                    .exec()
+                    .await?
+                    .id
+            }
+        };
+        vector_db_config_ids.insert(config.id, id);
+    }
+
+    let mut splitting_ids = HashMap::new();
+    for bundled in bundle.splittings {
+        let existing = db
+            .splitting()
+            .find_unique(splitting::chunk_overlap_chunk_size(
+                bundled.chunk_overlap,
+                bundled.chunk_size,
+            ))
+            .exec()
+            .await?;
+        let id = match existing {
+            Some(existing) => {
+                report.splittings.reused += 1;
+                existing.id
+            }
+            None => {
+                report.splittings.created += 1;
+                db.splitting()
+                    .create(bundled.chunk_size, bundled.chunk_overlap, vec![])
+                    .exec()
+                    .await?
+                    .id
+            }
+        };
+        splitting_ids.insert(bundled.id, id);
+    }
+
+    let mut index_profile_ids = HashMap::new();
+    for profile in bundle.index_profiles {
+        let splitting_id = *splitting_ids
+            .get(&profile.splitting_id)
+            .ok_or_else(|| missing_reference(&profile, "splitting", profile.splitting_id))?;
+        let embeddings_client_id = *embeddings_client_ids
+            .get(&profile.embeddings_client_id)
+            .ok_or_else(|| {
+                missing_reference(&profile, "embeddings client", profile.embeddings_client_id)
+            })?;
+        let embeddings_config_id = *embeddings_config_ids
+            .get(&profile.embeddings_config_id)
+            .ok_or_else(|| {
+                missing_reference(&profile, "embeddings config", profile.embeddings_config_id)
+            })?;
+        let vector_db_client_id = *vector_db_client_ids
+            .get(&profile.vector_db_client_id)
+            .ok_or_else(|| {
+                missing_reference(&profile, "vector db client", profile.vector_db_client_id)
+            })?;
+        let vector_db_config_id = *vector_db_config_ids
+            .get(&profile.vector_db_config_id)
+            .ok_or_else(|| {
+                missing_reference(&profile, "vector db config", profile.vector_db_config_id)
+            })?;
+
+        let existing = db
+            .index_profile()
+            .find_first(vec![
+                index_profile::name::equals(profile.name.clone()),
+                index_profile::splitting_id::equals(splitting_id),
+                index_profile::embeddings_client_id::equals(embeddings_client_id),
+                index_profile::embeddings_config_id::equals(embeddings_config_id),
+                index_profile::vector_db_client_id::equals(vector_db_client_id),
+                index_profile::vector_db_config_id::equals(vector_db_config_id),
+            ])
+            .exec()
+            .await?;
+        if let Some(existing) = existing {
+            report.index_profiles.reused += 1;
+            index_profile_ids.insert(profile.id, existing.id);
+            continue;
+        }
+        let id = db
+            .index_profile()
+            .create(
+                profile.name,
+                splitting::id::equals(splitting_id),
+                embeddings_client::id::equals(embeddings_client_id),
+                embeddings_config::id::equals(embeddings_config_id),
+                vector_db_client::id::equals(vector_db_client_id),
+                vector_db_config::id::equals(vector_db_config_id),
+                vec![],
+            )
+            .exec()
+            .await?
+            .id;
+        report.index_profiles.created += 1;
+        index_profile_ids.insert(profile.id, id);
+    }
+
+    let ids = ImportedIds {
+        splittings: splitting_ids,
+        embeddings_configs: embeddings_config_ids,
+        index_profiles: index_profile_ids,
+    };
+    Ok((report, ids))
+}
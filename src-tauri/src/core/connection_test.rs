@@ -0,0 +1,189 @@
+use std::time::Instant;
+
+use serde::Serialize;
+use specta::Type;
+
+use crate::core::embeddings::Embeddings;
+use crate::core::http::HttpStatusError;
+use crate::core::result::Error;
+use crate::core::usage::{self, UsageScope};
+use crate::core::vector_db::VectorDb;
+use crate::prisma::{embeddings_client, vector_db_client, PrismaClient};
+
+/// The text embedded to test an embeddings client.
+const TEST_TEXT: &str = "Hello, world!";
+
+/// What went wrong, if anything, when testing the connection of a client.
+#[derive(Serialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Diagnosis {
+    Ok,
+    /// The index exists but is still being initialized.
+    NotReady,
+    /// The client info cannot be used, such as sealed secrets that are locked.
+    InvalidInfo,
+    /// The service cannot be reached, such as by a wrong base url or environment.
+    Unreachable,
+    /// The api key is rejected.
+    Unauthorized,
+    /// The model or the index is not found.
+    NotFound,
+    RateLimited,
+    /// The service rejects the request or fails otherwise.
+    ServiceError,
+}
+
+impl Diagnosis {
+    fn of_error(err: &Error) -> Self {
+        if let Some(err) = err.downcast_ref::<HttpStatusError>() {
+            return match err.status.as_u16() {
+                401 | 403 => Diagnosis::Unauthorized,
+                404 => Diagnosis::NotFound,
+                429 => Diagnosis::RateLimited,
+                _ => Diagnosis::ServiceError,
+            };
+        }
+        match err.downcast_ref::<reqwest::Error>() {
+            Some(err) if err.is_connect() || err.is_timeout() || err.is_request() => {
+                Diagnosis::Unreachable
+            }
+            Some(_) => Diagnosis::ServiceError,
+            None => Diagnosis::InvalidInfo,
+        }
+    }
+}
+
+/// The outcome of a minimal real call made by a client.
+#[derive(Serialize, Type, Debug)]
+pub struct ConnectionReport {
+    diagnosis: Diagnosis,
+    /// Milliseconds taken by the call, if it is made.
+    #[serde(rename = "latencyMs")]
+    latency_ms: Option<i32>,
+    /// Dimension of the vectors embedded by the model or stored in the index.
+    dimension: Option<i32>,
+    /// The model embedded with.
+    model: Option<String>,
+    /// Distance metric of the index.
+    metric: Option<String>,
+    /// The error, if any.
+    message: Option<String>,
+    /// What to check in the client info to fix the error.
+    hint: Option<String>,
+}
+
+impl ConnectionReport {
+    fn failed(
+        err: Error,
+        latency_ms: Option<i32>,
+        hint: fn(Diagnosis) -> Option<&'static str>,
+    ) -> Self {
+        let diagnosis = Diagnosis::of_error(&err);
+        Self {
+            diagnosis,
+            latency_ms,
+            dimension: None,
+            model: None,
+            metric: None,
+            message: Some(err.to_string()),
+            hint: hint(diagnosis).map(str::to_string),
+        }
+    }
+}
+
+fn elapsed_ms(start: Instant) -> Option<i32> {
+    Some(start.elapsed().as_millis().min(i32::MAX as u128) as i32)
+}
+
+fn embeddings_hint(diagnosis: Diagnosis) -> Option<&'static str> {
+    match diagnosis {
+        Diagnosis::InvalidInfo => Some("Check the client info, or unlock the secrets"),
+        Diagnosis::Unreachable => Some("Check the base url and the network"),
+        Diagnosis::Unauthorized => Some("Check the api key"),
+        Diagnosis::NotFound => Some("Check the base url, the service may not serve the model"),
+        Diagnosis::RateLimited => Some("Check the quota of the account"),
+        _ => None,
+    }
+}
+
+fn vector_db_hint(diagnosis: Diagnosis) -> Option<&'static str> {
+    match diagnosis {
+        Diagnosis::InvalidInfo => Some("Check the client info, or unlock the secrets"),
+        Diagnosis::NotReady => Some("Wait for the index to be initialized"),
+        Diagnosis::Unreachable => Some("Check the environment and the network"),
+        Diagnosis::Unauthorized => Some("Check the api key and the environment"),
+        Diagnosis::NotFound => Some("Check the index name"),
+        _ => None,
+    }
+}
+
+/// Test an embeddings client by embedding a short text with the default model of its type.
+///
+/// The tokens spent are recorded for the client.
+pub async fn test_embeddings_client(
+    db: &PrismaClient,
+    client: &embeddings_client::Data,
+) -> ConnectionReport {
+    let embeddings = match Embeddings::from_client(client) {
+        Ok(embeddings) => embeddings,
+        Err(err) => return ConnectionReport::failed(err, None, embeddings_hint),
+    };
+    let start = Instant::now();
+    let result = embeddings.embed_query(TEST_TEXT).await;
+    let latency_ms = elapsed_ms(start);
+
+    let scope = UsageScope {
+        embeddings_client_id: Some(client.id),
+        ..Default::default()
+    };
+    if let Err(err) = usage::record(db, embeddings.take_usage(), &scope).await {
+        log::error!(
+            "Failed to record the usage of embeddings client {}: {}",
+            client.id,
+            err
+        );
+    }
+
+    match result {
+        Ok(vector) => ConnectionReport {
+            diagnosis: Diagnosis::Ok,
+            latency_ms,
+            dimension: Some(vector.len() as i32),
+            model: Some(embeddings.model().to_string()),
+            metric: None,
+            message: None,
+            hint: None,
+        },
+        Err(err) => ConnectionReport::failed(err, latency_ms, embeddings_hint),
+    }
+}
+
+/// Test a vector db client by describing its index.
+pub async fn test_vector_db_client(client: &vector_db_client::Data) -> ConnectionReport {
+    let vector_db = match VectorDb::from_client(client) {
+        Ok(vector_db) => vector_db,
+        Err(err) => return ConnectionReport::failed(err, None, vector_db_hint),
+    };
+    let start = Instant::now();
+    let result = vector_db.describe_index().await;
+    let latency_ms = elapsed_ms(start);
+
+    match result {
+        Ok(description) => {
+            let diagnosis = if description.ready {
+                Diagnosis::Ok
+            } else {
+                Diagnosis::NotReady
+            };
+            ConnectionReport {
+                diagnosis,
+                latency_ms,
+                dimension: Some(description.dimension),
+                model: None,
+                metric: description.metric,
+                message: None,
+                hint: vector_db_hint(diagnosis).map(str::to_string),
+            }
+        }
+        Err(err) => ConnectionReport::failed(err, latency_ms, vector_db_hint),
+    }
+}
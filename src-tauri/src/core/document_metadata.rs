@@ -0,0 +1,594 @@
+use std::path::Path;
+
+use lopdf::Object;
+use once_cell::sync::Lazy;
+use prisma_client_rust::chrono::Local;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::core::validation::ValidationError;
+use crate::prisma::{document, document_metadata, PrismaClient};
+
+const DC_NS: &str = "http://purl.org/dc/elements/1.1/";
+const PDF_NS: &str = "http://ns.adobe.com/pdf/1.3/";
+/// Prefix of the namespaces of the versions of PRISM, which publishers record the doi and the
+/// journal by.
+const PRISM_NS_PREFIX: &str = "http://prismstandard.org/namespaces/basic/";
+/// Maximum number of characters of an abstract found on the first page.
+const MAX_ABSTRACT_LENGTH: usize = 3000;
+/// How many lines from the top of the first page are considered for the title.
+const TITLE_SEARCH_LINES: usize = 10;
+
+static DOI: Lazy<Regex> = Lazy::new(|| Regex::new(r"^10\.\d{4,9}/\S+$").unwrap());
+static ARXIV_ID: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(\d{4}\.\d{4,5}|[a-z\-]+(\.[A-Z]{2})?/\d{7})(v\d+)?$").unwrap());
+static AUTHORS_SEMICOLON_SEPARATOR: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s*;\s*").unwrap());
+static AUTHORS_SEPARATOR: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s*,\s*|\s+and\s+").unwrap());
+static KEYWORDS_SEPARATOR: Lazy<Regex> = Lazy::new(|| Regex::new(r"\s*[,;·•]\s*").unwrap());
+/// A doi anywhere in a text.
+static DOI_IN_TEXT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?i)\b(10\.\d{4,9}/[^\s"<>]+)"#).unwrap());
+/// An arXiv identifier anywhere in a text, capturing the year apart.
+static ARXIV_IN_TEXT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)arXiv:\s*(\d{2})(\d{2}\.\d{4,5})(v\d+)?").unwrap());
+/// A heading ending the abstract on the first page.
+static ABSTRACT_END: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)^(\d+\.?\s*|I\.\s*)?(introduction|keywords|index terms)\b").unwrap()
+});
+static ABSTRACT_START: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^abstract\b[\s.:—–-]*").unwrap());
+
+/// Bibliographic metadata of a document.
+#[derive(Serialize, Deserialize, Type, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub year: Option<i32>,
+    pub venue: Option<String>,
+    pub doi: Option<String>,
+    #[serde(rename = "arxivId")]
+    pub arxiv_id: Option<String>,
+    #[serde(rename = "abstract")]
+    pub abstract_text: Option<String>,
+    pub keywords: Vec<String>,
+}
+
+impl Metadata {
+    pub fn from_data(data: &document_metadata::Data) -> crate::Result<Self> {
+        Ok(Self {
+            title: data.title.clone(),
+            authors: serde_json::from_str(&data.authors)?,
+            year: data.year,
+            venue: data.venue.clone(),
+            doi: data.doi.clone(),
+            arxiv_id: data.arxiv_id.clone(),
+            abstract_text: data.abstract_text.clone(),
+            keywords: serde_json::from_str(&data.keywords)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The fields of `self`, with the missing ones taken from `other`.
+    pub fn or(self, other: Metadata) -> Metadata {
+        Metadata {
+            title: self.title.or(other.title),
+            authors: if self.authors.is_empty() {
+                other.authors
+            } else {
+                self.authors
+            },
+            year: self.year.or(other.year),
+            venue: self.venue.or(other.venue),
+            doi: self.doi.or(other.doi),
+            arxiv_id: self.arxiv_id.or(other.arxiv_id),
+            abstract_text: self.abstract_text.or(other.abstract_text),
+            keywords: if self.keywords.is_empty() {
+                other.keywords
+            } else {
+                self.keywords
+            },
+        }
+    }
+
+    /// Trim the fields, dropping the empty ones and the repeated authors and keywords.
+    pub fn normalized(self) -> Metadata {
+        let text = |value: Option<String>| {
+            value
+                .map(|value| collapse_whitespace(&value))
+                .filter(|value| !value.is_empty())
+        };
+        let list = |values: Vec<String>| {
+            let mut seen = vec![];
+            for value in values.iter().map(|value| collapse_whitespace(value)) {
+                if !value.is_empty() && !seen.contains(&value) {
+                    seen.push(value);
+                }
+            }
+            seen
+        };
+        Metadata {
+            title: text(self.title),
+            authors: list(self.authors),
+            year: self.year,
+            venue: text(self.venue),
+            doi: text(self.doi).map(|doi| doi.to_lowercase()),
+            arxiv_id: text(self.arxiv_id),
+            abstract_text: text(self.abstract_text),
+            keywords: list(self.keywords),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut errors = ValidationError::default();
+        if !self.year.map_or(true, is_valid_year) {
+            errors.add("year", "must be a year of four digits");
+        }
+        if !self.doi.as_deref().map_or(true, is_valid_doi) {
+            errors.add("doi", "must be in the form of 10.<prefix>/<suffix>");
+        }
+        if !self.arxiv_id.as_deref().map_or(true, is_valid_arxiv_id) {
+            errors.add("arxivId", "must be an arXiv identifier such as 1706.03762");
+        }
+        errors.into_result()
+    }
+
+    /// Drop the fields that do not validate, for metadata from elsewhere than the user.
+    pub fn without_invalid(self) -> Metadata {
+        Metadata {
+            year: self.year.filter(|year| is_valid_year(*year)),
+            doi: self.doi.filter(|doi| is_valid_doi(doi)),
+            arxiv_id: self.arxiv_id.filter(|arxiv_id| is_valid_arxiv_id(arxiv_id)),
+            ..self
+        }
+    }
+
+    /// A short label of the document in the author-year style, such as `Vaswani et al., 2017`.
+    pub fn short_label(&self) -> Option<String> {
+        let authors = match self.authors.as_slice() {
+            [] => None,
+            [one] => Some(family_name(one)),
+            [one, two] => Some(format!("{} and {}", family_name(one), family_name(two))),
+            [one, ..] => Some(format!("{} et al.", family_name(one))),
+        };
+        match (authors, self.year) {
+            (Some(authors), Some(year)) => Some(format!("{}, {}", authors, year)),
+            (Some(authors), None) => Some(authors),
+            (None, Some(year)) => self
+                .title
+                .as_ref()
+                .map(|title| format!("{}, {}", title, year)),
+            (None, None) => self.title.clone(),
+        }
+    }
+
+    /// A reference to the document as listed in a bibliography, if there is at least a title.
+    pub fn reference(&self) -> Option<String> {
+        let title = self.title.as_ref()?;
+        let mut reference = String::new();
+        if !self.authors.is_empty() {
+            reference.push_str(&self.authors.join(", "));
+            reference.push(' ');
+        }
+        if let Some(year) = self.year {
+            reference.push_str(&format!("({}). ", year));
+        } else if !reference.is_empty() {
+            reference.push_str(". ");
+        }
+        reference.push_str(title.trim_end_matches('.'));
+        reference.push('.');
+        if let Some(venue) = &self.venue {
+            reference.push_str(&format!(" {}.", venue));
+        }
+        if let Some(doi) = &self.doi {
+            reference.push_str(&format!(" https://doi.org/{}", doi));
+        } else if let Some(arxiv_id) = &self.arxiv_id {
+            reference.push_str(&format!(" arXiv:{}", arxiv_id));
+        }
+        Some(reference)
+    }
+}
+
+/// The family name of an author, written either as `First Last` or `Last, First`.
+fn family_name(author: &str) -> String {
+    match author.split_once(',') {
+        Some((last, _)) => last.trim().to_string(),
+        None => author
+            .split_whitespace()
+            .last()
+            .unwrap_or(author)
+            .to_string(),
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn is_valid_year(year: i32) -> bool {
+    (1000..=9999).contains(&year)
+}
+
+fn is_valid_doi(doi: &str) -> bool {
+    DOI.is_match(doi)
+}
+
+fn is_valid_arxiv_id(arxiv_id: &str) -> bool {
+    ARXIV_ID.is_match(arxiv_id)
+}
+
+pub async fn get(db: &PrismaClient, document_id: i32) -> crate::Result<Option<Metadata>> {
+    db.document_metadata()
+        .find_unique(document_metadata::document_id::equals(document_id))
+        .exec()
+        .await?
+        .map(|data| Metadata::from_data(&data))
+        .transpose()
+}
+
+/// Save the metadata of a document, replacing the one it has.
+pub async fn save(
+    db: &PrismaClient,
+    document_id: i32,
+    metadata: Metadata,
+) -> crate::Result<Metadata> {
+    let metadata = metadata.normalized();
+    metadata.validate()?;
+    let authors = serde_json::to_string(&metadata.authors)?;
+    let keywords = serde_json::to_string(&metadata.keywords)?;
+    let update_time = Local::now();
+    // the fields are not cloneable, so that they are made for both cases of the upsert
+    let fields = || {
+        vec![
+            document_metadata::title::set(metadata.title.clone()),
+            document_metadata::authors::set(authors.clone()),
+            document_metadata::year::set(metadata.year),
+            document_metadata::venue::set(metadata.venue.clone()),
+            document_metadata::doi::set(metadata.doi.clone()),
+            document_metadata::arxiv_id::set(metadata.arxiv_id.clone()),
+            document_metadata::abstract_text::set(metadata.abstract_text.clone()),
+            document_metadata::keywords::set(keywords.clone()),
+        ]
+    };
+    db.document_metadata()
+        .upsert(
+            document_metadata::document_id::equals(document_id),
+            (
+                document::id::equals(document_id),
+                update_time.into(),
+                fields(),
+            ),
+            fields()
+                .into_iter()
+                .chain([document_metadata::update_time::set(update_time.into())])
+                .collect(),
+        )
+        .exec()
+        .await?;
+    Ok(metadata)
+}
+
+pub async fn delete(db: &PrismaClient, document_id: i32) -> crate::Result<()> {
+    db.document_metadata()
+        .delete_many(vec![document_metadata::document_id::equals(document_id)])
+        .exec()
+        .await?;
+    Ok(())
+}
+
+/// Carry the metadata of a document over to another one, such as a new version of it, which keeps
+/// the fields of its own that the metadata carried over misses.
+pub async fn copy(
+    db: &PrismaClient,
+    from_document_id: i32,
+    to_document_id: i32,
+) -> crate::Result<()> {
+    if let Some(metadata) = get(db, from_document_id).await? {
+        let own = get(db, to_document_id).await?.unwrap_or_default();
+        save(db, to_document_id, metadata.or(own)).await?;
+    }
+    Ok(())
+}
+
+/// Extract the metadata of a new document from its file and save it, unless it has some already.
+///
+/// It is only a guess, so that extracting nothing or failing to, or to save it, is no error and
+/// is only logged.
+pub async fn extract_into(db: &PrismaClient, doc: &document::Data) {
+    if let Err(err) = try_extract_into(db, doc).await {
+        log::warn!(
+            "error while extracting metadata of document {}: {}",
+            doc.id,
+            err
+        );
+    }
+}
+
+async fn try_extract_into(db: &PrismaClient, doc: &document::Data) -> crate::Result<()> {
+    if get(db, doc.id).await?.is_some() {
+        return Ok(());
+    }
+    let filepath = doc.filepath.clone();
+    let filename = doc.filename.clone();
+    let metadata =
+        tokio::task::spawn_blocking(move || extract(Path::new(&filepath), &filename)).await??;
+    let metadata = metadata.without_invalid();
+    if !metadata.is_empty() {
+        save(db, doc.id, metadata).await?;
+    }
+    Ok(())
+}
+
+/// Extract the metadata of a stored document, which blocks until done.
+///
+/// Only pdfs have metadata, which is taken from the XMP metadata, then the info dictionary, and
+/// then guessed from the text of the first page, field by field.
+pub fn extract(filepath: &Path, filename: &str) -> crate::Result<Metadata> {
+    let is_pdf = Path::new(filename)
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("pdf"))
+        .unwrap_or(false);
+    if !is_pdf {
+        return Ok(Metadata::default());
+    }
+    let pdf = lopdf::Document::load(filepath)?;
+    let (info, creation_year) = info_metadata(&pdf).unwrap_or_default();
+    let metadata = xmp_metadata(&pdf)
+        .unwrap_or_default()
+        .or(info)
+        .or(first_page_metadata(&pdf).unwrap_or_default());
+    Ok(Metadata {
+        // the pdf may be made long after the document is published, so it is the last resort
+        year: metadata.year.or(creation_year),
+        ..metadata
+    }
+    .normalized())
+}
+
+/// Decode a text string of a pdf, which is either UTF-16BE with a byte order mark, or of the
+/// PDFDocEncoding, whose printable characters mostly agree with Latin-1.
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        let units = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>();
+        String::from_utf16_lossy(&units)
+    } else if let Some(utf8) = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+        String::from_utf8_lossy(utf8).to_string()
+    } else {
+        bytes.iter().map(|&byte| byte as char).collect()
+    }
+}
+
+/// Whether a title recorded in a pdf is likely the title of the document, rather than the name of
+/// the file it was made from.
+fn is_plausible_title(title: &str) -> bool {
+    let lower = title.to_lowercase();
+    !(lower.is_empty()
+        || lower == "untitled"
+        || lower.starts_with("microsoft word")
+        || [".doc", ".docx", ".pdf", ".tex", ".dvi", ".ps"]
+            .iter()
+            .any(|ext| lower.ends_with(ext)))
+}
+
+/// Split a list of authors separated by `;`, `,` or `and`.
+fn split_authors(authors: &str) -> Vec<String> {
+    let separator = if authors.contains(';') {
+        &AUTHORS_SEMICOLON_SEPARATOR
+    } else {
+        &AUTHORS_SEPARATOR
+    };
+    separator
+        .split(authors)
+        .map(str::to_string)
+        .filter(|author| !author.is_empty())
+        .collect()
+}
+
+fn split_keywords(keywords: &str) -> Vec<String> {
+    KEYWORDS_SEPARATOR
+        .split(keywords)
+        .map(str::to_string)
+        .filter(|keyword| !keyword.is_empty())
+        .collect()
+}
+
+/// The year of a date, either a pdf date such as `D:20170612...` or an ISO date.
+fn year_of_date(date: &str) -> Option<i32> {
+    let digits = date.trim().trim_start_matches("D:");
+    digits.get(..4)?.parse().ok()
+}
+
+/// The metadata in the info dictionary of a pdf, with the year it was made.
+fn info_metadata(pdf: &lopdf::Document) -> Option<(Metadata, Option<i32>)> {
+    let (_, info) = pdf.dereference(pdf.trailer.get(b"Info").ok()?).ok()?;
+    let info = info.as_dict().ok()?;
+    let text = |key: &[u8]| {
+        info.get(key)
+            .ok()
+            .and_then(|value| pdf.dereference(value).ok())
+            .and_then(|(_, value)| value.as_str().ok())
+            .map(decode_pdf_string)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let metadata = Metadata {
+        title: text(b"Title").filter(|title| is_plausible_title(title)),
+        authors: text(b"Author")
+            .map(|authors| split_authors(&authors))
+            .unwrap_or_default(),
+        keywords: text(b"Keywords")
+            .map(|keywords| split_keywords(&keywords))
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+    let creation_year = text(b"CreationDate").and_then(|date| year_of_date(&date));
+    Some((metadata, creation_year))
+}
+
+/// The metadata in the XMP metadata stream of a pdf.
+fn xmp_metadata(pdf: &lopdf::Document) -> Option<Metadata> {
+    let catalog = pdf.catalog().ok()?;
+    let (_, stream) = pdf.dereference(catalog.get(b"Metadata").ok()?).ok()?;
+    let stream = match stream {
+        Object::Stream(stream) => stream,
+        _ => return None,
+    };
+    let content = stream
+        .decompressed_content()
+        .unwrap_or_else(|_| stream.content.clone());
+    parse_xmp(&String::from_utf8_lossy(&content))
+}
+
+fn parse_xmp(xml: &str) -> Option<Metadata> {
+    let doc = roxmltree::Document::parse(xml.trim_matches(char::from(0))).ok()?;
+    // a property is either an attribute of a description, or an element whose value is its text
+    // or the items of a rdf container in it
+    let values = |matches_ns: &dyn Fn(&str) -> bool, name: &str| -> Vec<String> {
+        let mut values = vec![];
+        for node in doc.descendants().filter(|node| node.is_element()) {
+            for attr in node.attributes() {
+                if attr.name() == name && attr.namespace().map_or(false, matches_ns) {
+                    values.push(attr.value().to_string());
+                }
+            }
+            let tag = node.tag_name();
+            if tag.name() == name && tag.namespace().map_or(false, matches_ns) {
+                let items = node
+                    .descendants()
+                    .filter(|item| item.tag_name().name() == "li")
+                    .filter_map(|item| item.text())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                if items.is_empty() {
+                    values.extend(node.text().map(str::to_string));
+                } else {
+                    values.extend(items);
+                }
+            }
+        }
+        values
+            .into_iter()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .collect()
+    };
+    let dc = |ns: &str| ns == DC_NS;
+    let prism = |ns: &str| ns.starts_with(PRISM_NS_PREFIX);
+    let pdf = |ns: &str| ns == PDF_NS;
+    let first = |values: Vec<String>| values.into_iter().next();
+
+    let doi = first(values(&prism, "doi")).map(|doi| {
+        doi.trim_start_matches("https://doi.org/")
+            .trim_start_matches("doi:")
+            .to_string()
+    });
+    let mut keywords = values(&dc, "subject");
+    if keywords.is_empty() {
+        keywords = first(values(&pdf, "Keywords"))
+            .map(|keywords| split_keywords(&keywords))
+            .unwrap_or_default();
+    }
+    Some(Metadata {
+        title: first(values(&dc, "title")).filter(|title| is_plausible_title(title)),
+        authors: values(&dc, "creator"),
+        year: first(values(&prism, "coverDate"))
+            .or_else(|| first(values(&prism, "publicationDate")))
+            .and_then(|date| year_of_date(&date)),
+        venue: first(values(&prism, "publicationName")),
+        doi: doi.filter(|doi| DOI.is_match(doi)),
+        arxiv_id: None,
+        abstract_text: first(values(&dc, "description")),
+        keywords,
+    })
+}
+
+/// The metadata guessed from the text of the first page of a pdf.
+fn first_page_metadata(pdf: &lopdf::Document) -> Option<Metadata> {
+    let first = *pdf.get_pages().keys().next()?;
+    let text = pdf.extract_text(&[first]).ok()?;
+    Some(parse_first_page(&text))
+}
+
+/// Guess the metadata from the text of the first page of a paper.
+///
+/// The doi and the arXiv identifier are looked for anywhere, the title is the first line that
+/// reads like one, and the abstract is the text between the line starting with `Abstract` and the
+/// keywords or the introduction.
+fn parse_first_page(text: &str) -> Metadata {
+    let doi = DOI_IN_TEXT.captures(text).map(|captures| {
+        captures[1]
+            .trim_end_matches(|c| matches!(c, '.' | ',' | ';' | ')' | ']'))
+            .to_string()
+    });
+    let arxiv = ARXIV_IN_TEXT.captures(text);
+    let arxiv_id = arxiv
+        .as_ref()
+        .map(|captures| format!("{}{}", &captures[1], &captures[2]));
+    let year = arxiv
+        .as_ref()
+        .and_then(|captures| captures[1].parse::<i32>().ok())
+        .map(|yy| 2000 + yy);
+
+    let lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    let title = lines
+        .iter()
+        .take(TITLE_SEARCH_LINES)
+        .find(|line| {
+            line.split_whitespace().count() >= 3
+                && line.len() <= 300
+                && !line.contains('@')
+                && !line.to_lowercase().contains("arxiv")
+                && line.chars().any(char::is_alphabetic)
+        })
+        .map(|line| line.to_string());
+
+    let mut abstract_text = None;
+    let mut keywords = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        if abstract_text.is_none() && ABSTRACT_START.is_match(line) {
+            let mut parts = vec![ABSTRACT_START.replace(line, "").to_string()];
+            parts.extend(
+                lines[i + 1..]
+                    .iter()
+                    .take_while(|line| !ABSTRACT_END.is_match(line))
+                    .map(|line| line.to_string()),
+            );
+            let joined = collapse_whitespace(&parts.join(" "));
+            abstract_text = Some(match joined.char_indices().nth(MAX_ABSTRACT_LENGTH) {
+                Some((end, _)) => joined[..end].to_string(),
+                None => joined,
+            });
+        }
+        let lower = line.to_lowercase();
+        if keywords.is_empty()
+            && (lower.starts_with("keywords") || lower.starts_with("index terms"))
+        {
+            let rest = line
+                .splitn(2, |c: char| c == ':' || c == '—' || c == '-')
+                .nth(1)
+                .unwrap_or("");
+            keywords = split_keywords(rest);
+        }
+    }
+
+    Metadata {
+        title,
+        authors: vec![],
+        year,
+        venue: None,
+        doi: doi.filter(|doi| DOI.is_match(doi)),
+        arxiv_id,
+        abstract_text,
+        keywords,
+    }
+}
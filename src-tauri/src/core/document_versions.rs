@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use prisma_client_rust::chrono::Local;
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use specta::Type;
+
+use crate::core::document_metadata;
+use crate::core::result::Error;
+use crate::prisma::{
+    collection_index, collection_index_version_pin, collections_on_documents, document,
+    document_version, PrismaClient,
+};
+
+/// Lines of context around the changes of a diff.
+const DIFF_CONTEXT_LINES: usize = 3;
+
+document_version::include!(document_version_with_document { document });
+
+#[derive(Serialize, Type, Debug)]
+pub struct DocumentDiff {
+    /// The changes in the unified diff format.
+    pub unified: String,
+    pub insertions: i32,
+    pub deletions: i32,
+}
+
+/// The version of a document, which is recorded as the first version of itself if it has none.
+pub async fn version_of(
+    db: &PrismaClient,
+    document_id: i32,
+) -> crate::Result<document_version::Data> {
+    if let Some(version) = db
+        .document_version()
+        .find_unique(document_version::document_id::equals(document_id))
+        .exec()
+        .await?
+    {
+        return Ok(version);
+    }
+    let doc = db
+        .document()
+        .find_unique(document::id::equals(document_id))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("Document {} not found", document_id)))?;
+    Ok(db
+        .document_version()
+        .create(
+            document::id::equals(doc.id),
+            doc.id,
+            1,
+            doc.update_time,
+            vec![],
+        )
+        .exec()
+        .await?)
+}
+
+/// The versions of the document of a version, the latest first.
+pub async fn list(
+    db: &PrismaClient,
+    document_id: i32,
+) -> crate::Result<Vec<document_version_with_document::Data>> {
+    let version = version_of(db, document_id).await?;
+    Ok(db
+        .document_version()
+        .find_many(vec![document_version::lineage_id::equals(
+            version.lineage_id,
+        )])
+        .order_by(document_version::no::order(
+            prisma_client_rust::Direction::Desc,
+        ))
+        .include(document_version_with_document::include())
+        .exec()
+        .await?)
+}
+
+/// Record `document` as the latest version of the document of a version, which replaces the
+/// previous latest version in its collections.
+///
+/// The collection indexes following the latest version drop the previous one and index the new
+/// one once synced, while the ones pinning a version keep it. The metadata of the previous version
+/// is carried over.
+pub async fn add(
+    db: &PrismaClient,
+    document_id: i32,
+    document: &document::Data,
+    note: Option<String>,
+) -> crate::Result<document_version::Data> {
+    let lineage_id = version_of(db, document_id).await?.lineage_id;
+    let latest = db
+        .document_version()
+        .find_first(vec![document_version::lineage_id::equals(lineage_id)])
+        .order_by(document_version::no::order(
+            prisma_client_rust::Direction::Desc,
+        ))
+        .exec()
+        .await?
+        .ok_or_else(|| Error::msg(format!("No version of document {}", document_id)))?;
+    let version = db
+        .document_version()
+        .create(
+            document::id::equals(document.id),
+            lineage_id,
+            latest.no + 1,
+            Local::now().into(),
+            vec![document_version::note::set(note.unwrap_or_default())],
+        )
+        .exec()
+        .await?;
+    db.collections_on_documents()
+        .update_many(
+            vec![collections_on_documents::document_id::equals(
+                latest.document_id,
+            )],
+            vec![collections_on_documents::document_id::set(document.id)],
+        )
+        .exec()
+        .await?;
+    document_metadata::copy(db, latest.document_id, document.id).await?;
+    Ok(version)
+}
+
+/// Pin a version of a document for a collection index, which indexes it instead of the latest
+/// version from then on.
+pub async fn pin(
+    db: &PrismaClient,
+    collection_index_id: String,
+    document_id: i32,
+) -> crate::Result<collection_index_version_pin::Data> {
+    let version = version_of(db, document_id).await?;
+    Ok(db
+        .collection_index_version_pin()
+        .upsert(
+            collection_index_version_pin::index_id_lineage_id(
+                collection_index_id.clone(),
+                version.lineage_id,
+            ),
+            (
+                collection_index::id::equals(collection_index_id),
+                document::id::equals(document_id),
+                version.lineage_id,
+                vec![],
+            ),
+            vec![collection_index_version_pin::document::connect(
+                document::id::equals(document_id),
+            )],
+        )
+        .exec()
+        .await?)
+}
+
+/// Let a collection index follow the latest version of a document again.
+pub async fn unpin(
+    db: &PrismaClient,
+    collection_index_id: String,
+    document_id: i32,
+) -> crate::Result<()> {
+    let version = version_of(db, document_id).await?;
+    db.collection_index_version_pin()
+        .delete_many(vec![
+            collection_index_version_pin::index_id::equals(collection_index_id),
+            collection_index_version_pin::lineage_id::equals(version.lineage_id),
+        ])
+        .exec()
+        .await?;
+    Ok(())
+}
+
+/// The documents a collection index is to index: the documents of its collection, with the
+/// versions pinned by the index instead of the ones in the collection.
+pub async fn documents_of_index(
+    db: &PrismaClient,
+    collection_index_id: String,
+) -> crate::Result<Vec<document::Data>> {
+    let index = db
+        .collection_index()
+        .find_unique(collection_index::id::equals(collection_index_id.clone()))
+        .exec()
+        .await?
+        .ok_or_else(|| {
+            Error::msg(format!(
+                "Collection index {} not found",
+                collection_index_id
+            ))
+        })?;
+    let document_ids = db
+        .collections_on_documents()
+        .find_many(vec![collections_on_documents::collection_id::equals(
+            index.collection_id,
+        )])
+        .exec()
+        .await?
+        .into_iter()
+        .map(|rel| rel.document_id)
+        .collect::<Vec<_>>();
+    let pins = db
+        .collection_index_version_pin()
+        .find_many(vec![collection_index_version_pin::index_id::equals(
+            collection_index_id,
+        )])
+        .exec()
+        .await?
+        .into_iter()
+        .map(|pin| (pin.lineage_id, pin.document_id))
+        .collect::<HashMap<_, _>>();
+    let lineages = db
+        .document_version()
+        .find_many(vec![document_version::document_id::in_vec(
+            document_ids.clone(),
+        )])
+        .exec()
+        .await?
+        .into_iter()
+        .map(|version| (version.document_id, version.lineage_id))
+        .collect::<HashMap<_, _>>();
+
+    let mut indexed_ids = document_ids
+        .into_iter()
+        .map(|id| {
+            // a document with no version is the first version of itself
+            let lineage_id = lineages.get(&id).copied().unwrap_or(id);
+            pins.get(&lineage_id).copied().unwrap_or(id)
+        })
+        .collect::<Vec<_>>();
+    indexed_ids.sort();
+    indexed_ids.dedup();
+    Ok(db
+        .document()
+        .find_many(vec![document::id::in_vec(indexed_ids)])
+        .exec()
+        .await?)
+}
+
+/// Diff two texts line by line.
+pub fn diff(old_name: &str, old: &str, new_name: &str, new: &str) -> DocumentDiff {
+    let diff = TextDiff::from_lines(old, new);
+    let mut insertions = 0;
+    let mut deletions = 0;
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => insertions += 1,
+            ChangeTag::Delete => deletions += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    DocumentDiff {
+        unified: diff
+            .unified_diff()
+            .context_radius(DIFF_CONTEXT_LINES)
+            .header(old_name, new_name)
+            .to_string(),
+        insertions,
+        deletions,
+    }
+}
@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::client_types::{ClientPayload, EmbeddingsClientInfo, EmbeddingsConfigMeta};
+use crate::core::http::ensure_success;
+use crate::core::result::Error;
+use crate::core::secrets;
+use crate::core::tokenizer::count_tokens;
+use crate::core::usage::{Price, Usage, UsageKind, UsageLog};
+use crate::prisma::{embeddings_client, embeddings_config};
+
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const OPENAI_EMBEDDINGS_MODEL: &str = "text-embedding-ada-002";
+
+/// An embeddings client built from an `EmbeddingsClient` and an `EmbeddingsConfig`.
+///
+/// The tokens of every call are logged until taken by `take_usage` for recording.
+pub enum Embeddings {
+    OpenAi {
+        base_url: String,
+        api_key: String,
+        model: String,
+        price: Price,
+        http: reqwest::Client,
+        usage: UsageLog,
+    },
+}
+
+#[derive(Serialize)]
+struct OpenAiEmbeddingsRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingsResponse {
+    data: Vec<OpenAiEmbedding>,
+    usage: Option<OpenAiEmbeddingsUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingsUsage {
+    prompt_tokens: i32,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl Embeddings {
+    pub fn from_data(
+        client: &embeddings_client::Data,
+        config: &embeddings_config::Data,
+    ) -> crate::Result<Self> {
+        if client.r#type != config.client_type {
+            return Err(Error::msg(format!(
+                "Embeddings client type {} does not match config type {}",
+                client.r#type, config.client_type
+            )));
+        }
+        let info = EmbeddingsClientInfo::from_stored(
+            &client.r#type,
+            &secrets::reveal_info(&client.info)?,
+        )?;
+        let meta = EmbeddingsConfigMeta::from_stored(&config.client_type, &config.meta)?;
+        Ok(Self::from_payloads(info, meta))
+    }
+
+    /// Build a client by the default config of its type, such as the default model.
+    pub fn from_client(client: &embeddings_client::Data) -> crate::Result<Self> {
+        let info = EmbeddingsClientInfo::from_stored(
+            &client.r#type,
+            &secrets::reveal_info(&client.info)?,
+        )?;
+        let meta = match info {
+            EmbeddingsClientInfo::OpenAi(_) => EmbeddingsConfigMeta::OpenAi(Default::default()),
+        };
+        Ok(Self::from_payloads(info, meta))
+    }
+
+    fn from_payloads(info: EmbeddingsClientInfo, meta: EmbeddingsConfigMeta) -> Self {
+        match (info, meta) {
+            (EmbeddingsClientInfo::OpenAi(info), EmbeddingsConfigMeta::OpenAi(meta)) => {
+                let model = meta
+                    .model
+                    .unwrap_or_else(|| OPENAI_EMBEDDINGS_MODEL.to_string());
+                Embeddings::OpenAi {
+                    base_url: info
+                        .base_url
+                        .unwrap_or_else(|| OPENAI_BASE_URL.to_string())
+                        .trim_end_matches('/')
+                        .to_string(),
+                    api_key: info.api_key,
+                    price: Price::of_model(meta.prices.as_ref(), &model),
+                    model,
+                    http: reqwest::Client::new(),
+                    usage: UsageLog::default(),
+                }
+            }
+        }
+    }
+
+    /// The model embedded with.
+    pub fn model(&self) -> &str {
+        match self {
+            Embeddings::OpenAi { model, .. } => model,
+        }
+    }
+
+    /// Take the usages of the calls made so far.
+    pub fn take_usage(&self) -> Vec<Usage> {
+        match self {
+            Embeddings::OpenAi { usage, .. } => usage.take(),
+        }
+    }
+
+    /// Embed a single query text.
+    pub async fn embed_query(&self, text: &str) -> crate::Result<Vec<f32>> {
+        self.embed_documents(&[text.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| Error::msg("Embeddings service returned no vector"))
+    }
+
+    /// Embed a batch of texts, returning one vector per text in the same order.
+    pub async fn embed_documents(&self, texts: &[String]) -> crate::Result<Vec<Vec<f32>>> {
+        match self {
+            Embeddings::OpenAi {
+                base_url,
+                api_key,
+                model,
+                price,
+                http,
+                usage,
+            } => {
+                let response = http
+                    .post(format!("{}/embeddings", base_url))
+                    .bearer_auth(api_key)
+                    .json(&OpenAiEmbeddingsRequest {
+                        model,
+                        input: texts,
+                    })
+                    .send()
+                    .await?;
+                let response = ensure_success(response)
+                    .await?
+                    .json::<OpenAiEmbeddingsResponse>()
+                    .await?;
+                let prompt_tokens = match response.usage {
+                    Some(usage) => usage.prompt_tokens,
+                    None => texts.iter().map(|text| count_tokens(text) as i32).sum(),
+                };
+                usage.push(Usage::new(
+                    UsageKind::Embeddings,
+                    model,
+                    price,
+                    prompt_tokens,
+                    0,
+                ));
+                let mut data = response.data;
+                data.sort_by_key(|embedding| embedding.index);
+                Ok(data
+                    .into_iter()
+                    .map(|embedding| embedding.embedding)
+                    .collect())
+            }
+        }
+    }
+}
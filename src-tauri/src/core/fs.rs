@@ -1,22 +1,130 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
 use tokio::io::AsyncReadExt;
 
-pub async fn hash_file_in_md5<P: AsRef<std::path::Path>>(file_path: P) -> std::io::Result<String> {
-    let mut context = md5::Context::new();
+use crate::core::result::Error;
+
+/// Algorithm of the content hashes identifying documents, chunks and their cached embeddings.
+///
+/// The hashes are stored along with the name of their algorithm. Rows from before there is a
+/// choice are md5 ones, and stay valid: the chunks of a document are hashed by the algorithm of
+/// the document, so that they keep matching the embeddings cached for them.
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashAlgo {
+    #[serde(rename = "md5")]
+    Md5,
+    #[default]
+    #[serde(rename = "blake3")]
+    Blake3,
+}
+
+impl HashAlgo {
+    /// All the algorithms, which stored hashes may be of.
+    pub const ALL: [HashAlgo; 2] = [HashAlgo::Md5, HashAlgo::Blake3];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgo::Md5 => "md5",
+            HashAlgo::Blake3 => "blake3",
+        }
+    }
+
+    pub fn of_str(name: &str) -> crate::Result<Self> {
+        match name {
+            "md5" => Ok(HashAlgo::Md5),
+            "blake3" => Ok(HashAlgo::Blake3),
+            _ => Err(Error::msg(format!("Unknown hash algorithm {}", name))),
+        }
+    }
+
+    /// The algorithm of a hex encoded hash, told by its length.
+    pub fn of_hash(hash: &str) -> crate::Result<Self> {
+        match hash.len() {
+            32 => Ok(HashAlgo::Md5),
+            64 => Ok(HashAlgo::Blake3),
+            _ => Err(Error::msg(format!("Unknown hash {}", hash))),
+        }
+    }
+
+    /// Whether `hash` is a hex encoded hash of this algorithm, which is safe to use as a filename.
+    pub fn is_hash(&self, hash: &str) -> bool {
+        let len = match self {
+            HashAlgo::Md5 => 32,
+            HashAlgo::Blake3 => 64,
+        };
+        hash.len() == len && hash.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+
+    fn hasher(&self) -> Hasher {
+        match self {
+            HashAlgo::Md5 => Hasher::Md5(md5::Context::new()),
+            HashAlgo::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+}
+
+enum Hasher {
+    Md5(md5::Context),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(context) => context.consume(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Hasher::Md5(context) => format!("{:x}", context.compute()),
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+pub async fn hash_file<P: AsRef<std::path::Path>>(
+    algo: HashAlgo,
+    file_path: P,
+) -> std::io::Result<String> {
+    let mut hasher = algo.hasher();
     let mut file = tokio::fs::File::open(file_path).await?;
     let mut buffer = vec![0u8; 4 * 1024 * 1024];
     loop {
         let read = file.read(&mut buffer).await?;
         if read > 0 {
-            context.consume(&buffer[..read]);
+            hasher.update(&buffer[..read]);
+        } else {
+            break;
+        }
+    }
+    Ok(hasher.finalize())
+}
+
+/// Hash a file like `hash_file`, blocking until done.
+pub fn hash_file_blocking<P: AsRef<std::path::Path>>(
+    algo: HashAlgo,
+    file_path: P,
+) -> std::io::Result<String> {
+    let mut hasher = algo.hasher();
+    let mut file = std::fs::File::open(file_path)?;
+    let mut buffer = vec![0u8; 4 * 1024 * 1024];
+    loop {
+        let read = std::io::Read::read(&mut file, &mut buffer)?;
+        if read > 0 {
+            hasher.update(&buffer[..read]);
         } else {
             break;
         }
     }
-    let digest = context.compute();
-    Ok(format!("{:x}", digest))
+    Ok(hasher.finalize())
 }
 
-pub async fn hash_in_md5<T: AsRef<[u8]>>(data: T) -> std::io::Result<String> {
-    let digest = md5::compute(data);
-    Ok(format!("{:x}", digest))
+pub fn hash_bytes<T: AsRef<[u8]>>(algo: HashAlgo, data: T) -> String {
+    let mut hasher = algo.hasher();
+    hasher.update(data.as_ref());
+    hasher.finalize()
 }
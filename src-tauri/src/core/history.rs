@@ -0,0 +1,105 @@
+use serde::Deserialize;
+
+/// A question with the answer chosen by the user.
+pub struct QaPair {
+    pub question: String,
+    pub answer: String,
+}
+
+/// The conversation preceding a question.
+#[derive(Default)]
+pub struct History {
+    /// A summary of the earlier part of the conversation that is left out of `pairs`.
+    pub summary: String,
+    /// The latest questions with their answers in chronological order.
+    pub pairs: Vec<QaPair>,
+}
+
+impl History {
+    pub fn is_empty(&self) -> bool {
+        self.summary.is_empty() && self.pairs.is_empty()
+    }
+}
+
+#[derive(Deserialize)]
+struct UiChatDialogue {
+    question: String,
+    #[serde(default)]
+    answers: Vec<String>,
+    #[serde(default)]
+    chosen: i64,
+    #[serde(default)]
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct UiChatMessage {
+    message: UiChatMessageContent,
+    #[serde(default)]
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct UiChatMessageContent {
+    #[serde(default)]
+    text: String,
+}
+
+/// Pair up the user messages with the assistant messages following them.
+///
+/// `messages` are `(role, content)` in chronological order. A question without answer is paired
+/// with an empty answer.
+pub fn pair_messages<'a, I>(messages: I) -> Vec<QaPair>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    let mut pairs: Vec<QaPair> = vec![];
+    for (role, content) in messages {
+        match role {
+            "user" => pairs.push(QaPair {
+                question: content.to_string(),
+                answer: String::new(),
+            }),
+            "assistant" => {
+                if let Some(pair) = pairs.last_mut().filter(|pair| pair.answer.is_empty()) {
+                    pair.answer = content.to_string();
+                }
+            }
+            _ => {}
+        }
+    }
+    pairs
+}
+
+/// Parse the `history` of a session into question-answer pairs.
+///
+/// The history is dumped by the frontend as a json array of dialogues, each of which is itself a
+/// json string holding a question message and the candidate answers with the chosen one. Every
+/// message is again a json string. Dialogues that cannot be parsed are skipped, and so are the
+/// ones failed to answer, as the frontend leaves them out of the history too.
+pub fn parse_history(history: &str) -> Vec<QaPair> {
+    let dialogues: Vec<String> = serde_json::from_str(history).unwrap_or_default();
+    dialogues
+        .iter()
+        .filter_map(|dialogue| serde_json::from_str::<UiChatDialogue>(dialogue).ok())
+        .filter(|dialogue| dialogue.error.is_none())
+        .filter_map(|dialogue| {
+            let question = parse_message(&dialogue.question)?;
+            let answer = usize::try_from(dialogue.chosen)
+                .ok()
+                .and_then(|chosen| dialogue.answers.get(chosen))
+                .and_then(|answer| parse_message(answer));
+            match answer {
+                Some(answer) if answer.error.is_some() => None,
+                answer => Some(QaPair {
+                    question: question.message.text,
+                    answer: answer.map(|answer| answer.message.text).unwrap_or_default(),
+                }),
+            }
+        })
+        .collect()
+}
+
+fn parse_message(message: &str) -> Option<UiChatMessage> {
+    serde_json::from_str::<UiChatMessage>(message).ok()
+}
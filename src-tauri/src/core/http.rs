@@ -0,0 +1,33 @@
+/// A non-success HTTP response, carrying the response body.
+#[derive(Debug)]
+pub struct HttpStatusError {
+    pub url: reqwest::Url,
+    pub status: reqwest::StatusCode,
+    pub body: String,
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} responded with {}: {}",
+            self.url, self.status, self.body
+        )
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// Turn a non-success HTTP response into an error carrying the response body.
+///
+/// Remote APIs usually explain what went wrong in the body (e.g. an invalid api key or an unknown
+/// model), which `reqwest::Response::error_for_status` would drop.
+pub async fn ensure_success(response: reqwest::Response) -> crate::Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let url = response.url().clone();
+    let body = response.text().await.unwrap_or_default();
+    Err(HttpStatusError { url, status, body }.into())
+}
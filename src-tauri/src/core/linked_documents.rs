@@ -0,0 +1,160 @@
+use std::path::{Path, PathBuf};
+
+use prisma_client_rust::chrono::Local;
+use serde::Serialize;
+use specta::Type;
+
+use crate::core::fs::{hash_bytes, hash_file, HashAlgo};
+use crate::core::result::Error;
+use crate::core::{document_metadata, document_versions};
+use crate::prisma::{collection_index_on_document, document, PrismaClient};
+
+/// A linked document whose source file has changed.
+#[derive(Serialize, Type, Clone, Debug)]
+pub struct DocumentChange {
+    /// The document of the previous version, which is left out of the collections and stays in
+    /// the indexes until they are synced.
+    #[serde(rename = "previousId")]
+    pub previous_id: i32,
+    /// The document of the new version, which replaces the previous one in its collections.
+    pub document: document::Data,
+    /// The collection indexes of the previous document, which have to be synced to drop its
+    /// vectors and index the new one.
+    #[serde(rename = "indexIds")]
+    pub index_ids: Vec<String>,
+}
+
+/// Copy a file into the upload folder as a partial file, so that its content is hashed as stored
+/// even if the file is being written meanwhile.
+async fn copy_partial(source: &Path, upload_dir: &Path) -> std::io::Result<PathBuf> {
+    let name = hash_bytes(HashAlgo::default(), source.to_string_lossy().as_bytes());
+    let partial_path = upload_dir.join(format!("{}.part", name));
+    tokio::fs::copy(source, &partial_path).await?;
+    Ok(partial_path)
+}
+
+/// Move a partial file to where the content of `hash` is stored, unless it is stored already.
+async fn store_partial(
+    partial_path: &Path,
+    hash: &str,
+    upload_dir: &Path,
+) -> std::io::Result<PathBuf> {
+    let target_path = upload_dir.join(hash);
+    if target_path.exists() {
+        tokio::fs::remove_file(partial_path).await?;
+    } else {
+        tokio::fs::rename(partial_path, &target_path).await?;
+    }
+    Ok(target_path)
+}
+
+/// Create a document linked to its source file, which is copied into `upload_dir` like the other
+/// documents and watched for changes.
+pub async fn create(
+    db: &PrismaClient,
+    upload_dir: &Path,
+    filename: String,
+    source_path: &Path,
+) -> crate::Result<document::Data> {
+    // the path is compared to the ones reported by the watcher
+    let source_path = tokio::fs::canonicalize(source_path).await?;
+    let hash_algo = HashAlgo::default();
+    let partial_path = copy_partial(&source_path, upload_dir).await?;
+    let hash = hash_file(hash_algo, &partial_path).await?;
+    let target_path = store_partial(&partial_path, &hash, upload_dir).await?;
+    let doc = db
+        .document()
+        .create(
+            filename,
+            target_path.to_string_lossy().to_string(),
+            hash,
+            Local::now().into(),
+            vec![
+                document::hash_algo::set(hash_algo.as_str().to_string()),
+                document::source_path::set(Some(source_path.to_string_lossy().to_string())),
+            ],
+        )
+        .exec()
+        .await?;
+    document_metadata::extract_into(db, &doc).await;
+    Ok(doc)
+}
+
+/// Check the source file of a linked document, and record its new content if it has changed.
+///
+/// The new content is recorded as the latest version of the document, which takes the place of
+/// the previous one in its collections and is linked to the source file instead. The previous
+/// version keeps its chunks and stays in the collection indexes, so that syncing them drops its
+/// vectors, and indexes the new version split anew. A source file gone missing is no change.
+pub async fn sync(
+    db: &PrismaClient,
+    upload_dir: &Path,
+    doc: document::Data,
+) -> crate::Result<Option<DocumentChange>> {
+    let source_path = doc
+        .source_path
+        .clone()
+        .ok_or_else(|| Error::msg(format!("Document {} is not linked", doc.id)))?;
+    if !Path::new(&source_path).exists() {
+        return Ok(None);
+    }
+
+    let partial_path = copy_partial(Path::new(&source_path), upload_dir).await?;
+    let previous_algo = HashAlgo::of_str(&doc.hash_algo)?;
+    let previous_hash = hash_file(previous_algo, &partial_path).await?;
+    if previous_hash == doc.md_5_hash {
+        tokio::fs::remove_file(&partial_path).await?;
+        return Ok(None);
+    }
+    let hash_algo = HashAlgo::default();
+    let hash = if hash_algo == previous_algo {
+        previous_hash
+    } else {
+        hash_file(hash_algo, &partial_path).await?
+    };
+    let target_path = store_partial(&partial_path, &hash, upload_dir).await?;
+
+    let index_ids = db
+        .collection_index_on_document()
+        .find_many(vec![collection_index_on_document::document_id::equals(
+            doc.id,
+        )])
+        .exec()
+        .await?
+        .into_iter()
+        .map(|indexed| indexed.index_id)
+        .collect();
+    let document = db
+        ._transaction()
+        .run(|tx| async move {
+            let document = tx
+                .document()
+                .create(
+                    doc.filename,
+                    target_path.to_string_lossy().to_string(),
+                    hash,
+                    Local::now().into(),
+                    vec![
+                        document::hash_algo::set(hash_algo.as_str().to_string()),
+                        document::source_path::set(Some(source_path)),
+                    ],
+                )
+                .exec()
+                .await?;
+            tx.document()
+                .update(
+                    document::id::equals(doc.id),
+                    vec![document::source_path::set(None)],
+                )
+                .exec()
+                .await?;
+            document_versions::add(&tx, doc.id, &document, None).await?;
+            crate::Result::Ok(document)
+        })
+        .await?;
+    Ok(Some(DocumentChange {
+        previous_id: doc.id,
+        document,
+        index_ids,
+    }))
+}
@@ -0,0 +1,395 @@
+use prisma_client_rust::raw;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::core::session_search;
+use crate::prisma::PrismaClient;
+
+/// Maximum number of problems reported by the integrity check.
+const MAX_INTEGRITY_ERRORS: i32 = 100;
+
+/// Rows whose references are missing, found by a condition over the table aliased as `t`.
+struct OrphanRule {
+    table: &'static str,
+    description: &'static str,
+    condition: &'static str,
+}
+
+/// The rules in the order of repairing, the referenced rows before the referencing ones, so that
+/// the rows cascaded by a repair are not counted again.
+const ORPHAN_RULES: [OrphanRule; 13] = [
+    OrphanRule {
+        table: "IndexProfile",
+        description: "Index profiles referring to missing splittings, clients or configs",
+        condition: "NOT EXISTS (SELECT 1 FROM \"Splitting\" r WHERE r.\"id\" = t.\"splittingId\")
+    OR NOT EXISTS (SELECT 1 FROM \"EmbeddingsClient\" r WHERE r.\"id\" = t.\"embeddingsClientId\")
+    OR NOT EXISTS (SELECT 1 FROM \"EmbeddingsConfig\" r WHERE r.\"id\" = t.\"embeddingsConfigId\")
+    OR NOT EXISTS (SELECT 1 FROM \"VectorDbClient\" r WHERE r.\"id\" = t.\"vectorDbClientId\")
+    OR NOT EXISTS (SELECT 1 FROM \"VectorDbConfig\" r WHERE r.\"id\" = t.\"vectorDbConfigId\")",
+    },
+    OrphanRule {
+        table: "CollectionIndex",
+        description: "Collection indexes of missing collections or index profiles",
+        condition: "NOT EXISTS (SELECT 1 FROM \"Collection\" r WHERE r.\"id\" = t.\"collectionId\")
+    OR NOT EXISTS (SELECT 1 FROM \"IndexProfile\" r WHERE r.\"id\" = t.\"indexId\")",
+    },
+    OrphanRule {
+        table: "CollectionIndexOnDocument",
+        description: "Indexed documents of missing collection indexes or documents",
+        condition: "NOT EXISTS (SELECT 1 FROM \"CollectionIndex\" r WHERE r.\"id\" = t.\"indexId\")
+    OR NOT EXISTS (SELECT 1 FROM \"Document\" r WHERE r.\"id\" = t.\"documentId\")",
+    },
+    OrphanRule {
+        table: "CollectionIndexVersionPin",
+        description: "Pinned versions of missing collection indexes or documents",
+        condition: "NOT EXISTS (SELECT 1 FROM \"CollectionIndex\" r WHERE r.\"id\" = t.\"indexId\")
+    OR NOT EXISTS (SELECT 1 FROM \"Document\" r WHERE r.\"id\" = t.\"documentId\")",
+    },
+    OrphanRule {
+        table: "DocumentVersion",
+        description: "Versions of missing documents",
+        condition: "NOT EXISTS (SELECT 1 FROM \"Document\" r WHERE r.\"id\" = t.\"documentId\")",
+    },
+    OrphanRule {
+        table: "CollectionsOnDocuments",
+        description: "Documents of missing collections, or missing documents of collections",
+        condition: "NOT EXISTS (SELECT 1 FROM \"Collection\" r WHERE r.\"id\" = t.\"collectionId\")
+    OR NOT EXISTS (SELECT 1 FROM \"Document\" r WHERE r.\"id\" = t.\"documentId\")",
+    },
+    OrphanRule {
+        table: "SplittingsOnDocuments",
+        description: "Splittings of missing documents, or missing splittings of documents",
+        condition: "NOT EXISTS (SELECT 1 FROM \"Splitting\" r WHERE r.\"id\" = t.\"splittingId\")
+    OR NOT EXISTS (SELECT 1 FROM \"Document\" r WHERE r.\"id\" = t.\"documentId\")",
+    },
+    OrphanRule {
+        table: "DocumentChunk",
+        description: "Chunks of missing documents",
+        condition: "NOT EXISTS (SELECT 1 FROM \"Document\" r WHERE r.\"id\" = t.\"documentId\")",
+    },
+    OrphanRule {
+        table: "DocumentChunk",
+        description: "Chunks of missing splittings",
+        condition: "NOT EXISTS (SELECT 1 FROM \"Splitting\" r WHERE r.\"id\" = t.\"splittingId\")",
+    },
+    OrphanRule {
+        table: "EmbeddingVectorsOnDocumentChunks",
+        description: "Embedding vectors of missing embeddings configs",
+        condition: "NOT EXISTS (
+    SELECT 1 FROM \"EmbeddingsConfig\" r WHERE r.\"id\" = t.\"embeddingsConfigId\"
+)",
+    },
+    OrphanRule {
+        table: "Session",
+        description: "Sessions of missing collection indexes",
+        condition:
+            "NOT EXISTS (SELECT 1 FROM \"CollectionIndex\" r WHERE r.\"id\" = t.\"indexId\")",
+    },
+    OrphanRule {
+        table: "SessionMessage",
+        description: "Messages of missing sessions",
+        condition: "NOT EXISTS (SELECT 1 FROM \"Session\" r WHERE r.\"id\" = t.\"sessionId\")",
+    },
+    OrphanRule {
+        table: "SessionMessage",
+        description: "Messages replying to missing messages",
+        condition: "t.\"parentId\" IS NOT NULL
+    AND NOT EXISTS (SELECT 1 FROM \"SessionMessage\" r WHERE r.\"id\" = t.\"parentId\")",
+    },
+];
+
+/// Embedding vectors not of any chunk, which are kept as a cache for the chunks to come, such as
+/// the ones of a document deleted and added again.
+///
+/// `NOT IN` lets sqlite build a temporary index of the hashes, since the chunks are not indexed by
+/// them.
+const UNREFERENCED_VECTORS_CONDITION: &str =
+    "t.\"md5Hash\" NOT IN (SELECT \"md5Hash\" FROM \"DocumentChunk\")";
+
+impl OrphanRule {
+    fn count_sql(&self) -> String {
+        count_sql(self.table, self.condition)
+    }
+
+    fn delete_sql(&self) -> String {
+        delete_sql(self.table, self.condition)
+    }
+}
+
+fn count_sql(table: &str, condition: &str) -> String {
+    format!(
+        "SELECT COUNT(*) AS \"count\" FROM \"{}\" t WHERE {}",
+        table, condition
+    )
+}
+
+fn delete_sql(table: &str, condition: &str) -> String {
+    format!(
+        "DELETE FROM \"{}\" WHERE rowid IN (SELECT t.rowid FROM \"{}\" t WHERE {})",
+        table, table, condition
+    )
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct OrphanCount {
+    pub table: String,
+    pub description: String,
+    pub count: i32,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct TableSize {
+    pub name: String,
+    pub rows: i32,
+    /// Bytes of the pages of the table and its indexes, if sqlite can tell.
+    pub bytes: Option<f64>,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct VectorTotal {
+    #[serde(rename = "embeddingsConfigId")]
+    pub embeddings_config_id: i32,
+    pub count: i32,
+    pub bytes: f64,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct DatabaseReport {
+    /// Whether the integrity check passes and no orphan rows are found.
+    pub healthy: bool,
+    /// Problems found by the integrity check, or just `ok`.
+    pub integrity: Vec<String>,
+    pub orphans: Vec<OrphanCount>,
+    pub tables: Vec<TableSize>,
+    /// Cached embedding vectors by their configs.
+    pub vectors: Vec<VectorTotal>,
+    #[serde(rename = "unreferencedVectors")]
+    pub unreferenced_vectors: i32,
+    #[serde(rename = "unreferencedVectorBytes")]
+    pub unreferenced_vector_bytes: f64,
+    #[serde(rename = "fileBytes")]
+    pub file_bytes: f64,
+    /// Bytes of the free pages, which vacuuming gives back.
+    #[serde(rename = "freeBytes")]
+    pub free_bytes: f64,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct RepairReport {
+    pub deleted: Vec<OrphanCount>,
+    #[serde(rename = "prunedVectors")]
+    pub pruned_vectors: i32,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct VacuumReport {
+    #[serde(rename = "bytesBefore")]
+    pub bytes_before: f64,
+    #[serde(rename = "bytesAfter")]
+    pub bytes_after: f64,
+}
+
+#[derive(Deserialize)]
+struct CountRow {
+    count: i64,
+}
+
+#[derive(Deserialize)]
+struct IntegrityRow {
+    integrity_check: String,
+}
+
+#[derive(Deserialize)]
+struct NameRow {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct SizeRow {
+    name: String,
+    bytes: i64,
+}
+
+#[derive(Deserialize)]
+struct VectorRow {
+    #[serde(rename = "embeddingsConfigId")]
+    embeddings_config_id: i32,
+    count: i64,
+    bytes: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct PageCountRow {
+    page_count: i64,
+}
+
+#[derive(Deserialize)]
+struct PageSizeRow {
+    page_size: i64,
+}
+
+#[derive(Deserialize)]
+struct FreelistCountRow {
+    freelist_count: i64,
+}
+
+async fn count(db: &PrismaClient, sql: &str) -> crate::Result<i32> {
+    let rows: Vec<CountRow> = db._query_raw(raw!(sql)).exec().await?;
+    Ok(rows.first().map(|row| row.count as i32).unwrap_or_default())
+}
+
+/// Bytes of the database file and of its free pages.
+async fn file_bytes(db: &PrismaClient) -> crate::Result<(f64, f64)> {
+    let page_count: Vec<PageCountRow> = db._query_raw(raw!("PRAGMA page_count")).exec().await?;
+    let page_size: Vec<PageSizeRow> = db._query_raw(raw!("PRAGMA page_size")).exec().await?;
+    let freelist_count: Vec<FreelistCountRow> =
+        db._query_raw(raw!("PRAGMA freelist_count")).exec().await?;
+    let page_size = page_size.first().map(|row| row.page_size).unwrap_or(0) as f64;
+    Ok((
+        page_count.first().map(|row| row.page_count).unwrap_or(0) as f64 * page_size,
+        freelist_count
+            .first()
+            .map(|row| row.freelist_count)
+            .unwrap_or(0) as f64
+            * page_size,
+    ))
+}
+
+/// Check the integrity of the database, and report the orphan rows and the sizes of the tables.
+pub async fn check(db: &PrismaClient) -> crate::Result<DatabaseReport> {
+    let integrity = db
+        ._query_raw::<IntegrityRow>(raw!(&format!(
+            "PRAGMA integrity_check({})",
+            MAX_INTEGRITY_ERRORS
+        )))
+        .exec()
+        .await?
+        .into_iter()
+        .map(|row| row.integrity_check)
+        .collect::<Vec<_>>();
+
+    let mut orphans = vec![];
+    for rule in &ORPHAN_RULES {
+        orphans.push(OrphanCount {
+            table: rule.table.to_string(),
+            description: rule.description.to_string(),
+            count: count(db, &rule.count_sql()).await?,
+        });
+    }
+
+    let names: Vec<NameRow> = db
+        ._query_raw(raw!(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+ORDER BY name"
+        ))
+        .exec()
+        .await?;
+    // the dbstat table is only there if sqlite is built with it
+    let sizes: Vec<SizeRow> = db
+        ._query_raw(raw!(
+            "SELECT COALESCE(m.tbl_name, s.name) AS name, SUM(s.pgsize) AS bytes
+FROM dbstat s LEFT JOIN sqlite_master m ON m.name = s.name
+GROUP BY COALESCE(m.tbl_name, s.name)"
+        ))
+        .exec()
+        .await
+        .unwrap_or_default();
+    let mut tables = vec![];
+    for NameRow { name } in names {
+        tables.push(TableSize {
+            rows: count(db, &count_sql(&name, "1")).await?,
+            bytes: sizes
+                .iter()
+                .find(|size| size.name == name)
+                .map(|size| size.bytes as f64),
+            name,
+        });
+    }
+
+    let vectors = db
+        ._query_raw::<VectorRow>(raw!(
+            "SELECT \"embeddingsConfigId\", COUNT(*) AS \"count\", SUM(LENGTH(\"vector\")) AS \"bytes\"
+FROM \"EmbeddingVectorsOnDocumentChunks\"
+GROUP BY \"embeddingsConfigId\""
+        ))
+        .exec()
+        .await?
+        .into_iter()
+        .map(|row| VectorTotal {
+            embeddings_config_id: row.embeddings_config_id,
+            count: row.count as i32,
+            bytes: row.bytes.unwrap_or(0) as f64,
+        })
+        .collect();
+    let unreferenced = db
+        ._query_raw::<VectorRow>(raw!(&format!(
+            "SELECT 0 AS \"embeddingsConfigId\", COUNT(*) AS \"count\",
+    SUM(LENGTH(t.\"vector\")) AS \"bytes\"
+FROM \"EmbeddingVectorsOnDocumentChunks\" t
+WHERE {}",
+            UNREFERENCED_VECTORS_CONDITION
+        )))
+        .exec()
+        .await?
+        .pop();
+
+    let (file_bytes, free_bytes) = file_bytes(db).await?;
+    Ok(DatabaseReport {
+        healthy: integrity == ["ok"] && orphans.iter().all(|orphan| orphan.count == 0),
+        integrity,
+        orphans,
+        tables,
+        vectors,
+        unreferenced_vectors: unreferenced
+            .as_ref()
+            .map(|row| row.count as i32)
+            .unwrap_or_default(),
+        unreferenced_vector_bytes: unreferenced.and_then(|row| row.bytes).unwrap_or_default()
+            as f64,
+        file_bytes,
+        free_bytes,
+    })
+}
+
+/// Delete the orphan rows, and the embedding vectors not of any chunk if `prune_vectors` is set.
+///
+/// The fts index of session messages is rebuilt afterwards, in case it is out of sync.
+pub async fn repair(db: &PrismaClient, prune_vectors: bool) -> crate::Result<RepairReport> {
+    let mut deleted = vec![];
+    for rule in &ORPHAN_RULES {
+        let count = db._execute_raw(raw!(&rule.delete_sql())).exec().await?;
+        if count > 0 {
+            deleted.push(OrphanCount {
+                table: rule.table.to_string(),
+                description: rule.description.to_string(),
+                count: count as i32,
+            });
+        }
+    }
+    let pruned_vectors = if prune_vectors {
+        db._execute_raw(raw!(&delete_sql(
+            "EmbeddingVectorsOnDocumentChunks",
+            UNREFERENCED_VECTORS_CONDITION
+        )))
+        .exec()
+        .await? as i32
+    } else {
+        0
+    };
+    session_search::prepare_index(db).await?;
+    Ok(RepairReport {
+        deleted,
+        pruned_vectors,
+    })
+}
+
+/// Rebuild the database file to give back the free pages and defragment the tables.
+pub async fn vacuum(db: &PrismaClient) -> crate::Result<VacuumReport> {
+    let (bytes_before, _) = file_bytes(db).await?;
+    db._execute_raw(raw!("VACUUM")).exec().await?;
+    let (bytes_after, _) = file_bytes(db).await?;
+    Ok(VacuumReport {
+        bytes_before,
+        bytes_after,
+    })
+}
@@ -1,4 +1,33 @@
+pub mod backup;
+pub mod bibliography;
+pub mod client_types;
+pub mod collection_archive;
+pub mod completion;
+pub mod config_bundle;
+pub mod connection_test;
+pub mod document_metadata;
+pub mod document_versions;
+pub mod embeddings;
 pub mod fs;
+pub mod history;
+pub mod http;
 #[cfg(feature = "http-invoke")]
 pub mod http_invoke;
+pub mod linked_documents;
+pub mod maintenance;
+pub mod qa;
+pub mod reranker;
 pub mod result;
+pub mod retrieval;
+pub mod secrets;
+pub mod session_export;
+pub mod session_search;
+pub mod session_summary;
+pub mod storage;
+pub mod text;
+pub mod tokenizer;
+pub mod usage;
+pub mod validation;
+pub mod vector_db;
+pub mod watcher;
+pub mod workspace;
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::core::completion::{fill_prompt, ChatMessage, Completion};
+use crate::core::history::History;
+use crate::core::retrieval::{self, RetrievedChunk};
+use crate::core::tokenizer::{count_message_tokens, count_tokens, fit_tokens, truncate_tokens};
+use crate::prisma::{session, PrismaClient};
+
+/// Number of chunks retrieved as sources for answering a question.
+const NUM_SOURCES: usize = 4;
+/// Tokens taken by the number, the filename and the page heading each source in the prompt.
+const SOURCE_HEADER_TOKENS: usize = 32;
+
+/// A prompt for compressing question into a compact form.
+const CONDENSE_PROMPT: &str = "Given the following conversation and a follow up question, \
+rephrase the follow up question to be a standalone question.
+
+Chat History:
+{chat_history}
+Follow Up Input: {question}
+Standalone question:";
+
+/// A prompt for answering a question by the numbered sources.
+///
+/// Referring to https://github.com/mayooear/gpt4-pdf-chatbot-langchain
+const QA_PROMPT: &str = "You are an AI assistant helping user understanding a collection of \
+documents. You need to answer a given question by referencing a few given sections of the \
+collection as a context.
+The sections are numbered and wrapped between two ========= tags. Cite the sections you use by \
+their numbers in square brackets, such as [1]. If you can't find answer in the context, just say \
+\"Sorry, I cannot find references from the document collection\".
+
+Question: {question}
+=========
+{context}
+=========
+Answer in Markdown (Code block MUST have a correct language tag):";
+
+/// How the conversation history is taken into account when asking a question.
+#[derive(Deserialize, Type, Clone, Copy, Debug)]
+pub enum QaMode {
+    /// Rephrase the question with the history into a standalone one before retrieving.
+    RephraseHistory,
+    /// Ask the question as is.
+    WithoutHistory,
+}
+
+#[derive(Serialize, Type)]
+pub struct QaAnswer {
+    /// The question actually used for retrieving and answering.
+    #[serde(rename = "standaloneQuestion")]
+    pub standalone_question: String,
+    pub answer: String,
+    /// The chunks given to the model as sources, numbered from 1 in this order.
+    pub sources: Vec<RetrievedChunk>,
+}
+
+/// Answer a question over the collection index of a session.
+///
+/// The question is first rephrased with the history if asked to, then the most relevant chunks
+/// are retrieved from the collection index and put into the prompt as numbered sources. The
+/// answer is streamed to `on_delta`.
+///
+/// The oldest questions of the history and the least relevant sources are left out of the
+/// prompts if they would not fit in the context window of the model.
+pub async fn ask<F>(
+    db: &PrismaClient,
+    session: &session::Data,
+    history: &History,
+    question: String,
+    mode: QaMode,
+    completion: &Completion,
+    on_delta: F,
+) -> crate::Result<QaAnswer>
+where
+    F: FnMut(&str),
+{
+    let standalone_question = match mode {
+        QaMode::RephraseHistory if !history.is_empty() => {
+            rephrase(history, &question, completion).await?
+        }
+        _ => question,
+    };
+
+    let mut sources = retrieval::retrieve(
+        db,
+        session.index_id.clone(),
+        &standalone_question,
+        NUM_SOURCES,
+        Some(session.id),
+    )
+    .await?;
+    let available = completion
+        .budget()
+        .remaining(count_message_tokens(&[ChatMessage::user(fill_prompt(
+            QA_PROMPT,
+            &[("question", &standalone_question), ("context", "")],
+        ))]));
+    fit_tokens(
+        &mut sources,
+        available,
+        |source| source.tokens() + SOURCE_HEADER_TOKENS,
+        |source, available| {
+            source.content = truncate_tokens(
+                &source.content,
+                available.saturating_sub(SOURCE_HEADER_TOKENS),
+            );
+            source.token_count = None;
+        },
+    );
+    let prompt = fill_prompt(
+        QA_PROMPT,
+        &[
+            ("question", &standalone_question),
+            ("context", &format_sources(&sources)),
+        ],
+    );
+    let answer = completion
+        .stream(&[ChatMessage::user(prompt)], on_delta)
+        .await?;
+
+    Ok(QaAnswer {
+        standalone_question,
+        answer,
+        sources,
+    })
+}
+
+async fn rephrase(
+    history: &History,
+    question: &str,
+    completion: &Completion,
+) -> crate::Result<String> {
+    let summary = if history.summary.is_empty() {
+        String::new()
+    } else {
+        format!("Summary of the earlier conversation: {}\n", history.summary)
+    };
+    let available = completion
+        .budget()
+        .remaining(count_message_tokens(&[ChatMessage::user(fill_prompt(
+            CONDENSE_PROMPT,
+            &[("chat_history", &summary), ("question", question)],
+        ))]));
+
+    // keep the latest questions, which matter the most for the follow up one
+    let mut pairs = history
+        .pairs
+        .iter()
+        .rev()
+        .map(|pair| format!("Human: {}\nAssistant: {}\n", pair.question, pair.answer))
+        .collect::<Vec<_>>();
+    fit_tokens(
+        &mut pairs,
+        available,
+        |pair| count_tokens(pair),
+        |pair, available| *pair = truncate_tokens(pair, available),
+    );
+    pairs.reverse();
+    let prompt = fill_prompt(
+        CONDENSE_PROMPT,
+        &[
+            ("chat_history", &(summary + &pairs.concat())),
+            ("question", question),
+        ],
+    );
+    Ok(completion
+        .complete(&[ChatMessage::user(prompt)])
+        .await?
+        .trim()
+        .to_string())
+}
+
+/// Format the sources as numbered sections, named after the documents they are from.
+fn format_sources(sources: &[RetrievedChunk]) -> String {
+    sources
+        .iter()
+        .enumerate()
+        .map(|(i, source)| {
+            let mut name = source.filename.clone();
+            if let Some(label) = source.metadata.as_ref().and_then(|m| m.short_label()) {
+                name = format!("{}: {}", label, name);
+            }
+            match source.page() {
+                Some(page) => format!("[{}] {} (page {})\n{}", i + 1, name, page, source.content),
+                None => format!("[{}] {}\n{}", i + 1, name, source.content),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
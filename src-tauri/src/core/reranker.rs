@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::client_types::{ClientPayload, RerankerClientInfo};
+use crate::core::http::ensure_success;
+use crate::core::secrets;
+use crate::prisma::reranker_client;
+
+const COHERE_RERANK_URL: &str = "https://api.cohere.ai/v1/rerank";
+const COHERE_RERANK_MODEL: &str = "rerank-english-v2.0";
+const JINA_RERANK_URL: &str = "https://api.jina.ai/v1/rerank";
+const JINA_RERANK_MODEL: &str = "jina-reranker-v1-base-en";
+
+/// A cross-encoder reranker speaking the Cohere/Jina rerank protocol.
+///
+/// Both services accept a query with a list of documents and answer with relevance scores
+/// referring to the documents by index, so a single client serves both of them. The endpoint can
+/// be overridden by `url` in the client info, which is handy for pointing to a local mock server
+/// or a self-hosted model.
+pub struct Reranker {
+    url: String,
+    api_key: Option<String>,
+    model: String,
+    http: reqwest::Client,
+}
+
+#[derive(Serialize)]
+struct RerankRequest<'a> {
+    model: &'a str,
+    query: &'a str,
+    documents: &'a [String],
+    top_n: usize,
+}
+
+#[derive(Deserialize)]
+struct RerankResponse {
+    results: Vec<RerankResult>,
+}
+
+/// Relevance of the document at `index` of the reranked documents.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RerankResult {
+    pub index: usize,
+    pub relevance_score: f32,
+}
+
+impl Reranker {
+    pub fn from_data(client: &reranker_client::Data) -> crate::Result<Self> {
+        let info =
+            RerankerClientInfo::from_stored(&client.r#type, &secrets::reveal_info(&client.info)?)?;
+        let (default_url, default_model) = match info {
+            RerankerClientInfo::Cohere(_) => (COHERE_RERANK_URL, COHERE_RERANK_MODEL),
+            RerankerClientInfo::Jina(_) => (JINA_RERANK_URL, JINA_RERANK_MODEL),
+        };
+        let info = info.info().clone();
+        Ok(Reranker {
+            url: info.url.unwrap_or_else(|| default_url.to_string()),
+            api_key: info.api_key,
+            model: info.model.unwrap_or_else(|| default_model.to_string()),
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// Score each of `documents` against `query`, returning at most `top_n` results ordered by
+    /// descending relevance.
+    pub async fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+        top_n: usize,
+    ) -> crate::Result<Vec<RerankResult>> {
+        if documents.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut request = self.http.post(&self.url).json(&RerankRequest {
+            model: &self.model,
+            query,
+            documents,
+            top_n,
+        });
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let mut results = ensure_success(request.send().await?)
+            .await?
+            .json::<RerankResponse>()
+            .await?
+            .results;
+        results.retain(|result| result.index < documents.len());
+        results.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+        results.truncate(top_n);
+        Ok(results)
+    }
+}
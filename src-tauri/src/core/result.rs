@@ -1,12 +1,38 @@
 use serde::ser::StdError;
 use serde::{Serialize, Serializer};
 
+use crate::core::validation::ValidationError;
+
 /// Wrap anyhow::Error so that we can serialize it.
 #[derive(Debug)]
 pub struct Error(anyhow::Error);
 
 pub type Result<T, E = Error> = anyhow::Result<T, E>;
 
+impl Error {
+    /// Create an error from a plain message.
+    pub fn msg<M>(message: M) -> Self
+    where
+        M: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static,
+    {
+        Error(anyhow::Error::msg(message))
+    }
+
+    /// The underlying error if it is of type `E`.
+    pub fn downcast_ref<E>(&self) -> Option<&E>
+    where
+        E: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static,
+    {
+        self.0.downcast_ref::<E>()
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
 /// Support to convert from any std errors to crate::result::Error
 impl<E> From<E> for Error
 where
@@ -23,6 +49,9 @@ impl Serialize for Error {
     where
         S: Serializer,
     {
+        if let Some(validation) = self.0.downcast_ref::<ValidationError>() {
+            return serializer.serialize_newtype_struct("Error", validation);
+        }
         let err = serde_error::Error::new(&*self.0);
         serializer.serialize_newtype_struct("Error", &err)
     }
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::core::document_metadata::Metadata;
+use crate::core::embeddings::Embeddings;
+use crate::core::reranker::Reranker;
+use crate::core::result::Error;
+use crate::core::tokenizer::count_tokens;
+use crate::core::usage::{self, UsageScope};
+use crate::core::vector_db::{ScoredVector, VectorDb};
+use crate::prisma::{collection_index, document_chunk, PrismaClient};
+
+/// How many candidates per requested chunk are fetched from the vector db when a reranker is
+/// configured, so that the reranker has something to choose from.
+const RERANK_CANDIDATES_FACTOR: usize = 4;
+
+collection_index::include!(collection_index_with_profile {
+    index: include {
+        embeddings_client embeddings_config vector_db_client vector_db_config reranker_client
+    }
+    indexed_documents
+});
+
+document_chunk::include!(document_chunk_with_document {
+    document: include { metadata }
+});
+
+/// Identity of a document chunk.
+#[derive(Serialize, Deserialize, Type, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkRef {
+    #[serde(rename = "documentId")]
+    pub document_id: i32,
+    #[serde(rename = "splittingId")]
+    pub splitting_id: i32,
+    pub no: i32,
+}
+
+/// A document chunk retrieved for a query.
+#[derive(Serialize, Type, Clone)]
+pub struct RetrievedChunk {
+    #[serde(rename = "documentId")]
+    pub document_id: i32,
+    #[serde(rename = "splittingId")]
+    pub splitting_id: i32,
+    pub no: i32,
+    pub content: String,
+    pub meta: serde_json::Value,
+    #[serde(rename = "md5Hash")]
+    pub md5_hash: String,
+    pub filename: String,
+    /// Bibliographic metadata of the document, if it has some.
+    pub metadata: Option<Metadata>,
+    /// Number of tokens of the content, if counted when the chunk was created.
+    #[serde(rename = "tokenCount")]
+    pub token_count: Option<i32>,
+    /// Similarity score given by the vector db.
+    pub score: f32,
+    /// Relevance score given by the reranker, if the index profile has one.
+    #[serde(rename = "rerankScore")]
+    pub rerank_score: Option<f32>,
+}
+
+impl RetrievedChunk {
+    fn new(chunk: &document_chunk_with_document::Data, score: f32) -> Self {
+        Self {
+            document_id: chunk.document_id,
+            splitting_id: chunk.splitting_id,
+            no: chunk.no,
+            content: chunk.content.clone(),
+            meta: serde_json::from_str(&chunk.meta).unwrap_or(serde_json::Value::Null),
+            md5_hash: chunk.md_5_hash.clone(),
+            filename: chunk.document.filename.clone(),
+            metadata: chunk
+                .document
+                .metadata
+                .as_ref()
+                .and_then(|metadata| Metadata::from_data(metadata).ok()),
+            token_count: chunk.token_count,
+            score,
+            rerank_score: None,
+        }
+    }
+
+    pub fn chunk_ref(&self) -> ChunkRef {
+        ChunkRef {
+            document_id: self.document_id,
+            splitting_id: self.splitting_id,
+            no: self.no,
+        }
+    }
+
+    /// The page number the chunk comes from.
+    pub fn page(&self) -> Option<i64> {
+        page_of(&self.meta)
+    }
+
+    /// Number of tokens of the content, counted now if not counted before.
+    pub fn tokens(&self) -> usize {
+        self.token_count
+            .map(|count| count as usize)
+            .unwrap_or_else(|| count_tokens(&self.content))
+    }
+}
+
+/// The page number recorded by the document loader in the meta of a chunk.
+pub fn page_of(meta: &serde_json::Value) -> Option<i64> {
+    meta.pointer("/loc/pageNumber")
+        .and_then(|page| page.as_i64())
+}
+
+/// Retrieve the `top_k` chunks most relevant to `query` from a collection index.
+///
+/// The query is embedded and matched against the vectors stored in the namespace of the
+/// collection index. If the index profile has a reranker, more candidates are fetched and then
+/// reordered by the reranker before being cut down to `top_k`.
+///
+/// The embedding of the query is recorded as a usage of the session if given.
+pub async fn retrieve(
+    db: &PrismaClient,
+    collection_index_id: String,
+    query: &str,
+    top_k: usize,
+    session_id: Option<i32>,
+) -> crate::Result<Vec<RetrievedChunk>> {
+    let index = find_index(db, collection_index_id).await?;
+    let profile = &index.index;
+    let embeddings = Embeddings::from_data(&profile.embeddings_client, &profile.embeddings_config)?;
+    let vector_db = VectorDb::from_data(&profile.vector_db_client, &profile.vector_db_config)?;
+    let reranker = profile
+        .reranker_client
+        .as_ref()
+        .map(Reranker::from_data)
+        .transpose()?;
+
+    let vector = embeddings.embed_query(query).await?;
+    let scope = UsageScope {
+        embeddings_client_id: Some(profile.embeddings_client_id),
+        index_profile_id: Some(profile.id),
+        session_id,
+        ..Default::default()
+    };
+    if let Err(err) = usage::record(db, embeddings.take_usage(), &scope).await {
+        log::error!(
+            "error while recording usage of index profile {}: {}",
+            profile.id,
+            err
+        );
+    }
+    let n_candidates = match reranker {
+        Some(_) => top_k * RERANK_CANDIDATES_FACTOR,
+        None => top_k,
+    };
+    let matches = vector_db.query(&index.id, &vector, n_candidates).await?;
+    let mut candidates = resolve_chunks(db, &index, matches).await?;
+    if let Some(reranker) = reranker {
+        candidates = rerank(&reranker, query, candidates, top_k).await?;
+    }
+    candidates.truncate(top_k);
+    Ok(candidates)
+}
+
+/// Find a collection index with its index profile and the clients of the profile.
+pub async fn find_index(
+    db: &PrismaClient,
+    collection_index_id: String,
+) -> crate::Result<collection_index_with_profile::Data> {
+    db.collection_index()
+        .find_unique(collection_index::id::equals(collection_index_id.clone()))
+        .include(collection_index_with_profile::include())
+        .exec()
+        .await?
+        .ok_or_else(|| {
+            Error::msg(format!(
+                "Collection index {} not found",
+                collection_index_id
+            ))
+        })
+}
+
+/// Map the matched vectors back to the local chunks they were embedded from.
+///
+/// Vectors are identified by the hash of their chunks. Matches that no longer have a chunk
+/// among the indexed documents are dropped.
+async fn resolve_chunks(
+    db: &PrismaClient,
+    index: &collection_index_with_profile::Data,
+    matches: Vec<ScoredVector>,
+) -> crate::Result<Vec<RetrievedChunk>> {
+    let document_ids = index
+        .indexed_documents
+        .iter()
+        .map(|indexed| indexed.document_id)
+        .collect();
+    let hashes = matches.iter().map(|m| m.id.clone()).collect();
+    let chunks = db
+        .document_chunk()
+        .find_many(vec![
+            document_chunk::splitting_id::equals(index.index.splitting_id),
+            document_chunk::document_id::in_vec(document_ids),
+            document_chunk::md_5_hash::in_vec(hashes),
+        ])
+        .include(document_chunk_with_document::include())
+        .exec()
+        .await?;
+
+    let mut chunks_by_hash = HashMap::new();
+    for chunk in chunks {
+        chunks_by_hash
+            .entry(chunk.md_5_hash.clone())
+            .or_insert(chunk);
+    }
+    Ok(matches
+        .into_iter()
+        .filter_map(|m| {
+            chunks_by_hash
+                .get(&m.id)
+                .map(|chunk| RetrievedChunk::new(chunk, m.score))
+        })
+        .collect())
+}
+
+async fn rerank(
+    reranker: &Reranker,
+    query: &str,
+    candidates: Vec<RetrievedChunk>,
+    top_k: usize,
+) -> crate::Result<Vec<RetrievedChunk>> {
+    let documents = candidates
+        .iter()
+        .map(|candidate| candidate.content.clone())
+        .collect::<Vec<_>>();
+    let results = reranker.rerank(query, &documents, top_k).await?;
+
+    let mut candidates = candidates.into_iter().map(Some).collect::<Vec<_>>();
+    Ok(results
+        .into_iter()
+        .filter_map(|result| {
+            candidates[result.index].take().map(|mut candidate| {
+                candidate.rerank_score = Some(result.relevance_score);
+                candidate
+            })
+        })
+        .collect())
+}
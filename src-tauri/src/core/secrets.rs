@@ -0,0 +1,381 @@
+use std::sync::RwLock;
+
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::Serialize;
+use specta::Type;
+
+use crate::core::result::Error;
+use crate::core::validation::ValidationError;
+use crate::prisma::{
+    completion_client, embeddings_client, reranker_client, secret_vault, vector_db_client,
+    PrismaClient,
+};
+
+/// What secret fields are replaced with when client infos are sent out of the backend.
+pub const REDACTED: &str = "********";
+
+/// Prefix of the secret values sealed by the key, followed by the base64 of the nonce and the
+/// ciphertext.
+const SEALED_PREFIX: &str = "sealed:v1:";
+/// A known text sealed by the key, for checking passphrases.
+const VERIFIER_TEXT: &str = "academic-chatgpt secret vault";
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+
+/// The key derived from the passphrase, present only while the secrets are unlocked.
+static KEY: RwLock<Option<[u8; 32]>> = RwLock::new(None);
+
+#[derive(Serialize, Type)]
+pub struct SecretsStatus {
+    /// Whether a passphrase is set, in which case secrets are stored sealed.
+    configured: bool,
+    /// Whether the secrets can be sealed and revealed now.
+    unlocked: bool,
+}
+
+/// Whether a field of a client info holds a secret, such as `apiKey`.
+pub fn is_secret_field(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["key", "secret", "token", "password"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> crate::Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut key)?;
+    Ok(key)
+}
+
+fn seal(key: &[u8; 32], plaintext: &str) -> crate::Result<String> {
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let mut sealed = nonce.to_vec();
+    sealed.extend(cipher.encrypt(&nonce, plaintext.as_bytes())?);
+    Ok(format!("{}{}", SEALED_PREFIX, BASE64.encode(sealed)))
+}
+
+fn open(key: &[u8; 32], sealed: &str) -> crate::Result<String> {
+    let bytes = BASE64.decode(sealed.trim_start_matches(SEALED_PREFIX))?;
+    if bytes.len() < NONCE_LENGTH {
+        return Err(Error::msg("Sealed secret is too short"));
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let plaintext = cipher.decrypt(XNonce::from_slice(nonce), ciphertext)?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+fn current_key() -> Option<[u8; 32]> {
+    *KEY.read().unwrap()
+}
+
+/// Apply `f` to the string values of the secret fields of a client info.
+fn map_secrets<F>(info: &mut serde_json::Value, mut f: F) -> crate::Result<()>
+where
+    F: FnMut(&str) -> crate::Result<String>,
+{
+    if let Some(fields) = info.as_object_mut() {
+        for (name, value) in fields.iter_mut() {
+            if let (true, Some(text)) = (is_secret_field(name), value.as_str()) {
+                *value = serde_json::Value::String(f(text)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replace the secrets of a client info with `REDACTED`.
+pub fn redact_info(mut info: serde_json::Value) -> serde_json::Value {
+    let _ = map_secrets(&mut info, |_| Ok(REDACTED.to_string()));
+    info
+}
+
+/// Redact a stored client info, sealed or not, as the JSON text it is stored as.
+pub fn redact_stored_info(info: &str) -> String {
+    serde_json::from_str(info)
+        .map(|info| redact_info(info).to_string())
+        .unwrap_or_else(|_| "{}".to_string())
+}
+
+/// Remove the secret fields of a client info.
+pub fn strip_info(mut info: serde_json::Value) -> serde_json::Value {
+    if let Some(fields) = info.as_object_mut() {
+        fields.retain(|name, _| !is_secret_field(name));
+    }
+    info
+}
+
+/// Put back the stored secrets in place of the `REDACTED` ones of an edited client info, so that
+/// a redacted info sent back by the frontend does not overwrite the secrets.
+pub fn restore_redacted(info: &mut serde_json::Value, stored: &str) -> crate::Result<()> {
+    let stored: serde_json::Value = serde_json::from_str(stored)?;
+    if let Some(fields) = info.as_object_mut() {
+        for (name, value) in fields.iter_mut() {
+            if value.as_str() == Some(REDACTED) {
+                if let Some(stored) = stored.get(name) {
+                    *value = stored.clone();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Seal the plain secrets of a client info for storing.
+///
+/// Secrets are stored as is if no passphrase is set. Otherwise the secrets have to be unlocked.
+pub async fn seal_info(db: &PrismaClient, mut info: serde_json::Value) -> crate::Result<String> {
+    match current_key() {
+        Some(key) => map_secrets(&mut info, |text| {
+            if text.starts_with(SEALED_PREFIX) {
+                Ok(text.to_string())
+            } else {
+                seal(&key, text)
+            }
+        })?,
+        None if find_vault(db).await?.is_some() => return Err(locked_error()),
+        None => {}
+    }
+    Ok(serde_json::to_string(&info)?)
+}
+
+/// Reveal the sealed secrets of a stored client info, for use inside the backend only.
+pub fn reveal_info(info: &str) -> crate::Result<String> {
+    if !info.contains(SEALED_PREFIX) {
+        return Ok(info.to_string());
+    }
+    let key = current_key().ok_or_else(locked_error)?;
+    let mut info: serde_json::Value = serde_json::from_str(info)?;
+    map_secrets(&mut info, |text| {
+        if text.starts_with(SEALED_PREFIX) {
+            open(&key, text)
+        } else {
+            Ok(text.to_string())
+        }
+    })?;
+    Ok(serde_json::to_string(&info)?)
+}
+
+/// Check that a client info to be stored is not the same as one of the stored infos of the
+/// other clients of its type, given with their names.
+///
+/// Sealing uses a random nonce, so the unique constraint on the stored infos does not catch
+/// duplicates once a passphrase is set. The infos are compared revealed instead.
+pub fn check_duplicate_info<'a>(
+    info: &serde_json::Value,
+    others: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> crate::Result<()> {
+    let info: serde_json::Value = serde_json::from_str(&reveal_info(&info.to_string())?)?;
+    let mut errors = ValidationError::default();
+    for (name, other) in others {
+        let other: serde_json::Value = serde_json::from_str(&reveal_info(other)?)?;
+        if other == info {
+            errors.add("info", format!("is the same as of the client {}", name));
+        }
+    }
+    errors.into_result()?;
+    Ok(())
+}
+
+/// The kinds of clients whose infos hold secrets.
+#[derive(Clone, Copy)]
+pub enum ClientKind {
+    Embeddings,
+    VectorDb,
+    Reranker,
+    Completion,
+}
+
+/// Check that no other client of the kind and the type has the same info as the client to be
+/// stored, `client_id` being the one of the client if it is already stored.
+///
+/// Run it in the transaction storing the client, so that no duplicate is stored in between.
+pub async fn check_duplicate(
+    db: &PrismaClient,
+    kind: ClientKind,
+    client_id: Option<i32>,
+    r#type: &str,
+    info: &serde_json::Value,
+) -> crate::Result<()> {
+    let r#type = r#type.to_string();
+    let others: Vec<(i32, String, String)> = match kind {
+        ClientKind::Embeddings => db
+            .embeddings_client()
+            .find_many(vec![embeddings_client::r#type::equals(r#type)])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|other| (other.id, other.name, other.info))
+            .collect(),
+        ClientKind::VectorDb => db
+            .vector_db_client()
+            .find_many(vec![vector_db_client::r#type::equals(r#type)])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|other| (other.id, other.name, other.info))
+            .collect(),
+        ClientKind::Reranker => db
+            .reranker_client()
+            .find_many(vec![reranker_client::r#type::equals(r#type)])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|other| (other.id, other.name, other.info))
+            .collect(),
+        ClientKind::Completion => db
+            .completion_client()
+            .find_many(vec![completion_client::r#type::equals(r#type)])
+            .exec()
+            .await?
+            .into_iter()
+            .map(|other| (other.id, other.name, other.info))
+            .collect(),
+    };
+    check_duplicate_info(
+        info,
+        others
+            .iter()
+            .filter(|(id, _, _)| Some(*id) != client_id)
+            .map(|(_, name, info)| (name.as_str(), info.as_str())),
+    )
+}
+
+fn locked_error() -> Error {
+    Error::msg("Secrets are locked, unlock them with the passphrase first")
+}
+
+async fn find_vault(db: &PrismaClient) -> crate::Result<Option<secret_vault::Data>> {
+    Ok(db.secret_vault().find_first(vec![]).exec().await?)
+}
+
+pub async fn status(db: &PrismaClient) -> crate::Result<SecretsStatus> {
+    Ok(SecretsStatus {
+        configured: find_vault(db).await?.is_some(),
+        unlocked: current_key().is_some(),
+    })
+}
+
+/// Unlock the secrets with the passphrase.
+pub async fn unlock(db: &PrismaClient, passphrase: &str) -> crate::Result<()> {
+    let vault = find_vault(db)
+        .await?
+        .ok_or_else(|| Error::msg("No passphrase is set for secrets"))?;
+    let key = derive_key(passphrase, &BASE64.decode(&vault.salt)?)?;
+    match open(&key, &vault.verifier) {
+        Ok(text) if text == VERIFIER_TEXT => {
+            *KEY.write().unwrap() = Some(key);
+            Ok(())
+        }
+        _ => Err(Error::msg("Wrong passphrase")),
+    }
+}
+
+/// Forget the key, so that secrets cannot be revealed until unlocked again.
+pub fn lock() {
+    *KEY.write().unwrap() = None;
+}
+
+/// Set the passphrase sealing the secrets, and reseal all the stored client infos by it.
+///
+/// Changing a passphrase requires the current one. The secrets are left unlocked by the new one.
+pub async fn set_passphrase(
+    db: &PrismaClient,
+    current: Option<String>,
+    passphrase: String,
+) -> crate::Result<()> {
+    if passphrase.is_empty() {
+        return Err(Error::msg("Passphrase must not be empty"));
+    }
+    let vault = find_vault(db).await?;
+    if vault.is_some() {
+        unlock(db, current.as_deref().unwrap_or_default()).await?;
+    }
+
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let new_key = derive_key(&passphrase, &salt)?;
+    let reseal = |info: &str| -> crate::Result<String> {
+        let mut info: serde_json::Value = serde_json::from_str(&reveal_info(info)?)?;
+        map_secrets(&mut info, |text| seal(&new_key, text))?;
+        Ok(serde_json::to_string(&info)?)
+    };
+
+    let embeddings_clients = db.embeddings_client().find_many(vec![]).exec().await?;
+    let vector_db_clients = db.vector_db_client().find_many(vec![]).exec().await?;
+    let reranker_clients = db.reranker_client().find_many(vec![]).exec().await?;
+    let completion_clients = db.completion_client().find_many(vec![]).exec().await?;
+    let vault_id = vault.map(|vault| vault.id);
+    let salt = BASE64.encode(salt);
+    let verifier = seal(&new_key, VERIFIER_TEXT)?;
+    db._transaction()
+        .run(|tx| async move {
+            for client in embeddings_clients {
+                tx.embeddings_client()
+                    .update(
+                        embeddings_client::id::equals(client.id),
+                        vec![embeddings_client::info::set(reseal(&client.info)?)],
+                    )
+                    .exec()
+                    .await?;
+            }
+            for client in vector_db_clients {
+                tx.vector_db_client()
+                    .update(
+                        vector_db_client::id::equals(client.id),
+                        vec![vector_db_client::info::set(reseal(&client.info)?)],
+                    )
+                    .exec()
+                    .await?;
+            }
+            for client in reranker_clients {
+                tx.reranker_client()
+                    .update(
+                        reranker_client::id::equals(client.id),
+                        vec![reranker_client::info::set(reseal(&client.info)?)],
+                    )
+                    .exec()
+                    .await?;
+            }
+            for client in completion_clients {
+                tx.completion_client()
+                    .update(
+                        completion_client::id::equals(client.id),
+                        vec![completion_client::info::set(reseal(&client.info)?)],
+                    )
+                    .exec()
+                    .await?;
+            }
+            match vault_id {
+                Some(id) => {
+                    tx.secret_vault()
+                        .update(
+                            secret_vault::id::equals(id),
+                            vec![
+                                secret_vault::salt::set(salt),
+                                secret_vault::verifier::set(verifier),
+                            ],
+                        )
+                        .exec()
+                        .await?
+                }
+                None => {
+                    tx.secret_vault()
+                        .create(salt, verifier, vec![])
+                        .exec()
+                        .await?
+                }
+            };
+            crate::Result::Ok(())
+        })
+        .await?;
+
+    *KEY.write().unwrap() = Some(new_key);
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::core::document_metadata::Metadata;
+
+/// Maximum number of characters of a cited chunk quoted in an export.
+pub const EXCERPT_LENGTH: usize = 300;
+
+#[derive(Deserialize, Type, Clone, Copy, Debug)]
+pub enum SessionExportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+/// A conversation prepared for exporting, with its citations resolved.
+#[derive(Serialize)]
+pub struct Transcript {
+    pub name: String,
+    #[serde(rename = "exportTime")]
+    pub export_time: String,
+    pub messages: Vec<TranscriptMessage>,
+}
+
+#[derive(Serialize)]
+pub struct TranscriptMessage {
+    pub role: String,
+    pub content: String,
+    #[serde(rename = "createTime")]
+    pub create_time: String,
+    pub model: Option<String>,
+    pub citations: Vec<Citation>,
+}
+
+/// A source cited by a message, numbered as in the prompt the message answered.
+#[derive(Serialize, Clone)]
+pub struct Citation {
+    pub number: usize,
+    #[serde(rename = "documentId")]
+    pub document_id: i32,
+    pub filename: String,
+    /// Bibliographic metadata of the document, if it has some.
+    pub metadata: Option<Metadata>,
+    pub page: Option<i64>,
+    pub excerpt: String,
+}
+
+/// Cut `content` down to an excerpt of at most `EXCERPT_LENGTH` characters.
+pub fn excerpt(content: &str) -> String {
+    let content = content.trim();
+    match content.char_indices().nth(EXCERPT_LENGTH) {
+        Some((end, _)) => format!("{}…", &content[..end]),
+        None => content.to_string(),
+    }
+}
+
+pub fn render(transcript: &Transcript, format: SessionExportFormat) -> crate::Result<String> {
+    Ok(match format {
+        SessionExportFormat::Markdown => render_markdown(transcript),
+        SessionExportFormat::Json => serde_json::to_string_pretty(transcript)?,
+        SessionExportFormat::Html => render_html(transcript),
+    })
+}
+
+fn role_title(role: &str) -> &str {
+    match role {
+        "user" => "User",
+        "assistant" => "Assistant",
+        "system" => "System",
+        other => other,
+    }
+}
+
+/// The source of a citation, referred to by its bibliographic reference if it has one.
+fn citation_source(citation: &Citation) -> String {
+    let source = citation
+        .metadata
+        .as_ref()
+        .and_then(Metadata::reference)
+        .unwrap_or_else(|| citation.filename.clone());
+    match citation.page {
+        Some(page) => format!("{}, page {}", source, page),
+        None => source,
+    }
+}
+
+fn render_markdown(transcript: &Transcript) -> String {
+    let mut out = format!(
+        "# {}\n\n_Exported at {}_\n",
+        transcript.name, transcript.export_time
+    );
+    for message in &transcript.messages {
+        out += &format!(
+            "\n## {}\n\n_{}_\n\n{}\n",
+            role_title(&message.role),
+            message.create_time,
+            message.content.trim()
+        );
+        if !message.citations.is_empty() {
+            out += "\n**Sources**\n\n";
+            for citation in &message.citations {
+                out += &format!(
+                    "{}. {}\n\n   > {}\n\n",
+                    citation.number,
+                    citation_source(citation),
+                    citation.excerpt.replace('\n', "\n   > ")
+                );
+            }
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+const HTML_STYLE: &str = "body { font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', \
+sans-serif; max-width: 800px; margin: 2em auto; padding: 0 1em; color: #222; }
+.message { border-radius: 8px; padding: 0.5em 1em; margin: 1em 0; }
+.user { background: #eef4ff; }
+.assistant { background: #f6f6f6; }
+.meta { color: #888; font-size: 0.85em; }
+.content { white-space: pre-wrap; }
+.sources { font-size: 0.9em; }
+blockquote { color: #555; border-left: 3px solid #ccc; margin: 0.3em 0; padding-left: 0.8em; }";
+
+fn render_html(transcript: &Transcript) -> String {
+    let mut body = String::new();
+    for message in &transcript.messages {
+        body += &format!(
+            "<div class=\"message {}\">\n<div class=\"meta\">{} · {}{}</div>\n\
+            <div class=\"content\">{}</div>\n",
+            escape_html(&message.role),
+            escape_html(role_title(&message.role)),
+            escape_html(&message.create_time),
+            message
+                .model
+                .as_ref()
+                .map(|model| format!(" · {}", escape_html(model)))
+                .unwrap_or_default(),
+            escape_html(message.content.trim())
+        );
+        if !message.citations.is_empty() {
+            body += "<ol class=\"sources\">\n";
+            for citation in &message.citations {
+                body += &format!(
+                    "<li value=\"{}\">{}<blockquote>{}</blockquote></li>\n",
+                    citation.number,
+                    escape_html(&citation_source(citation)),
+                    escape_html(&citation.excerpt)
+                );
+            }
+            body += "</ol>\n";
+        }
+        body += "</div>\n";
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+        <style>\n{style}\n</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+        <p class=\"meta\">Exported at {time}</p>\n{body}</body>\n</html>\n",
+        title = escape_html(&transcript.name),
+        style = HTML_STYLE,
+        time = escape_html(&transcript.export_time),
+        body = body
+    )
+}
@@ -0,0 +1,256 @@
+use prisma_client_rust::{raw, PrismaValue};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::prisma::PrismaClient;
+
+/// Maximum number of matched messages looked at for a search.
+const MAX_MATCHES: i32 = 200;
+/// Maximum number of snippets returned for each matched session.
+const MAX_SNIPPETS_PER_SESSION: usize = 3;
+/// Number of tokens around the matched terms kept in a snippet.
+const SNIPPET_TOKENS: i32 = 16;
+
+/// Markers put around the matched terms by the fts snippet function, chosen to never appear in
+/// the content of messages so that snippets can be split into segments safely.
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_END: char = '\u{3}';
+
+/// Statements creating the fts index of session messages and the triggers keeping it in sync,
+/// the same as the migration adding them.
+///
+/// The index is an external-content fts5 table over `SessionMessage`, which is not declared in
+/// the prisma schema since prisma cannot model virtual tables. The schema is pushed rather than
+/// migrated in debug builds, so it is created here instead.
+#[cfg(debug_assertions)]
+const CREATE_INDEX_STATEMENTS: [&str; 4] = [
+    "CREATE VIRTUAL TABLE IF NOT EXISTS \"SessionMessageFts\" USING fts5(
+    content, content='SessionMessage', content_rowid='id', tokenize='unicode61'
+)",
+    "CREATE TRIGGER IF NOT EXISTS \"SessionMessageFts_ai\" AFTER INSERT ON \"SessionMessage\" BEGIN
+    INSERT INTO \"SessionMessageFts\" (rowid, content) VALUES (new.id, new.content);
+END",
+    "CREATE TRIGGER IF NOT EXISTS \"SessionMessageFts_ad\" AFTER DELETE ON \"SessionMessage\" BEGIN
+    INSERT INTO \"SessionMessageFts\" (\"SessionMessageFts\", rowid, content)
+        VALUES ('delete', old.id, old.content);
+END",
+    "CREATE TRIGGER IF NOT EXISTS \"SessionMessageFts_au\" AFTER UPDATE OF content ON \"SessionMessage\" BEGIN
+    INSERT INTO \"SessionMessageFts\" (\"SessionMessageFts\", rowid, content)
+        VALUES ('delete', old.id, old.content);
+    INSERT INTO \"SessionMessageFts\" (rowid, content) VALUES (new.id, new.content);
+END",
+];
+
+#[cfg(debug_assertions)]
+#[derive(Deserialize)]
+struct CountRow {
+    count: i64,
+}
+
+/// How far the fts index has caught up with the messages, by the rows of its `docsize` shadow
+/// table, which has one row per indexed message.
+#[derive(Deserialize)]
+struct IndexStateRow {
+    messages: i64,
+    indexed: i64,
+    #[serde(rename = "lastMessageId")]
+    last_message_id: i64,
+    #[serde(rename = "lastIndexedId")]
+    last_indexed_id: i64,
+}
+
+/// Make sure the fts index of session messages is there, and rebuild it if it is out of date.
+///
+/// The index is created by a migration and kept in sync by triggers, so it only has to be
+/// rebuilt when messages were written or deleted without them, such as by a restored backup or a
+/// repair of the database.
+pub async fn prepare_index(db: &PrismaClient) -> crate::Result<()> {
+    #[cfg(debug_assertions)]
+    if !index_exists(db).await? {
+        for statement in CREATE_INDEX_STATEMENTS {
+            db._execute_raw(raw!(statement)).exec().await?;
+        }
+    }
+    if is_index_stale(db).await? {
+        rebuild_index(db).await?;
+    }
+    Ok(())
+}
+
+#[cfg(debug_assertions)]
+async fn index_exists(db: &PrismaClient) -> crate::Result<bool> {
+    let rows: Vec<CountRow> = db
+        ._query_raw(raw!(
+            "SELECT count(*) AS count FROM sqlite_master WHERE name = 'SessionMessageFts'"
+        ))
+        .exec()
+        .await?;
+    Ok(rows.first().map(|row| row.count > 0).unwrap_or_default())
+}
+
+async fn is_index_stale(db: &PrismaClient) -> crate::Result<bool> {
+    let rows: Vec<IndexStateRow> = db
+        ._query_raw(raw!(
+            "SELECT
+    (SELECT count(*) FROM \"SessionMessage\") AS \"messages\",
+    (SELECT count(*) FROM \"SessionMessageFts_docsize\") AS \"indexed\",
+    (SELECT ifnull(max(\"id\"), 0) FROM \"SessionMessage\") AS \"lastMessageId\",
+    (SELECT ifnull(max(id), 0) FROM \"SessionMessageFts_docsize\") AS \"lastIndexedId\""
+        ))
+        .exec()
+        .await?;
+    Ok(rows.first().map_or(false, |row| {
+        row.messages != row.indexed || row.last_message_id != row.last_indexed_id
+    }))
+}
+
+async fn rebuild_index(db: &PrismaClient) -> crate::Result<()> {
+    db._execute_raw(raw!(
+        "INSERT INTO \"SessionMessageFts\" (\"SessionMessageFts\") VALUES ('rebuild')"
+    ))
+    .exec()
+    .await?;
+    Ok(())
+}
+
+/// A piece of a snippet, either highlighted as matching the query or not.
+#[derive(Serialize, Type, Debug)]
+pub struct SnippetSegment {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct MessageSnippet {
+    #[serde(rename = "messageId")]
+    pub message_id: i32,
+    pub role: String,
+    pub segments: Vec<SnippetSegment>,
+}
+
+/// Messages of a session matching a search, in the order of relevance.
+#[derive(Debug)]
+pub struct SessionMatch {
+    pub session_id: i32,
+    pub snippets: Vec<MessageSnippet>,
+}
+
+#[derive(Deserialize)]
+struct MatchedMessage {
+    #[serde(rename = "sessionId")]
+    session_id: i32,
+    #[serde(rename = "messageId")]
+    message_id: i32,
+    role: String,
+    snippet: String,
+}
+
+/// Search the messages of all sessions, or of the sessions over a collection if given.
+///
+/// Every word of the query has to appear in a message for it to match, and the last word
+/// matches as a prefix. Sessions are ordered by their most relevant message.
+pub async fn search(
+    db: &PrismaClient,
+    query: &str,
+    collection_id: Option<i32>,
+) -> crate::Result<Vec<SessionMatch>> {
+    let query = match to_fts_query(query) {
+        Some(query) => query,
+        None => return Ok(vec![]),
+    };
+    let snippet = format!(
+        "snippet(\"SessionMessageFts\", 0, '{}', '{}', '…', {})",
+        HIGHLIGHT_START, HIGHLIGHT_END, SNIPPET_TOKENS
+    );
+    let select = format!(
+        "SELECT m.\"sessionId\" AS \"sessionId\", m.\"id\" AS \"messageId\", m.\"role\" AS \"role\",
+    {} AS \"snippet\"
+FROM \"SessionMessageFts\"
+    JOIN \"SessionMessage\" m ON m.\"id\" = \"SessionMessageFts\".rowid
+    JOIN \"Session\" s ON s.\"id\" = m.\"sessionId\"
+    JOIN \"CollectionIndex\" ci ON ci.\"id\" = s.\"indexId\"
+WHERE \"SessionMessageFts\" MATCH {{}}",
+        snippet
+    );
+    let matches: Vec<MatchedMessage> = match collection_id {
+        Some(collection_id) => {
+            let sql = format!(
+                "{} AND ci.\"collectionId\" = {{}} ORDER BY rank LIMIT {}",
+                select, MAX_MATCHES
+            );
+            db._query_raw(raw!(
+                &sql,
+                PrismaValue::String(query),
+                PrismaValue::Int(collection_id as i64)
+            ))
+            .exec()
+            .await?
+        }
+        None => {
+            let sql = format!("{} ORDER BY rank LIMIT {}", select, MAX_MATCHES);
+            db._query_raw(raw!(&sql, PrismaValue::String(query)))
+                .exec()
+                .await?
+        }
+    };
+
+    let mut sessions: Vec<SessionMatch> = vec![];
+    for matched in matches {
+        let snippet = MessageSnippet {
+            message_id: matched.message_id,
+            role: matched.role,
+            segments: split_snippet(&matched.snippet),
+        };
+        match sessions
+            .iter_mut()
+            .find(|session| session.session_id == matched.session_id)
+        {
+            Some(session) if session.snippets.len() < MAX_SNIPPETS_PER_SESSION => {
+                session.snippets.push(snippet)
+            }
+            Some(_) => {}
+            None => sessions.push(SessionMatch {
+                session_id: matched.session_id,
+                snippets: vec![snippet],
+            }),
+        }
+    }
+    Ok(sessions)
+}
+
+/// Turn free text into an fts5 query, quoting every word so that punctuation in the text cannot
+/// be taken as query syntax.
+fn to_fts_query(text: &str) -> Option<String> {
+    let words = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        return None;
+    }
+    Some(words.join(" ") + "*")
+}
+
+fn split_snippet(snippet: &str) -> Vec<SnippetSegment> {
+    let mut segments = vec![];
+    let mut rest = snippet;
+    while !rest.is_empty() {
+        let (text, after, highlighted) = match rest.find(HIGHLIGHT_START) {
+            Some(0) => {
+                let end = rest.find(HIGHLIGHT_END).unwrap_or(rest.len());
+                let after = (end + HIGHLIGHT_END.len_utf8()).min(rest.len());
+                (&rest[HIGHLIGHT_START.len_utf8()..end], after, true)
+            }
+            Some(start) => (&rest[..start], start, false),
+            None => (rest, rest.len(), false),
+        };
+        if !text.is_empty() {
+            segments.push(SnippetSegment {
+                text: text.to_string(),
+                highlighted,
+            });
+        }
+        rest = &rest[after..];
+    }
+    segments
+}
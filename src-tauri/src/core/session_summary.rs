@@ -0,0 +1,91 @@
+use crate::core::completion::{fill_prompt, ChatMessage, Completion};
+use crate::core::history::QaPair;
+use crate::core::tokenizer::truncate_tokens;
+
+/// Maximum number of questions kept verbatim in the history before the earlier ones are folded
+/// into the summary of the session.
+pub const MAX_UNSUMMARIZED_PAIRS: usize = 6;
+/// Maximum number of tokens of the questions and answers kept verbatim in the history before the
+/// earlier ones are folded into the summary of the session.
+pub const MAX_UNSUMMARIZED_TOKENS: usize = 2000;
+/// Number of the latest questions always kept verbatim when summarizing.
+pub const KEEP_RECENT_PAIRS: usize = 2;
+/// Maximum number of characters of a generated title.
+const MAX_TITLE_LENGTH: usize = 60;
+/// Maximum number of tokens of the first exchange looked at for a title.
+const MAX_TITLE_CONTEXT_TOKENS: usize = 1000;
+
+/// A prompt for naming a conversation after its first exchange.
+const TITLE_PROMPT: &str = "Write a short title of no more than six words for the following \
+conversation. Reply with the title only, without quotes or ending punctuation.
+
+Human: {question}
+Assistant: {answer}
+Title:";
+
+/// A prompt for folding a part of a conversation into the summary of the conversation so far.
+const SUMMARY_PROMPT: &str = "Progressively summarize the lines of a conversation provided, \
+adding onto the previous summary and returning a new summary. Keep the topics, the documents and \
+the conclusions discussed, so that follow up questions can be understood with the summary only.
+
+Current summary:
+{summary}
+
+New lines of conversation:
+{conversation}
+
+New summary:";
+
+/// Whether the questions kept verbatim in the history have grown too long to send in prompts,
+/// given the number of the questions and the tokens of them and their answers.
+pub fn needs_summary(num_pairs: usize, tokens: usize) -> bool {
+    num_pairs > MAX_UNSUMMARIZED_PAIRS || tokens > MAX_UNSUMMARIZED_TOKENS
+}
+
+/// Generate a title for a conversation from its first question and answer.
+pub async fn generate_title(pair: &QaPair, completion: &Completion) -> crate::Result<String> {
+    let prompt = fill_prompt(
+        TITLE_PROMPT,
+        &[
+            (
+                "question",
+                &truncate_tokens(&pair.question, MAX_TITLE_CONTEXT_TOKENS),
+            ),
+            (
+                "answer",
+                &truncate_tokens(&pair.answer, MAX_TITLE_CONTEXT_TOKENS),
+            ),
+        ],
+    );
+    let title = completion.complete(&[ChatMessage::user(prompt)]).await?;
+    let title = title
+        .trim()
+        .trim_matches(|c: char| c == '"' || c == '\'' || c == '.')
+        .trim();
+    Ok(match title.char_indices().nth(MAX_TITLE_LENGTH) {
+        Some((end, _)) => title[..end].trim_end().to_string(),
+        None => title.to_string(),
+    })
+}
+
+/// Fold questions and answers into the summary of the conversation before them.
+pub async fn summarize(
+    summary: &str,
+    pairs: &[QaPair],
+    completion: &Completion,
+) -> crate::Result<String> {
+    let conversation = pairs
+        .iter()
+        .map(|pair| format!("Human: {}\nAssistant: {}", pair.question, pair.answer))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let prompt = fill_prompt(
+        SUMMARY_PROMPT,
+        &[("summary", summary), ("conversation", &conversation)],
+    );
+    Ok(completion
+        .complete(&[ChatMessage::user(prompt)])
+        .await?
+        .trim()
+        .to_string())
+}
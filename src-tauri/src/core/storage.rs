@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Serialize;
+use specta::Type;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::core::fs::{hash_file, HashAlgo};
+use crate::core::result::Error;
+use crate::prisma::document;
+
+/// Maximum number of files hashed at the same time.
+const MAX_CONCURRENT_HASHES: usize = 4;
+
+#[derive(Serialize, Type, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    Ok,
+    Missing,
+    /// The file is there, but its content does not match the hash of the document.
+    Corrupted,
+    /// The file is there, but cannot be read.
+    Unreadable,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct DocumentCheck {
+    #[serde(rename = "documentId")]
+    pub document_id: i32,
+    pub filename: String,
+    pub filepath: String,
+    pub status: FileStatus,
+    /// The hash of the content found, if the file is corrupted.
+    #[serde(rename = "actualHash")]
+    pub actual_hash: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Type, Debug)]
+pub struct VerificationReport {
+    pub checked: i32,
+    pub ok: i32,
+    /// The documents whose files are not ok.
+    pub problems: Vec<DocumentCheck>,
+}
+
+/// The status of a stored file, with the hash of its content if it is corrupted, and the error if
+/// it is unreadable.
+type FileResult = (FileStatus, Option<String>, Option<String>);
+
+async fn check_file(filepath: String, hash_algo: String, expected_hash: String) -> FileResult {
+    let hash_algo = match HashAlgo::of_str(&hash_algo) {
+        Ok(hash_algo) => hash_algo,
+        Err(err) => return (FileStatus::Unreadable, None, Some(err.to_string())),
+    };
+    match hash_file(hash_algo, &filepath).await {
+        Ok(hash) if hash == expected_hash => (FileStatus::Ok, None, None),
+        Ok(hash) => (FileStatus::Corrupted, Some(hash), None),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => (FileStatus::Missing, None, None),
+        Err(err) => (FileStatus::Unreadable, None, Some(err.to_string())),
+    }
+}
+
+/// Hash the stored files of the documents again, several at a time, and check them against the
+/// hashes of the documents.
+///
+/// A file shared by documents of the same content is hashed once.
+pub async fn verify(documents: Vec<document::Data>) -> crate::Result<VerificationReport> {
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_HASHES));
+    let mut tasks = JoinSet::new();
+    let mut files = HashMap::new();
+    for doc in &documents {
+        let key = (
+            doc.filepath.clone(),
+            doc.hash_algo.clone(),
+            doc.md_5_hash.clone(),
+        );
+        if files.insert(key.clone(), None).is_none() {
+            let semaphore = semaphore.clone();
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let result = check_file(key.0.clone(), key.1.clone(), key.2.clone()).await;
+                (key, result)
+            });
+        }
+    }
+    while let Some(joined) = tasks.join_next().await {
+        let (key, result) = joined?;
+        files.insert(key, Some(result));
+    }
+
+    let checked = documents.len() as i32;
+    let mut problems = vec![];
+    for doc in documents {
+        let (status, actual_hash, message) = files
+            .get(&(
+                doc.filepath.clone(),
+                doc.hash_algo.clone(),
+                doc.md_5_hash.clone(),
+            ))
+            .cloned()
+            .flatten()
+            .unwrap_or((FileStatus::Ok, None, None));
+        if status != FileStatus::Ok {
+            problems.push(DocumentCheck {
+                document_id: doc.id,
+                filename: doc.filename,
+                filepath: doc.filepath,
+                status,
+                actual_hash,
+                message,
+            });
+        }
+    }
+    Ok(VerificationReport {
+        checked,
+        ok: checked - problems.len() as i32,
+        problems,
+    })
+}
+
+/// Copy a replacement of the stored file of content `expected_hash` by `hash_algo` into
+/// `upload_dir`, if it has the same content, returning where it is stored.
+pub async fn store_replacement(
+    replacement: &Path,
+    hash_algo: HashAlgo,
+    expected_hash: &str,
+    upload_dir: &Path,
+) -> crate::Result<String> {
+    let hash = hash_file(hash_algo, replacement).await?;
+    if hash != expected_hash {
+        return Err(Error::msg(format!(
+            "The content of {} does not match the document, whose hash is {} instead of {}",
+            replacement.display(),
+            hash,
+            expected_hash
+        )));
+    }
+    let target_path = upload_dir.join(expected_hash);
+    // copy to a partial file first, so that the broken file is only replaced by a complete one
+    let partial_path = target_path.with_extension("part");
+    tokio::fs::copy(replacement, &partial_path).await?;
+    tokio::fs::rename(&partial_path, &target_path).await?;
+    Ok(target_path.to_string_lossy().to_string())
+}
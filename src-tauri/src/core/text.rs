@@ -0,0 +1,19 @@
+use std::path::Path;
+
+/// Extract the text of a stored document, by the extension of its filename.
+///
+/// The text of a pdf is extracted page by page, and other documents are read as text. It blocks
+/// until done.
+pub fn extract_text(filepath: &Path, filename: &str) -> crate::Result<String> {
+    let is_pdf = Path::new(filename)
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("pdf"))
+        .unwrap_or(false);
+    if is_pdf {
+        let pdf = lopdf::Document::load(filepath)?;
+        let pages = pdf.get_pages().into_keys().collect::<Vec<_>>();
+        Ok(pdf.extract_text(&pages)?)
+    } else {
+        Ok(String::from_utf8_lossy(&std::fs::read(filepath)?).to_string())
+    }
+}
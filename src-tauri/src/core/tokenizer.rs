@@ -0,0 +1,100 @@
+use tiktoken_rs::cl100k_base_singleton;
+
+use crate::core::completion::ChatMessage;
+
+/// Tokens taken by the role and the separators of each chat message.
+const TOKENS_PER_MESSAGE: usize = 4;
+/// Tokens priming the reply of the assistant after the chat messages.
+const TOKENS_PER_REPLY: usize = 3;
+/// Tokens reserved for the answer when the maximum tokens of a completion are not configured.
+const DEFAULT_COMPLETION_TOKENS: usize = 1024;
+
+/// Count the tokens of a text by the cl100k encoding used by the chat and embedding models.
+pub fn count_tokens(text: &str) -> usize {
+    cl100k_base_singleton().lock().encode_ordinary(text).len()
+}
+
+/// Count the tokens of chat messages sent in a completion request, including the overheads of
+/// the chat format.
+pub fn count_message_tokens(messages: &[ChatMessage]) -> usize {
+    messages
+        .iter()
+        .map(|message| TOKENS_PER_MESSAGE + count_tokens(&message.content))
+        .sum::<usize>()
+        + TOKENS_PER_REPLY
+}
+
+/// Cut a text down to its first `max_tokens` tokens.
+pub fn truncate_tokens(text: &str, max_tokens: usize) -> String {
+    let bpe = cl100k_base_singleton();
+    let bpe = bpe.lock();
+    let tokens = bpe.encode_ordinary(text);
+    if tokens.len() <= max_tokens {
+        return text.to_string();
+    }
+    // the cut may split a multi-byte character, whose leftover bytes are dropped
+    String::from_utf8_lossy(&bpe._decode_native(&tokens[..max_tokens]))
+        .trim_end_matches(char::REPLACEMENT_CHARACTER)
+        .to_string()
+}
+
+/// The token budget of a prompt sent to a completion model.
+#[derive(Clone, Copy, Debug)]
+pub struct ContextBudget {
+    /// Size of the context window of the model, shared by the prompt and the answer.
+    pub context_size: usize,
+    /// Tokens reserved for the answer.
+    pub completion_tokens: usize,
+}
+
+impl ContextBudget {
+    pub fn for_model(model: &str, max_tokens: Option<i32>) -> Self {
+        let context_size = tiktoken_rs::model::get_context_size(model);
+        let completion_tokens = max_tokens
+            .map(|max_tokens| max_tokens.max(0) as usize)
+            .unwrap_or(DEFAULT_COMPLETION_TOKENS)
+            .min(context_size / 2);
+        Self {
+            context_size,
+            completion_tokens,
+        }
+    }
+
+    /// Tokens left for the prompt.
+    pub fn prompt_tokens(&self) -> usize {
+        self.context_size - self.completion_tokens
+    }
+
+    /// Tokens left for the prompt after `used` tokens.
+    pub fn remaining(&self, used: usize) -> usize {
+        self.prompt_tokens().saturating_sub(used)
+    }
+}
+
+/// Drop items from the end of `items` until the tokens of the rest fit in `available`.
+///
+/// At least one item is kept, cut down to fit by `truncate`, so that there is something left
+/// to work with.
+pub fn fit_tokens<T, C, F>(items: &mut Vec<T>, available: usize, count: C, truncate: F)
+where
+    C: Fn(&T) -> usize,
+    F: FnOnce(&mut T, usize),
+{
+    let mut used = 0;
+    let mut keep = 0;
+    for item in items.iter() {
+        let tokens = count(item);
+        if used + tokens > available {
+            break;
+        }
+        used += tokens;
+        keep += 1;
+    }
+    if keep == 0 {
+        if let Some(first) = items.first_mut() {
+            truncate(first, available);
+            keep = 1;
+        }
+    }
+    items.truncate(keep);
+}
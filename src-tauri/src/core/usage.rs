@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::prisma::{usage_record, PrismaClient};
+
+/// Prices in dollars of the models known by default, as `(model prefix, price)`.
+///
+/// More specific prefixes come first since the first matching one is taken.
+const DEFAULT_PRICES: [(&str, Price); 6] = [
+    ("text-embedding-ada-002", Price::new(0.0001, 0.0)),
+    ("gpt-3.5-turbo-16k", Price::new(0.003, 0.004)),
+    ("gpt-3.5-turbo", Price::new(0.0015, 0.002)),
+    ("gpt-4-32k", Price::new(0.06, 0.12)),
+    ("gpt-4", Price::new(0.03, 0.06)),
+    ("text-davinci", Price::new(0.02, 0.02)),
+];
+
+/// Price of a model in dollars per thousand tokens.
+#[derive(Serialize, Deserialize, Type, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Price {
+    #[serde(rename = "promptPer1k")]
+    pub prompt_per_1k: f64,
+    #[serde(rename = "completionPer1k", default)]
+    pub completion_per_1k: f64,
+}
+
+impl Price {
+    pub const fn new(prompt_per_1k: f64, completion_per_1k: f64) -> Self {
+        Self {
+            prompt_per_1k,
+            completion_per_1k,
+        }
+    }
+
+    /// The price of `model` in `prices`, falling back to the price known by default.
+    pub fn of_model(prices: Option<&HashMap<String, Price>>, model: &str) -> Self {
+        prices
+            .and_then(|prices| prices.get(model).copied())
+            .or_else(|| {
+                DEFAULT_PRICES
+                    .iter()
+                    .find(|(prefix, _)| model.starts_with(prefix))
+                    .map(|(_, price)| *price)
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn cost(&self, prompt_tokens: i32, completion_tokens: i32) -> f64 {
+        (prompt_tokens as f64 * self.prompt_per_1k
+            + completion_tokens as f64 * self.completion_per_1k)
+            / 1000.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsageKind {
+    Embeddings,
+    Completion,
+}
+
+impl UsageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageKind::Embeddings => "embeddings",
+            UsageKind::Completion => "completion",
+        }
+    }
+}
+
+/// Tokens consumed by a call to an embeddings or completion service, and what they cost.
+#[derive(Clone, Debug)]
+pub struct Usage {
+    pub kind: UsageKind,
+    pub model: String,
+    pub prompt_tokens: i32,
+    pub completion_tokens: i32,
+    pub cost: f64,
+}
+
+impl Usage {
+    pub fn new(
+        kind: UsageKind,
+        model: &str,
+        price: &Price,
+        prompt_tokens: i32,
+        completion_tokens: i32,
+    ) -> Self {
+        Self {
+            kind,
+            model: model.to_string(),
+            prompt_tokens,
+            completion_tokens,
+            cost: price.cost(prompt_tokens, completion_tokens),
+        }
+    }
+}
+
+/// Usages of the calls made by a client, kept until they are recorded.
+#[derive(Default)]
+pub struct UsageLog(Mutex<Vec<Usage>>);
+
+impl UsageLog {
+    pub fn push(&self, usage: Usage) {
+        self.0.lock().unwrap().push(usage);
+    }
+
+    /// Take the usages logged so far, leaving the log empty.
+    pub fn take(&self) -> Vec<Usage> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// What the usages are spent for.
+///
+/// The ids are kept as plain values rather than relations, so that the records stay after the
+/// clients, profiles or sessions are deleted.
+#[derive(Clone, Debug, Default)]
+pub struct UsageScope {
+    pub embeddings_client_id: Option<i32>,
+    pub completion_client_id: Option<i32>,
+    pub index_profile_id: Option<i32>,
+    pub session_id: Option<i32>,
+}
+
+/// Record usages in the `UsageRecord` table.
+pub async fn record(
+    db: &PrismaClient,
+    usages: Vec<Usage>,
+    scope: &UsageScope,
+) -> crate::Result<()> {
+    if usages.is_empty() {
+        return Ok(());
+    }
+    db._batch(usages.into_iter().map(|usage| {
+        db.usage_record().create(
+            usage.kind.as_str().to_string(),
+            usage.model,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            usage.cost,
+            vec![
+                usage_record::embeddings_client_id::set(scope.embeddings_client_id),
+                usage_record::completion_client_id::set(scope.completion_client_id),
+                usage_record::index_profile_id::set(scope.index_profile_id),
+                usage_record::session_id::set(scope.session_id),
+            ],
+        )
+    }))
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use specta::Type;
+
+/// A problem of a single field of a payload, such as `info.apiKey`.
+#[derive(Serialize, Type, Clone, Debug)]
+pub struct FieldError {
+    /// Path of the field, with nested fields joined by dots.
+    pub field: String,
+    pub message: String,
+}
+
+/// Field-level problems of a payload, collected so that all of them can be reported at once.
+///
+/// It is serialized to the frontend as `{ description, fieldErrors }`, so that forms can point
+/// to the fields while other error handlers keep showing the description.
+#[derive(Debug, Default)]
+pub struct ValidationError {
+    errors: Vec<FieldError>,
+}
+
+impl ValidationError {
+    pub fn add<F: Into<String>, M: Into<String>>(&mut self, field: F, message: M) {
+        self.errors.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    pub fn errors(&self) -> &[FieldError] {
+        &self.errors
+    }
+
+    /// Require a text field to be filled.
+    pub fn require(&mut self, field: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.add(field, "is required");
+        }
+    }
+
+    /// Require an optional text field to be filled if given.
+    pub fn require_some(&mut self, field: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.require(field, value);
+        }
+    }
+
+    /// Require an optional field to be an http(s) url if given.
+    pub fn check_url(&mut self, field: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            match reqwest::Url::parse(value) {
+                Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+                _ => self.add(field, "must be an http(s) url"),
+            }
+        }
+    }
+
+    /// Require an optional number to be positive if given.
+    pub fn check_positive(&mut self, field: &str, value: Option<i32>) {
+        if matches!(value, Some(value) if value <= 0) {
+            self.add(field, "must be positive");
+        }
+    }
+
+    /// Fail with the problems collected, if any.
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors = self
+            .errors
+            .iter()
+            .map(|error| format!("{} {}", error.field, error.message))
+            .collect::<Vec<_>>()
+            .join("; ");
+        write!(f, "Invalid fields: {}", errors)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl Serialize for ValidationError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("ValidationError", 2)?;
+        state.serialize_field("description", &self.to_string())?;
+        state.serialize_field("fieldErrors", &self.errors)?;
+        state.end()
+    }
+}
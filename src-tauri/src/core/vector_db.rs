@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::core::client_types::{ClientPayload, VectorDbClientInfo};
+use crate::core::http::ensure_success;
+use crate::core::result::Error;
+use crate::core::secrets;
+use crate::prisma::{vector_db_client, vector_db_config};
+
+/// A vector database client built from a `VectorDbClient` and a `VectorDbConfig`.
+pub enum VectorDb {
+    Pinecone {
+        api_key: String,
+        environment: String,
+        index_name: String,
+        http: reqwest::Client,
+    },
+}
+
+/// Number of vectors sent per upsert request.
+const UPSERT_BATCH_SIZE: usize = 100;
+
+/// A vector to be stored with its metadata.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct Vector {
+    pub id: String,
+    pub values: Vec<f32>,
+    /// Metadata stored along the vector, flattened before upserting since nested objects are not
+    /// supported.
+    #[serde(default)]
+    pub metadata: serde_json::Value,
+}
+
+/// A vector matched by a similarity query.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ScoredVector {
+    pub id: String,
+    pub score: f32,
+    #[serde(default)]
+    pub metadata: serde_json::Value,
+}
+
+/// What a vector database says about the index vectors are stored in.
+#[derive(Debug, Clone)]
+pub struct IndexDescription {
+    pub dimension: i32,
+    pub metric: Option<String>,
+    /// Whether the index is ready to be queried and upserted.
+    pub ready: bool,
+}
+
+#[derive(Deserialize)]
+struct PineconeWhoAmI {
+    project_name: String,
+}
+
+#[derive(Serialize)]
+struct PineconeQueryRequest<'a> {
+    namespace: &'a str,
+    vector: &'a [f32],
+    #[serde(rename = "topK")]
+    top_k: usize,
+    #[serde(rename = "includeMetadata")]
+    include_metadata: bool,
+}
+
+#[derive(Serialize)]
+struct PineconeUpsertRequest<'a> {
+    namespace: &'a str,
+    vectors: &'a [Vector],
+}
+
+#[derive(Serialize)]
+struct PineconeDeleteRequest<'a> {
+    namespace: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ids: Option<&'a [String]>,
+    #[serde(rename = "deleteAll")]
+    delete_all: bool,
+}
+
+#[derive(Serialize)]
+struct PineconeCreateIndexRequest<'a> {
+    name: &'a str,
+    dimension: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metric: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct PineconeDescribeResponse {
+    database: PineconeDatabase,
+    status: Option<PineconeIndexStatus>,
+}
+
+#[derive(Deserialize)]
+struct PineconeDatabase {
+    dimension: i32,
+    metric: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PineconeIndexStatus {
+    #[serde(default)]
+    ready: bool,
+}
+
+#[derive(Deserialize)]
+struct PineconeQueryResponse {
+    #[serde(default)]
+    matches: Vec<ScoredVector>,
+}
+
+impl VectorDb {
+    pub fn from_data(
+        client: &vector_db_client::Data,
+        config: &vector_db_config::Data,
+    ) -> crate::Result<Self> {
+        if client.r#type != config.client_type {
+            return Err(Error::msg(format!(
+                "Vector db client type {} does not match config type {}",
+                client.r#type, config.client_type
+            )));
+        }
+        Self::from_client(client)
+    }
+
+    /// Build a client of the index in the client info, which needs no config.
+    pub fn from_client(client: &vector_db_client::Data) -> crate::Result<Self> {
+        let info =
+            VectorDbClientInfo::from_stored(&client.r#type, &secrets::reveal_info(&client.info)?)?;
+        match info {
+            VectorDbClientInfo::Pinecone(info) => Ok(VectorDb::Pinecone {
+                api_key: info.api_key,
+                environment: info.environment,
+                index_name: info.index_name,
+                http: reqwest::Client::new(),
+            }),
+        }
+    }
+
+    /// Query the `top_k` vectors most similar to `vector` in the given namespace.
+    pub async fn query(
+        &self,
+        namespace: &str,
+        vector: &[f32],
+        top_k: usize,
+    ) -> crate::Result<Vec<ScoredVector>> {
+        match self {
+            VectorDb::Pinecone { api_key, http, .. } => {
+                let response = http
+                    .post(format!("{}/query", self.pinecone_index_url().await?))
+                    .header("Api-Key", api_key)
+                    .json(&PineconeQueryRequest {
+                        namespace,
+                        vector,
+                        top_k,
+                        include_metadata: true,
+                    })
+                    .send()
+                    .await?;
+                Ok(ensure_success(response)
+                    .await?
+                    .json::<PineconeQueryResponse>()
+                    .await?
+                    .matches)
+            }
+        }
+    }
+
+    /// Upsert vectors into the given namespace, in batches.
+    pub async fn upsert(&self, namespace: &str, vectors: Vec<Vector>) -> crate::Result<()> {
+        let vectors = vectors
+            .into_iter()
+            .map(|vector| Vector {
+                metadata: flatten_metadata(vector.metadata),
+                ..vector
+            })
+            .collect::<Vec<_>>();
+        match self {
+            VectorDb::Pinecone { api_key, http, .. } => {
+                let url = format!("{}/vectors/upsert", self.pinecone_index_url().await?);
+                for batch in vectors.chunks(UPSERT_BATCH_SIZE) {
+                    let response = http
+                        .post(&url)
+                        .header("Api-Key", api_key)
+                        .json(&PineconeUpsertRequest {
+                            namespace,
+                            vectors: batch,
+                        })
+                        .send()
+                        .await?;
+                    ensure_success(response).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Delete the vectors of the given ids from the namespace, or all of its vectors if no ids
+    /// are given.
+    pub async fn delete(&self, namespace: &str, ids: Option<&[String]>) -> crate::Result<()> {
+        if matches!(ids, Some(ids) if ids.is_empty()) {
+            return Ok(());
+        }
+        match self {
+            VectorDb::Pinecone { api_key, http, .. } => {
+                let response = http
+                    .post(format!(
+                        "{}/vectors/delete",
+                        self.pinecone_index_url().await?
+                    ))
+                    .header("Api-Key", api_key)
+                    .json(&PineconeDeleteRequest {
+                        namespace,
+                        ids,
+                        delete_all: ids.is_none(),
+                    })
+                    .send()
+                    .await?;
+                ensure_success(response).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Create the index of the client if it does not exist yet.
+    pub async fn ensure_index(&self, dimension: i32, metric: Option<&str>) -> crate::Result<()> {
+        match self {
+            VectorDb::Pinecone {
+                api_key,
+                environment,
+                index_name,
+                http,
+            } => {
+                let url = format!("https://controller.{}.pinecone.io/databases", environment);
+                let response = http.get(&url).header("Api-Key", api_key).send().await?;
+                let indexes = ensure_success(response)
+                    .await?
+                    .json::<Vec<String>>()
+                    .await?;
+                if indexes.contains(index_name) {
+                    return Ok(());
+                }
+                let response = http
+                    .post(&url)
+                    .header("Api-Key", api_key)
+                    .json(&PineconeCreateIndexRequest {
+                        name: index_name,
+                        dimension,
+                        metric,
+                    })
+                    .send()
+                    .await?;
+                ensure_success(response).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Describe the index of the client.
+    pub async fn describe_index(&self) -> crate::Result<IndexDescription> {
+        match self {
+            VectorDb::Pinecone {
+                api_key,
+                environment,
+                index_name,
+                http,
+            } => {
+                let response = http
+                    .get(format!(
+                        "https://controller.{}.pinecone.io/databases/{}",
+                        environment, index_name
+                    ))
+                    .header("Api-Key", api_key)
+                    .send()
+                    .await?;
+                let description = ensure_success(response)
+                    .await?
+                    .json::<PineconeDescribeResponse>()
+                    .await?;
+                Ok(IndexDescription {
+                    dimension: description.database.dimension,
+                    metric: description.database.metric,
+                    ready: matches!(
+                        description.status,
+                        Some(PineconeIndexStatus { ready: true })
+                    ),
+                })
+            }
+        }
+    }
+
+    /// Resolve the data plane url of the pinecone index.
+    ///
+    /// The url embeds the project name, which is only exposed by the `whoami` action of the
+    /// controller api.
+    async fn pinecone_index_url(&self) -> crate::Result<String> {
+        match self {
+            VectorDb::Pinecone {
+                api_key,
+                environment,
+                index_name,
+                http,
+            } => {
+                let response = http
+                    .get(format!(
+                        "https://controller.{}.pinecone.io/actions/whoami",
+                        environment
+                    ))
+                    .header("Api-Key", api_key)
+                    .send()
+                    .await?;
+                let whoami = ensure_success(response)
+                    .await?
+                    .json::<PineconeWhoAmI>()
+                    .await?;
+                Ok(format!(
+                    "https://{}-{}.svc.{}.pinecone.io",
+                    index_name, whoami.project_name, environment
+                ))
+            }
+        }
+    }
+}
+
+/// Flatten nested objects of the metadata into dotted keys, dropping nulls and empty objects.
+fn flatten_metadata(metadata: serde_json::Value) -> serde_json::Value {
+    fn flatten_into(
+        prefix: Option<&str>,
+        value: serde_json::Value,
+        flat: &mut serde_json::Map<String, serde_json::Value>,
+    ) {
+        match value {
+            serde_json::Value::Object(object) => {
+                for (key, value) in object {
+                    let key = match prefix {
+                        Some(prefix) => format!("{}.{}", prefix, key),
+                        None => key,
+                    };
+                    flatten_into(Some(&key), value, flat);
+                }
+            }
+            serde_json::Value::Null => {}
+            value => {
+                if let Some(prefix) = prefix {
+                    flat.insert(prefix.to_string(), value);
+                }
+            }
+        }
+    }
+
+    let mut flat = serde_json::Map::new();
+    flatten_into(None, metadata, &mut flat);
+    flat.into()
+}
@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tauri::{AppHandle, Manager};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::core::linked_documents::{self, DocumentChange};
+use crate::core::workspace::Workspaces;
+use crate::prisma::{document, PrismaClient};
+
+/// Event emitted with a `DocumentChange` whenever the source file of a linked document changes.
+pub const DOCUMENT_CHANGED_EVENT: &str = "document://changed";
+/// How long the changes of a file have to settle before it is synced, since editors write files
+/// in several steps.
+const SETTLE_DELAY: Duration = Duration::from_millis(1000);
+
+/// Watcher of the source files of the linked documents of the active workspace, managed as a
+/// tauri state.
+///
+/// The folders of the source files are watched rather than the files, since editors often save
+/// a file by replacing it, which ends the watch of the file.
+pub struct DocumentWatcher {
+    app: AppHandle,
+    watcher: Mutex<RecommendedWatcher>,
+    dirs: Mutex<HashSet<PathBuf>>,
+    /// The paths changed, taken by `handle_changes` once the watcher is managed.
+    changes: Mutex<Option<UnboundedReceiver<PathBuf>>>,
+    /// Held while syncing, so that a change found by the watcher and by a manual sync at the same
+    /// time is recorded once.
+    syncing: tokio::sync::Mutex<()>,
+}
+
+fn is_content_change(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Data(_))
+            | EventKind::Modify(ModifyKind::Name(_))
+            | EventKind::Modify(ModifyKind::Any)
+    )
+}
+
+impl DocumentWatcher {
+    /// Start watching, with no folder watched until `rewatch`.
+    ///
+    /// The changes are queued until `handle_changes` is called, which has to be after the watcher
+    /// and the workspaces are managed.
+    pub fn start(app: AppHandle) -> crate::Result<Self> {
+        let (sender, receiver) = unbounded_channel();
+        let watcher =
+            notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+                Ok(event) if is_content_change(&event.kind) => {
+                    for path in event.paths {
+                        let _ = sender.send(path);
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => log::error!("error while watching linked documents: {}", err),
+            })?;
+        Ok(Self {
+            app,
+            watcher: Mutex::new(watcher),
+            dirs: Mutex::new(HashSet::new()),
+            changes: Mutex::new(Some(receiver)),
+            syncing: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Watch the folders of the source files of the linked documents in `db`, and stop watching
+    /// the others.
+    ///
+    /// Called whenever the linked documents or the active workspace change.
+    pub async fn rewatch(&self, db: &PrismaClient) -> crate::Result<()> {
+        let dirs = db
+            .document()
+            .find_many(vec![document::source_path::not(None)])
+            .exec()
+            .await?
+            .into_iter()
+            .filter_map(|doc| doc.source_path)
+            .filter_map(|path| Path::new(&path).parent().map(Path::to_path_buf))
+            .collect::<HashSet<_>>();
+
+        let mut watcher = self.watcher.lock().unwrap();
+        let mut watched = self.dirs.lock().unwrap();
+        for dir in watched.difference(&dirs) {
+            let _ = watcher.unwatch(dir);
+        }
+        for dir in dirs.difference(&watched) {
+            // the folder may be gone, which is watched again once its documents are relinked
+            if let Err(err) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                log::warn!("error while watching {}: {}", dir.display(), err);
+            }
+        }
+        *watched = dirs;
+        Ok(())
+    }
+
+    /// Sync the linked documents of the files changed in the background, once their changes
+    /// settle.
+    pub fn handle_changes(&self) {
+        if let Some(receiver) = self.changes.lock().unwrap().take() {
+            tauri::async_runtime::spawn(handle_changes(self.app.clone(), receiver));
+        }
+    }
+
+    /// Sync the linked documents in `db` matching `filters`, storing their new contents into
+    /// `upload_dir` and emitting a `document://changed` event for each document changed.
+    pub async fn sync(
+        &self,
+        db: &PrismaClient,
+        upload_dir: &Path,
+        mut filters: Vec<document::WhereParam>,
+    ) -> crate::Result<Vec<DocumentChange>> {
+        let _syncing = self.syncing.lock().await;
+        filters.push(document::source_path::not(None));
+        let documents = db.document().find_many(filters).exec().await?;
+        tokio::fs::create_dir_all(upload_dir).await?;
+
+        let mut changes = vec![];
+        for doc in documents {
+            if let Some(change) = linked_documents::sync(db, upload_dir, doc).await? {
+                let _ = self.app.emit_all(DOCUMENT_CHANGED_EVENT, &change);
+                changes.push(change);
+            }
+        }
+        Ok(changes)
+    }
+}
+
+/// Sync the linked documents of the files changed, once their changes settle.
+async fn handle_changes(app: AppHandle, mut receiver: UnboundedReceiver<PathBuf>) {
+    while let Some(path) = receiver.recv().await {
+        let mut paths = HashSet::from([path]);
+        while let Ok(Some(path)) = tokio::time::timeout(SETTLE_DELAY, receiver.recv()).await {
+            paths.insert(path);
+        }
+        let source_paths = paths
+            .into_iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        let watcher = app.state::<DocumentWatcher>();
+        let (db, upload_dir) = app.state::<Workspaces>().active_with_upload_dir();
+        if let Err(err) = watcher
+            .sync(
+                db,
+                &upload_dir,
+                vec![document::source_path::in_vec(source_paths)],
+            )
+            .await
+        {
+            log::error!("error while syncing linked documents: {}", err);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+use crate::core::backup::{self, BackupKind};
+use crate::core::result::Error;
+use crate::core::secrets;
+use crate::core::validation::ValidationError;
+use crate::prisma::PrismaClient;
+
+/// The workspace of the files from before there are workspaces, which stay where they are.
+pub const DEFAULT_WORKSPACE: &str = "default";
+const DB_NAME: &str = "dev.db";
+const UPLOAD_DIR: &str = "uploaded";
+/// Folder of the workspaces other than the default one, in both the config and the data dirs.
+const WORKSPACES_DIR: &str = "workspaces";
+/// File in the config dir remembering the active workspace across restarts.
+const SETTINGS_FILE: &str = "workspace.json";
+const MAX_NAME_LENGTH: usize = 64;
+/// The schema the databases are prepared with.
+const SCHEMA: &str = include_str!("../../prisma/schema.prisma");
+/// Suffix of the file next to a database recording the hash of the schema it was prepared with.
+const SCHEMA_STAMP_SUFFIX: &str = ".schema";
+
+#[derive(Serialize, Deserialize, Default)]
+struct WorkspaceSettings {
+    active: Option<String>,
+}
+
+#[derive(Serialize, Type, Clone, Debug)]
+pub struct WorkspaceInfo {
+    pub name: String,
+    pub active: bool,
+}
+
+/// Where the files of the workspaces are kept.
+#[derive(Clone, Debug)]
+pub struct WorkspaceDirs {
+    pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
+}
+
+impl WorkspaceDirs {
+    pub fn of_app(config: &tauri::Config) -> crate::Result<Self> {
+        Ok(Self {
+            config_dir: tauri::api::path::app_config_dir(config)
+                .ok_or_else(|| Error::msg("Failed to get app config dir"))?,
+            data_dir: tauri::api::path::app_local_data_dir(config)
+                .ok_or_else(|| Error::msg("Failed to get app local data dir"))?,
+        })
+    }
+
+    fn dir_of(root: &Path, name: &str) -> PathBuf {
+        if name == DEFAULT_WORKSPACE {
+            root.to_path_buf()
+        } else {
+            root.join(WORKSPACES_DIR).join(name)
+        }
+    }
+
+    /// The database file of a workspace, next to which its backups are kept.
+    pub fn db_path(&self, name: &str) -> PathBuf {
+        Self::dir_of(&self.config_dir, name).join(DB_NAME)
+    }
+
+    /// The folder the documents of a workspace are uploaded to.
+    pub fn upload_dir(&self, name: &str) -> PathBuf {
+        Self::dir_of(&self.data_dir, name).join(UPLOAD_DIR)
+    }
+
+    /// Whether a workspace is listed, which rules out names of paths out of the workspaces dir.
+    fn exists(&self, name: &str) -> bool {
+        matches!(self.names(), Ok(names) if names.iter().any(|other| other == name))
+    }
+
+    /// Names of the workspaces, the default one first.
+    fn names(&self) -> crate::Result<Vec<String>> {
+        let mut names = vec![];
+        let dir = self.config_dir.join(WORKSPACES_DIR);
+        if dir.exists() {
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    names.push(entry.file_name().to_string_lossy().to_string());
+                }
+            }
+        }
+        names.sort();
+        names.insert(0, DEFAULT_WORKSPACE.to_string());
+        Ok(names)
+    }
+
+    /// The workspace remembered as active, or the default one if it is gone.
+    fn load_active(&self) -> String {
+        std::fs::read_to_string(self.config_dir.join(SETTINGS_FILE))
+            .ok()
+            .and_then(|text| serde_json::from_str::<WorkspaceSettings>(&text).ok())
+            .and_then(|settings| settings.active)
+            .filter(|name| self.exists(name))
+            .unwrap_or_else(|| DEFAULT_WORKSPACE.to_string())
+    }
+
+    fn save_active(&self, name: &str) -> crate::Result<()> {
+        let settings = WorkspaceSettings {
+            active: Some(name.to_string()),
+        };
+        std::fs::write(
+            self.config_dir.join(SETTINGS_FILE),
+            serde_json::to_string_pretty(&settings)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// The workspaces of the app with the database of the active one, managed as a tauri state.
+///
+/// Commands bind the client of the active workspace once at their start, so that all their
+/// queries go to the same workspace even if another one is made active meanwhile. The clients of
+/// the workspaces opened are kept for the lifetime of the app, which makes switching back cheap
+/// and lets the commands in progress on a workspace switched away from finish.
+pub struct Workspaces {
+    dirs: WorkspaceDirs,
+    active: RwLock<(String, &'static PrismaClient)>,
+    opened: tokio::sync::Mutex<HashMap<String, &'static PrismaClient>>,
+}
+
+impl Workspaces {
+    /// Open the workspace remembered as active.
+    pub async fn open_active(dirs: WorkspaceDirs) -> crate::Result<Self> {
+        let name = dirs.load_active();
+        let db: &'static PrismaClient = Box::leak(Box::new(open_db(&dirs.db_path(&name)).await?));
+        Ok(Self {
+            dirs,
+            active: RwLock::new((name.clone(), db)),
+            opened: tokio::sync::Mutex::new(HashMap::from([(name, db)])),
+        })
+    }
+
+    /// The client of the database of the active workspace.
+    pub fn active(&self) -> &'static PrismaClient {
+        self.active.read().unwrap().1
+    }
+
+    /// The client of the active workspace together with its database file.
+    pub fn active_with_db_path(&self) -> (&'static PrismaClient, PathBuf) {
+        let active = self.active.read().unwrap();
+        (active.1, self.dirs.db_path(&active.0))
+    }
+
+    /// The client of the active workspace together with the folder its documents are uploaded
+    /// to.
+    pub fn active_with_upload_dir(&self) -> (&'static PrismaClient, PathBuf) {
+        let active = self.active.read().unwrap();
+        (active.1, self.dirs.upload_dir(&active.0))
+    }
+
+    /// Name of the active workspace.
+    pub fn name(&self) -> String {
+        self.active.read().unwrap().0.clone()
+    }
+
+    /// The database file of the active workspace.
+    pub fn db_path(&self) -> PathBuf {
+        self.dirs.db_path(&self.name())
+    }
+
+    pub fn list(&self) -> crate::Result<Vec<WorkspaceInfo>> {
+        let active = self.name();
+        Ok(self
+            .dirs
+            .names()?
+            .into_iter()
+            .map(|name| WorkspaceInfo {
+                active: name == active,
+                name,
+            })
+            .collect())
+    }
+
+    /// Create a workspace with an empty database, without switching to it.
+    pub async fn create(&self, name: &str) -> crate::Result<WorkspaceInfo> {
+        let mut errors = ValidationError::default();
+        errors.require("name", name);
+        if name.len() > MAX_NAME_LENGTH {
+            errors.add(
+                "name",
+                format!("must be at most {} characters", MAX_NAME_LENGTH),
+            );
+        }
+        // the name is a folder name
+        if !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == ' ')
+            || name.starts_with(' ')
+            || name.ends_with(' ')
+        {
+            errors.add(
+                "name",
+                "must only contain letters, digits, spaces, '-' and '_'",
+            );
+        } else if self.dirs.exists(name) {
+            errors.add("name", "is taken by another workspace");
+        }
+        errors.into_result()?;
+
+        tokio::fs::create_dir_all(self.dirs.db_path(name).parent().unwrap()).await?;
+        tokio::fs::create_dir_all(self.dirs.upload_dir(name)).await?;
+        self.open(name).await?;
+        Ok(WorkspaceInfo {
+            name: name.to_string(),
+            active: false,
+        })
+    }
+
+    /// Make a workspace active, and remember it across restarts.
+    ///
+    /// The secrets are locked, since each workspace has its own passphrase.
+    pub async fn switch(&self, name: &str) -> crate::Result<()> {
+        if !self.dirs.exists(name) {
+            return Err(Error::msg(format!("Workspace {} not found", name)));
+        }
+        let db = self.open(name).await?;
+        self.dirs.save_active(name)?;
+        *self.active.write().unwrap() = (name.to_string(), db);
+        secrets::lock();
+        Ok(())
+    }
+
+    async fn open(&self, name: &str) -> crate::Result<&'static PrismaClient> {
+        let mut opened = self.opened.lock().await;
+        if let Some(db) = opened.get(name) {
+            return Ok(*db);
+        }
+        let db: &'static PrismaClient =
+            Box::leak(Box::new(open_db(&self.dirs.db_path(name)).await?));
+        opened.insert(name.to_string(), db);
+        Ok(db)
+    }
+}
+
+/// Connect to the database at `db_path`, creating it if it does not exist, and prepare it.
+///
+/// An existing database is backed up before its schema is migrated, which is when it was last
+/// prepared with another schema than the current one.
+async fn open_db(db_path: &Path) -> crate::Result<PrismaClient> {
+    let stamp_path = schema_stamp_path(db_path);
+    let schema_hash = blake3::hash(SCHEMA.as_bytes()).to_hex().to_string();
+    let stamped = tokio::fs::read_to_string(&stamp_path)
+        .await
+        .map_or(false, |stamp| stamp.trim() == schema_hash);
+    if db_path.exists() && !stamped {
+        let path = db_path.to_path_buf();
+        tokio::task::spawn_blocking(move || -> crate::Result<()> {
+            backup::create(&path, BackupKind::PreMigration)?;
+            backup::rotate(&path, BackupKind::PreMigration, backup::KEEP_PRE_MIGRATION)
+        })
+        .await??;
+    }
+    let url = format!("file:{}", db_path.to_string_lossy());
+    let db = PrismaClient::_builder().with_url(url).build().await?;
+    prepare(&db).await?;
+    if !stamped {
+        tokio::fs::write(&stamp_path, schema_hash).await?;
+    }
+    Ok(db)
+}
+
+fn schema_stamp_path(db_path: &Path) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(SCHEMA_STAMP_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Bring the schema of the database up to date, and prepare what prisma cannot model.
+///
+/// The schema is pushed in debug builds, and migrated in release builds.
+pub async fn prepare(db: &PrismaClient) -> crate::Result<()> {
+    #[cfg(debug_assertions)]
+    db._db_push().accept_data_loss().await?;
+    #[cfg(not(debug_assertions))]
+    db._migrate_deploy().await?;
+
+    crate::core::session_search::prepare_index(db).await
+}
@@ -299,7 +299,7 @@ export const useCollectionStore = defineStore('collections', () => {
   async function clearCollectionIndexes(indexes: CollectionIndexWithAll[]) {
     for (const index of indexes) {
       // remove index from remote vectorstore
-      await deleteIndexFromVectorstore(index.index.vectorDbClient, index.index.vectorDbConfig, index.id);
+      await deleteIndexFromVectorstore(index.id);
 
       // remove sessions and related data of this index from the cache
       await sessionStore.deleteSessionsByIndex(index);
@@ -122,6 +122,7 @@ export const useIndexProfileStore = defineStore('indexProfiles', () => {
       embeddingsConfigId: embeddingsConfig.id,
       vectorDbClientId: vectorDbClient.id,
       vectorDbConfigId: vectorDbConfig.id,
+      rerankerClientId: null,
     } as CreateIndexProfileData;
 
     await ensureCreateIndexProfileData(data, embeddingsClient, vectorDbClient);
@@ -1,41 +1,4 @@
 declare global {
-  interface CreateEmbeddingsClientFormState {
-    name: string;
-    type: string;
-    info: any;
-  }
-
-  interface CreateEmbeddingsConfigFormState {
-    clientType: string;
-    name: string;
-    meta: any;
-  }
-
-  interface CreateVectorDbConfigFormState {
-    clientType: string;
-    name: string;
-    meta: any;
-  }
-
-  interface OpenAIEmbeddingsClientInfo {
-    apiKey: string;
-  }
-
-  interface OpenAIEmbeddingsConfigMeta {
-    dimension: number;
-  }
-
-  interface PineconeVectorstoreClientInfo {
-    apiKey: string;
-    indexName: string;
-    environment: string;
-  }
-
-  interface PineconeVectorstoreConfigMeta {
-    dimension: number;
-    metric: string;
-  }
-
   /**
    * The config of the completion.
    */
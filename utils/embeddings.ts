@@ -1,19 +1,25 @@
-import { OpenAIEmbeddings } from 'langchain/embeddings';
-import { EmbeddingsClient, EmbeddingsConfig } from '~/plugins/tauri/bindings';
+import { Embeddings } from 'langchain/embeddings';
 
-export async function createEmbeddings(client: EmbeddingsClient, config: EmbeddingsConfig) {
-  switch (config.clientType) {
-    case 'openai':
-      const clientInfo = JSON.parse(client.info) as OpenAIEmbeddingsClientInfo;
-      const configMeta = JSON.parse(config.meta) as OpenAIEmbeddingsConfigMeta;
-      if (configMeta.dimension && configMeta.dimension !== 1536) {
-        throw Error(`Not supported dimension for OpenAI embedding: ${configMeta.dimension}`);
-      }
-      return new OpenAIEmbeddings({
-        openAIApiKey: clientInfo.apiKey,
-      });
+/**
+ * Embeddings computed by the backend with the embeddings client of a collection index, so that
+ * the secrets of the client never reach the frontend.
+ */
+export class CollectionIndexEmbeddings extends Embeddings {
+  constructor(public collectionIndexId: string, public sessionId?: number) {
+    super({});
+  }
+
+  async embedDocuments(texts: string[]): Promise<number[][]> {
+    const { $tauriCommands } = useNuxtApp();
+    return await $tauriCommands.embedTextsForCollectionIndex(this.collectionIndexId, texts, this.sessionId ?? null);
+  }
 
-    default:
-      throw Error(`Not supported embeddings client: ${config.clientType}`);
+  async embedQuery(text: string): Promise<number[]> {
+    const [vector] = await this.embedDocuments([text]);
+    return vector;
   }
 }
+
+export function createEmbeddings(collectionIndexId: string, sessionId?: number) {
+  return new CollectionIndexEmbeddings(collectionIndexId, sessionId);
+}
@@ -1,6 +1,5 @@
 import { message } from 'ant-design-vue';
 import { Embeddings } from 'langchain/embeddings';
-import { PineconeStore, VectorStore } from 'langchain/vectorstores';
 import { extname } from 'pathe';
 import { useHash } from '~/composables/useHash';
 import {
@@ -8,14 +7,17 @@ import {
   CreateDocumentData,
   Document,
   DocumentChunk,
+  HashAlgo,
   Splitting,
 } from '~/plugins/tauri/bindings';
 import { CollectionSummarizer } from '~/utils/collectionSummarizers/base';
 import { dbDocumentChunk2Ui, uiDocumentChunks2Db } from '~/utils/db';
 import { DocumentLoader } from '~/utils/documentLoaders/base';
+import { createEmbeddings } from '~/utils/embeddings';
 import { IndexSyncStatus } from '~/utils/indexSyncStatus';
 import { asyncFilter } from '~/utils/itertools';
 import { Tracer } from '~/utils/tracer';
+import { CollectionIndexVectorStore, createVectorstore } from '~/utils/vectorstores';
 
 // noinspection JSUnusedGlobalSymbols
 export class Indexer {
@@ -23,7 +25,7 @@ export class Indexer {
 
   constructor(
     public embeddings: Embeddings,
-    public vectorstore: VectorStore,
+    public vectorstore: CollectionIndexVectorStore,
     public embeddingsConfigId: number,
     public splitting: Splitting,
     public tracer: Tracer,
@@ -40,17 +42,8 @@ export class Indexer {
     loader: DocumentLoader,
     summarizer: CollectionSummarizer,
   ) {
-    const namespace = collectionIndex.id;
-    const embeddings = await createEmbeddings(
-      collectionIndex.index.embeddingsClient,
-      collectionIndex.index.embeddingsConfig,
-    );
-    const vectorstore = await createVectorstore(
-      collectionIndex.index.vectorDbClient,
-      collectionIndex.index.vectorDbConfig,
-      embeddings,
-      namespace,
-    );
+    const embeddings = createEmbeddings(collectionIndex.id);
+    const vectorstore = await createVectorstore(embeddings, collectionIndex.id);
     return new Indexer(
       embeddings,
       vectorstore,
@@ -106,19 +99,9 @@ export class Indexer {
     this.tracer.log(`fetched vectors to delete: ${vectorIds.length}`);
 
     this.tracer.log('deleting vectors from vector database...');
-    if (this.vectorstore instanceof PineconeStore) {
-      await this.vectorstore.pineconeIndex
-        .delete1({
-          ids: vectorIds,
-          namespace: index.id,
-          deleteAll: false,
-        })
-        .catch((e) => {
-          message.warn('Deleting vectors failed: ' + errToString(e));
-        });
-    } else {
-      message.warn('Deleting vectors is not supported for this vector store.');
-    }
+    await this.vectorstore.delete(vectorIds).catch((e) => {
+      message.warn('Deleting vectors failed: ' + errToString(e));
+    });
 
     const deleted = await this.tauriCommands.removeDocumentsFromCollectionIndex(index.id, toDeleted);
     index.indexedDocuments.filter((document) => !toDeleted.includes(document.id));
@@ -198,13 +181,13 @@ export class Indexer {
       { summaryDocument: undefined, documents: [] } as { summaryDocument?: Document; documents: Document[] },
     );
 
-    // generate a new summary and its MD5 hashcode
+    // generate a new summary
     const summary = await this.summarizer.summarize(status.index.collection, documents);
-    const summaryMD5Hashcode = await useHash().hashStrInMd5(summary);
 
     if (summaryDocument) {
-      // there is an existing summary document
-      if (summaryMD5Hashcode == summaryDocument.md5Hash) {
+      // there is an existing summary document, compare by its hash algorithm
+      const summaryHashcode = await useHash().hashStr(summary, summaryDocument.hashAlgo as HashAlgo);
+      if (summaryHashcode == summaryDocument.md5Hash) {
         // the summary has not changed, no need to update.
         return;
       }
@@ -295,15 +278,11 @@ export class Indexer {
    */
   private async uploadEmbeddingVectors(vectors: number[][], chunks: DocumentChunk[]) {
     this.tracer.log('Storing vectors into vectorstore...');
-    if (this.vectorstore instanceof PineconeStore) {
-      await this.vectorstore.addVectors(
-        vectors,
-        chunks.map(dbDocumentChunk2Ui),
-        chunks.map((e) => e.md5Hash),
-      );
-    } else {
-      await this.vectorstore.addVectors(vectors, chunks.map(dbDocumentChunk2Ui));
-    }
+    await this.vectorstore.addVectors(
+      vectors,
+      chunks.map(dbDocumentChunk2Ui),
+      chunks.map((e) => e.md5Hash),
+    );
     this.tracer.log('store done');
   }
 
@@ -0,0 +1,63 @@
+import { listen } from '@tauri-apps/api/event';
+import { message } from 'ant-design-vue';
+import { DocumentChange } from '~/plugins/tauri/bindings';
+import { BasicCollectionSummarizer } from '~/utils/collectionSummarizers/basic';
+import { BasicDocumentLoader } from '~/utils/documentLoaders/factory';
+import { Indexer } from '~/utils/indexer';
+import { IndexSyncStatus } from '~/utils/indexSyncStatus';
+import { NestedStepTracer } from '~/utils/tracer';
+
+/**
+ * Event emitted by the backend whenever the source file of a linked document changes.
+ */
+const DOCUMENT_CHANGED_EVENT = 'document://changed';
+
+/**
+ * Re-index the collection indexes of the linked documents whenever their source files change.
+ *
+ * The linked documents are synced once listening, which catches up with the changes made while
+ * the app was not running. The indexes are synced one at a time, so that changes arriving in a
+ * row never sync the same index concurrently.
+ *
+ * @returns A function to stop listening.
+ */
+export async function listenToLinkedDocumentChanges() {
+  const { $tauriCommands } = useNuxtApp();
+  let syncing = Promise.resolve();
+  const unlisten = await listen<DocumentChange>(DOCUMENT_CHANGED_EVENT, (event) => {
+    const { document, indexIds } = event.payload;
+    for (const indexId of indexIds) {
+      syncing = syncing
+        .then(() => syncCollectionIndex(indexId))
+        .catch((e) => {
+          message.error(`Failed to re-index ${document.filename}: ${errToString(e)}`);
+        });
+    }
+  });
+  await $tauriCommands.syncLinkedDocuments().catch((e) => {
+    message.error(`Failed to sync linked documents: ${errToString(e)}`);
+  });
+  return unlisten;
+}
+
+/**
+ * Sync a collection index with the documents of its collection, splitting and indexing the ones
+ * changed and deleting the vectors of the ones replaced.
+ *
+ * @param collectionIndexId
+ */
+async function syncCollectionIndex(collectionIndexId: string) {
+  const { $tauriCommands } = useNuxtApp();
+  const index = await $tauriCommands.getCollectionIndexByIdWithAll(collectionIndexId);
+  if (!index) return;
+
+  const documents = await $tauriCommands.getDocumentsByCollectionIndexId(index.id);
+  const status = IndexSyncStatus.compute(documents, index);
+  if (status.clean) return;
+
+  const tracer = new NestedStepTracer();
+  tracer.start();
+  const indexer = await Indexer.create(index, tracer, new BasicDocumentLoader(), new BasicCollectionSummarizer());
+  await indexer.sync(status, index);
+  tracer.finish();
+}
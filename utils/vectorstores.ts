@@ -1,74 +1,74 @@
+import { Document as DocumentPart } from 'langchain/docstore';
 import { Embeddings } from 'langchain/embeddings';
-import { PineconeStore, VectorStore } from 'langchain/vectorstores';
-import { VectorDbClient, VectorDbConfig } from '~/plugins/tauri/bindings';
-import { CrossPineconeClient } from '~/utils/pinecone';
+import { VectorStore } from 'langchain/vectorstores';
 
-export async function createVectorstore(
-  vectorstoreClient: VectorDbClient,
-  vectorstoreConfig: VectorDbConfig,
-  embeddings: Embeddings,
-  namespace: string,
-): Promise<VectorStore> {
-  switch (vectorstoreConfig.clientType) {
-    case 'pinecone':
-      const clientInfo = JSON.parse(vectorstoreClient.info) as PineconeVectorstoreClientInfo;
-      const configMeta = JSON.parse(vectorstoreConfig.meta) as PineconeVectorstoreConfigMeta;
+/**
+ * The key of the metadata holding the content of a vector, the same as the one used by the
+ * vectors uploaded before indexing went through the backend.
+ */
+const TEXT_KEY = 'text';
+
+/**
+ * A vector store of the namespace of a collection index, kept by the backend with the vector
+ * db client of the index, so that the secrets of the client never reach the frontend.
+ */
+export class CollectionIndexVectorStore extends VectorStore {
+  constructor(embeddings: Embeddings, public collectionIndexId: string) {
+    super(embeddings, {});
+  }
 
-      const client = new CrossPineconeClient();
-      await client.init({
-        environment: clientInfo.environment,
-        apiKey: clientInfo.apiKey,
-      });
+  async addVectors(vectors: number[][], documents: DocumentPart[], ids?: string[]): Promise<void> {
+    const { $tauriCommands } = useNuxtApp();
+    await $tauriCommands.upsertCollectionIndexVectors(
+      this.collectionIndexId,
+      vectors.map((values, i) => ({
+        id: ids?.[i] ?? crypto.randomUUID(),
+        values,
+        metadata: { ...documents[i].metadata, [TEXT_KEY]: documents[i].pageContent },
+      })),
+    );
+  }
 
-      // Create index if not exists
-      if ((await client.listIndexes()).indexOf(clientInfo.indexName) === -1) {
-        await client.createIndex({
-          createRequest: {
-            name: clientInfo.indexName,
-            metric: configMeta.metric,
-            dimension: configMeta.dimension,
-          },
-        });
-      }
+  async addDocuments(documents: DocumentPart[], ids?: string[]): Promise<void> {
+    const vectors = await this.embeddings.embedDocuments(documents.map((document) => document.pageContent));
+    await this.addVectors(vectors, documents, ids);
+  }
 
-      const index = client.Index(clientInfo.indexName);
-      return await PineconeStore.fromExistingIndex(embeddings, {
-        pineconeIndex: index,
-        namespace: namespace,
-        textKey: 'text',
-      });
+  async similaritySearchVectorWithScore(query: number[], k: number): Promise<[DocumentPart, number][]> {
+    const { $tauriCommands } = useNuxtApp();
+    const matches = await $tauriCommands.queryCollectionIndexVectors(this.collectionIndexId, query, k);
+    return matches.map((match) => {
+      const { [TEXT_KEY]: pageContent, ...metadata } = (match.metadata ?? {}) as Record<string, any>;
+      return [new DocumentPart({ pageContent: pageContent ?? '', metadata }), match.score];
+    });
+  }
 
-    default:
-      throw Error(`Not supported client: ${vectorstoreConfig.clientType}`);
+  /**
+   * Delete the vectors of the given ids, or all the vectors of the collection index if no ids
+   * are given.
+   */
+  async delete(ids?: string[]) {
+    const { $tauriCommands } = useNuxtApp();
+    await $tauriCommands.deleteCollectionIndexVectors(this.collectionIndexId, ids ?? null);
   }
 }
 
+/**
+ * Create the vector store of a collection index, creating its vector index first if it does not
+ * exist yet.
+ */
+export async function createVectorstore(embeddings: Embeddings, collectionIndexId: string) {
+  const { $tauriCommands } = useNuxtApp();
+  await $tauriCommands.prepareCollectionIndexVectors(collectionIndexId);
+  return new CollectionIndexVectorStore(embeddings, collectionIndexId);
+}
+
 /**
  * Delete the vectors for indexing from vectorstore
  *
- * @param vectorstoreClient
- * @param vectorstoreConfig
- * @param namespace
+ * @param collectionIndexId
  */
-export async function deleteIndexFromVectorstore(
-  vectorstoreClient: VectorDbClient,
-  vectorstoreConfig: VectorDbConfig,
-  namespace: string,
-) {
-  const clientInfo = JSON.parse(vectorstoreClient.info);
-
-  switch (vectorstoreConfig.clientType) {
-    case 'pinecone':
-      const client = new CrossPineconeClient();
-      await client.init({
-        environment: clientInfo.environment,
-        apiKey: clientInfo.apiKey,
-      });
-
-      const index = client.Index(clientInfo.indexName);
-      await index.delete1({
-        namespace: namespace,
-        deleteAll: true,
-      });
-  }
+export async function deleteIndexFromVectorstore(collectionIndexId: string) {
+  const { $tauriCommands } = useNuxtApp();
+  await $tauriCommands.deleteCollectionIndexVectors(collectionIndexId, null);
 }